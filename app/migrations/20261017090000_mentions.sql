@@ -0,0 +1,13 @@
+-- Mentions table: one row per user mentioned in a post or comment
+CREATE TABLE mentions (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    mentioned_user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    author_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    comment_id UUID REFERENCES comments(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_mentions_mentioned_user_id ON mentions(mentioned_user_id, created_at DESC);
+CREATE INDEX idx_mentions_post_id ON mentions(post_id);
+CREATE INDEX idx_mentions_comment_id ON mentions(comment_id);
@@ -0,0 +1,10 @@
+-- Engagement score used to rank trending content: recent likes plus
+-- recent comments, with comments weighted double
+CREATE OR REPLACE FUNCTION post_engagement_score(target_post_id UUID, since TIMESTAMP WITH TIME ZONE)
+RETURNS BIGINT AS $$
+    SELECT (SELECT COUNT(*) FROM likes WHERE post_id = target_post_id AND created_at >= since)
+         + 2 * (SELECT COUNT(*) FROM comments WHERE post_id = target_post_id AND created_at >= since)
+$$ LANGUAGE SQL STABLE;
+
+CREATE INDEX idx_likes_post_id_created_at ON likes(post_id, created_at);
+CREATE INDEX idx_comments_post_id_created_at ON comments(post_id, created_at);
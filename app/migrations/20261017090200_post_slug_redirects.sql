@@ -0,0 +1,9 @@
+-- Previous slugs of posts, kept so old links can be redirected
+CREATE TABLE post_slug_redirects (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    old_slug VARCHAR(255) UNIQUE NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_post_slug_redirects_post_id ON post_slug_redirects(post_id);
@@ -0,0 +1,4 @@
+-- When set on an unpublished post, the time it is scheduled to go live
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_posts_publish_at ON posts(publish_at) WHERE is_published = false AND publish_at IS NOT NULL;
@@ -0,0 +1,8 @@
+-- Set while an account is self-deactivated; the user and their content are
+-- hidden from everyone else until they reactivate
+ALTER TABLE users ADD COLUMN deactivated_at TIMESTAMP WITH TIME ZONE;
+
+-- Tokens issued before this time are rejected
+ALTER TABLE users ADD COLUMN tokens_revoked_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_users_deactivated_at ON users(deactivated_at) WHERE deactivated_at IS NOT NULL;
@@ -0,0 +1,4 @@
+-- Set when a post is soft-deleted; such posts are hidden from every query
+ALTER TABLE posts ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_posts_stale_drafts ON posts(updated_at) WHERE is_published = false AND deleted_at IS NULL;
@@ -0,0 +1,4 @@
+-- ISO 639-1 code, set by the author or detected from the content
+ALTER TABLE posts ADD COLUMN language VARCHAR(10);
+
+CREATE INDEX idx_posts_language ON posts(language) WHERE is_published = true;
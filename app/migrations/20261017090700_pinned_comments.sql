@@ -0,0 +1,5 @@
+-- Comments the post's author pinned to the top of the discussion
+ALTER TABLE comments ADD COLUMN pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- At most one pinned comment per post
+CREATE UNIQUE INDEX idx_comments_pinned_per_post ON comments(post_id) WHERE pinned;
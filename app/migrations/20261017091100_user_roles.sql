@@ -0,0 +1,6 @@
+-- Permission level of an account: user < moderator < admin
+ALTER TABLE users ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'user'
+    CHECK (role IN ('user', 'moderator', 'admin'));
+
+-- Set while an account is banned by an admin; banned users can't sign in
+ALTER TABLE users ADD COLUMN banned_at TIMESTAMP WITH TIME ZONE;
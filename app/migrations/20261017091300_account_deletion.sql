@@ -0,0 +1,5 @@
+-- When a self-deleted account gets purged. The account stays deactivated
+-- until then and reactivating it cancels the deletion.
+ALTER TABLE users ADD COLUMN deletion_scheduled_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_users_deletion_scheduled_at ON users(deletion_scheduled_at) WHERE deletion_scheduled_at IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Hash of the client a refresh token was issued to; tokens from before this
+-- column existed stay unbound until they are rotated
+ALTER TABLE refresh_tokens ADD COLUMN client_fingerprint VARCHAR(64);
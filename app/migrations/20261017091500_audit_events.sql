@@ -0,0 +1,16 @@
+-- Append-only record of security-sensitive actions. Actors and targets are
+-- kept as plain ids so events outlive the rows they refer to.
+CREATE TABLE audit_events (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    action VARCHAR(50) NOT NULL,
+    actor_id UUID,
+    target_type VARCHAR(20),
+    target_id UUID,
+    ip_address VARCHAR(45),
+    metadata JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_events_created_at ON audit_events(created_at DESC);
+CREATE INDEX idx_audit_events_actor_id ON audit_events(actor_id);
+CREATE INDEX idx_audit_events_action ON audit_events(action);
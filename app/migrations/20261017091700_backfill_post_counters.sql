@@ -0,0 +1,4 @@
+-- Counts for likes and comments made before the counter columns existed
+UPDATE posts p SET
+    like_count = (SELECT COUNT(*) FROM likes l WHERE l.post_id = p.id),
+    comment_count = (SELECT COUNT(*) FROM comments c WHERE c.post_id = p.id);
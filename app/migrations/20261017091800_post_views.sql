@@ -0,0 +1,11 @@
+-- One row per counted view. Repeat views by the same viewer within the
+-- dedup window are dropped before they get here. `viewer_key` is the user id
+-- for signed-in viewers and a hash of the client address otherwise.
+CREATE TABLE post_views (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    viewer_key VARCHAR(80) NOT NULL,
+    viewed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_post_views_post_id_viewed_at ON post_views(post_id, viewed_at);
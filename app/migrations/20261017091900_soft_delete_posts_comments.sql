@@ -0,0 +1,32 @@
+-- Posts and comments are soft-deleted. deleted_by records who removed a
+-- post, so posts taken down by a moderator can't be restored by their author.
+ALTER TABLE posts ADD COLUMN deleted_by UUID REFERENCES users(id) ON DELETE SET NULL;
+ALTER TABLE comments ADD COLUMN deleted_at TIMESTAMP WITH TIME ZONE;
+
+CREATE INDEX idx_posts_deleted_at ON posts(deleted_at) WHERE deleted_at IS NOT NULL;
+
+-- Deleted comments stay in place as placeholders but no longer count towards
+-- posts.comment_count
+CREATE OR REPLACE FUNCTION update_post_comment_count() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        IF NEW.deleted_at IS NULL THEN
+            UPDATE posts SET comment_count = comment_count + 1 WHERE id = NEW.post_id;
+        END IF;
+    ELSIF TG_OP = 'DELETE' THEN
+        IF OLD.deleted_at IS NULL THEN
+            UPDATE posts SET comment_count = comment_count - 1 WHERE id = OLD.post_id;
+        END IF;
+    ELSIF OLD.deleted_at IS NULL AND NEW.deleted_at IS NOT NULL THEN
+        UPDATE posts SET comment_count = comment_count - 1 WHERE id = NEW.post_id;
+    ELSIF OLD.deleted_at IS NOT NULL AND NEW.deleted_at IS NULL THEN
+        UPDATE posts SET comment_count = comment_count + 1 WHERE id = NEW.post_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER comments_update_post_comment_count ON comments;
+CREATE TRIGGER comments_update_post_comment_count
+    AFTER INSERT OR DELETE OR UPDATE OF deleted_at ON comments
+    FOR EACH ROW EXECUTE FUNCTION update_post_comment_count();
@@ -0,0 +1,13 @@
+-- Resized copies of uploaded images, generated in the background after an
+-- upload. A missing row means the variant isn't ready yet.
+CREATE TABLE media_variants (
+    media_id UUID NOT NULL REFERENCES media(id) ON DELETE CASCADE,
+    name VARCHAR(20) NOT NULL,
+    storage_key VARCHAR(255) NOT NULL UNIQUE,
+    content_type VARCHAR(100) NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (media_id, name)
+);
@@ -0,0 +1,18 @@
+-- Domain events written in the same transaction as the change they describe
+-- and delivered to subscribers by jobs::outbox. delivered_to lists the
+-- subscribers that have handled the event, so a retry skips them.
+CREATE TABLE outbox_events (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    event_type VARCHAR(50) NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    delivered_to TEXT[] NOT NULL DEFAULT '{}',
+    last_error TEXT,
+    available_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    dispatched_at TIMESTAMP WITH TIME ZONE,
+    failed_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_outbox_events_pending ON outbox_events(available_at)
+    WHERE dispatched_at IS NULL AND failed_at IS NULL;
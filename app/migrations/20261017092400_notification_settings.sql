@@ -0,0 +1,12 @@
+-- Per-user notification preferences. channels only holds what the user has
+-- changed, as {"email": {"follow": false}}; anything missing uses the default.
+CREATE TABLE notification_settings (
+    user_id UUID PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    channels JSONB NOT NULL DEFAULT '{}',
+    digest_frequency VARCHAR(10) NOT NULL DEFAULT 'off',
+    last_digest_at TIMESTAMP WITH TIME ZONE,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_notification_settings_digest ON notification_settings(digest_frequency)
+    WHERE digest_frequency <> 'off';
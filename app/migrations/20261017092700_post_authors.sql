@@ -0,0 +1,12 @@
+-- Co-authors of a post, besides posts.author_id. An invitation is pending
+-- until the invitee accepts it.
+CREATE TABLE post_authors (
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    PRIMARY KEY (post_id, user_id)
+);
+
+CREATE INDEX idx_post_authors_user_id ON post_authors(user_id);
@@ -0,0 +1,28 @@
+-- Likes on comments, counted on the comment like posts.like_count so threads
+-- can be sorted by them
+ALTER TABLE comments ADD COLUMN like_count BIGINT NOT NULL DEFAULT 0;
+
+CREATE TABLE comment_likes (
+    comment_id UUID NOT NULL REFERENCES comments(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (comment_id, user_id)
+);
+
+CREATE INDEX idx_comment_likes_user_id ON comment_likes(user_id);
+CREATE INDEX idx_comments_post_like_count ON comments(post_id, like_count DESC);
+
+CREATE OR REPLACE FUNCTION update_comment_like_count() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_OP = 'INSERT' THEN
+        UPDATE comments SET like_count = like_count + 1 WHERE id = NEW.comment_id;
+    ELSE
+        UPDATE comments SET like_count = like_count - 1 WHERE id = OLD.comment_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER comment_likes_update_comment_like_count
+    AFTER INSERT OR DELETE ON comment_likes
+    FOR EACH ROW EXECUTE FUNCTION update_comment_like_count();
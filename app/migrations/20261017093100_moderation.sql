@@ -0,0 +1,6 @@
+-- Comments hidden by a moderator stay visible to their author only
+ALTER TABLE comments ADD COLUMN hidden_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE comments ADD COLUMN hidden_by UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- A suspended account can't sign in or make requests until this time
+ALTER TABLE users ADD COLUMN suspended_until TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,22 @@
+-- Trending scores per period ('24h' or '7d'), rebuilt periodically by
+-- jobs::trending from recent likes, comments and views
+CREATE TABLE trending_posts (
+    period VARCHAR(3) NOT NULL,
+    post_id UUID NOT NULL REFERENCES posts(id) ON DELETE CASCADE,
+    score DOUBLE PRECISION NOT NULL,
+    computed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (period, post_id)
+);
+
+CREATE INDEX idx_trending_posts_period_score ON trending_posts(period, score DESC);
+
+CREATE TABLE trending_tags (
+    period VARCHAR(3) NOT NULL,
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    score DOUBLE PRECISION NOT NULL,
+    post_count BIGINT NOT NULL,
+    computed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    PRIMARY KEY (period, tag_id)
+);
+
+CREATE INDEX idx_trending_tags_period_score ON trending_tags(period, score DESC);
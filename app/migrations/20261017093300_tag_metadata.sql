@@ -0,0 +1,25 @@
+-- Tags get a URL slug and optional presentation fields, edited by admins
+ALTER TABLE tags ADD COLUMN slug VARCHAR(60);
+ALTER TABLE tags ADD COLUMN description TEXT;
+ALTER TABLE tags ADD COLUMN color VARCHAR(7);
+ALTER TABLE tags ADD COLUMN cover_image VARCHAR(500);
+ALTER TABLE tags ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW();
+
+-- Existing tags are slugged from their names; names that slug the same get
+-- -2, -3, ... in order of creation
+WITH base AS (
+    SELECT id, created_at,
+           COALESCE(NULLIF(trim(both '-' from regexp_replace(lower(name), '[^a-z0-9]+', '-', 'g')), ''), 'tag') as slug
+    FROM tags
+),
+numbered AS (
+    SELECT id, slug, row_number() OVER (PARTITION BY slug ORDER BY created_at, id) as n
+    FROM base
+)
+UPDATE tags t
+SET slug = CASE WHEN numbered.n = 1 THEN numbered.slug ELSE numbered.slug || '-' || numbered.n END
+FROM numbered
+WHERE t.id = numbered.id;
+
+ALTER TABLE tags ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE tags ADD CONSTRAINT tags_slug_key UNIQUE (slug);
@@ -0,0 +1,11 @@
+-- Alternative names that resolve to a tag: defined by admins, or left behind
+-- when a tag is merged into another. Matched case-insensitively.
+CREATE TABLE tag_synonyms (
+    name VARCHAR(50) PRIMARY KEY,
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_tag_synonyms_lower_name ON tag_synonyms(LOWER(name));
+CREATE INDEX idx_tag_synonyms_tag_id ON tag_synonyms(tag_id);
@@ -0,0 +1,46 @@
+-- Tag names are now stored lowercase with whitespace runs turned into
+-- hyphens. Tags that normalize to the same name are folded into the oldest
+-- one, keeping all of their posts and synonyms.
+CREATE TEMPORARY TABLE tag_normalization AS
+SELECT id,
+       lower(regexp_replace(btrim(name), '\s+', '-', 'g')) AS normalized,
+       first_value(id) OVER (
+           PARTITION BY lower(regexp_replace(btrim(name), '\s+', '-', 'g'))
+           ORDER BY created_at, id
+       ) AS keeper_id
+FROM tags;
+
+INSERT INTO post_tags (post_id, tag_id)
+SELECT pt.post_id, n.keeper_id
+FROM post_tags pt
+INNER JOIN tag_normalization n ON pt.tag_id = n.id
+WHERE n.id <> n.keeper_id
+ON CONFLICT DO NOTHING;
+
+UPDATE tag_synonyms s SET tag_id = n.keeper_id
+FROM tag_normalization n
+WHERE s.tag_id = n.id AND n.id <> n.keeper_id;
+
+DELETE FROM tags t
+USING tag_normalization n
+WHERE t.id = n.id AND n.id <> n.keeper_id;
+
+UPDATE tags t SET name = n.normalized
+FROM tag_normalization n
+WHERE t.id = n.id AND t.name <> n.normalized;
+
+DROP TABLE tag_normalization;
+
+-- Synonyms are normalized the same way; ones that now name a tag, or
+-- duplicate an older synonym, are dropped
+DELETE FROM tag_synonyms s
+WHERE EXISTS (
+    SELECT 1 FROM tags t WHERE t.name = lower(regexp_replace(btrim(s.name), '\s+', '-', 'g'))
+) OR EXISTS (
+    SELECT 1 FROM tag_synonyms o
+    WHERE lower(regexp_replace(btrim(o.name), '\s+', '-', 'g')) = lower(regexp_replace(btrim(s.name), '\s+', '-', 'g'))
+      AND (o.created_at, o.name) < (s.created_at, s.name)
+);
+
+UPDATE tag_synonyms SET name = lower(regexp_replace(btrim(name), '\s+', '-', 'g'))
+WHERE name <> lower(regexp_replace(btrim(name), '\s+', '-', 'g'));
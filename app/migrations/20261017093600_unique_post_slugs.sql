@@ -0,0 +1,11 @@
+-- Posts created with the same title shared a slug. The oldest keeps it and
+-- the others get a suffix from their id, so slugs can be made unique.
+UPDATE posts p SET slug = left(p.slug, 246) || '-' || left(replace(p.id::text, '-', ''), 8)
+FROM (
+    SELECT id, row_number() OVER (PARTITION BY slug ORDER BY created_at, id) AS position
+    FROM posts
+) ranked
+WHERE p.id = ranked.id AND ranked.position > 1;
+
+DROP INDEX idx_posts_slug;
+CREATE UNIQUE INDEX idx_posts_slug ON posts(slug);
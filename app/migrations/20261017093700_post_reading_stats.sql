@@ -0,0 +1,8 @@
+-- Computed from the markdown's text when a post is written. Existing posts
+-- get an estimate that counts markdown syntax as words, corrected the next
+-- time their content is edited.
+ALTER TABLE posts ADD COLUMN word_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE posts ADD COLUMN reading_time_minutes INTEGER NOT NULL DEFAULT 1;
+
+UPDATE posts SET word_count = (SELECT COUNT(*) FROM regexp_matches(content, '\S+', 'g'));
+UPDATE posts SET reading_time_minutes = GREATEST(1, CEIL(word_count / 200.0));
@@ -0,0 +1,6 @@
+-- Reports can be claimed for review before they are resolved, and carry the
+-- moderator's note on how they were handled
+ALTER TABLE reports DROP CONSTRAINT IF EXISTS reports_status_check;
+ALTER TABLE reports ADD CONSTRAINT reports_status_check
+    CHECK (status IN ('open', 'reviewing', 'resolved', 'dismissed'));
+ALTER TABLE reports ADD COLUMN IF NOT EXISTS resolution_note TEXT;
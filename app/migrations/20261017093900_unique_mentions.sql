@@ -0,0 +1,11 @@
+-- A user is mentioned at most once per post body or comment, so edits can
+-- keep existing mentions and only add the new ones
+DELETE FROM mentions m
+USING mentions older
+WHERE m.post_id = older.post_id
+  AND m.comment_id IS NOT DISTINCT FROM older.comment_id
+  AND m.mentioned_user_id = older.mentioned_user_id
+  AND (m.created_at, m.id) > (older.created_at, older.id);
+
+CREATE UNIQUE INDEX idx_mentions_post_body_user ON mentions(post_id, mentioned_user_id) WHERE comment_id IS NULL;
+CREATE UNIQUE INDEX idx_mentions_comment_user ON mentions(comment_id, mentioned_user_id) WHERE comment_id IS NOT NULL;
@@ -0,0 +1,9 @@
+-- Maintenance mode, shared by every replica. Holds a single row.
+CREATE TABLE maintenance_mode (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    enabled BOOLEAN NOT NULL DEFAULT false,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO maintenance_mode (id, enabled) VALUES (true, false);
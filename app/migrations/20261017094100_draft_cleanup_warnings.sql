@@ -0,0 +1,4 @@
+-- When the author was last warned that a draft is about to be cleaned up.
+-- A draft is only removed once a warning newer than its last edit is old
+-- enough.
+ALTER TABLE posts ADD COLUMN cleanup_warned_at TIMESTAMPTZ;
@@ -0,0 +1,4 @@
+-- The row is now created by the first replica to start, from its
+-- MAINTENANCE_MODE, or by the first switch through the API. Drop the default
+-- one if nobody has changed it, so the next start can seed it.
+DELETE FROM maintenance_mode WHERE NOT enabled AND updated_by IS NULL;
@@ -0,0 +1,8 @@
+-- Deleted and hidden comments no longer count towards engagement, as in the
+-- trending job
+CREATE OR REPLACE FUNCTION post_engagement_score(target_post_id UUID, since TIMESTAMP WITH TIME ZONE)
+RETURNS BIGINT AS $$
+    SELECT (SELECT COUNT(*) FROM likes WHERE post_id = target_post_id AND created_at >= since)
+         + 2 * (SELECT COUNT(*) FROM comments
+                WHERE post_id = target_post_id AND created_at >= since AND deleted_at IS NULL AND hidden_at IS NULL)
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,129 @@
+use actix_web::HttpRequest;
+use chrono::Utc;
+use serde_json::Value;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::middleware::client_ip::client_ip;
+
+/// Security-sensitive actions recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditAction {
+    Login,
+    LoginFailed,
+    PasswordChanged,
+    PasswordReset,
+    RoleChanged,
+    PostDeleted,
+    CommentDeleted,
+    UserBanned,
+    UserUnbanned,
+    MaintenanceChanged,
+    ReportResolved,
+    ReportUpdated,
+    PostUnpublished,
+    CommentHidden,
+    CommentUnhidden,
+    UserSuspended,
+    UserUnsuspended,
+    TagMerged,
+    TagSynonymCreated,
+    TagSynonymDeleted,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Login => "login",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::PasswordReset => "password_reset",
+            AuditAction::RoleChanged => "role_changed",
+            AuditAction::PostDeleted => "post_deleted",
+            AuditAction::CommentDeleted => "comment_deleted",
+            AuditAction::UserBanned => "user_banned",
+            AuditAction::UserUnbanned => "user_unbanned",
+            AuditAction::MaintenanceChanged => "maintenance_changed",
+            AuditAction::ReportResolved => "report_resolved",
+            AuditAction::ReportUpdated => "report_updated",
+            AuditAction::PostUnpublished => "post_unpublished",
+            AuditAction::CommentHidden => "comment_hidden",
+            AuditAction::CommentUnhidden => "comment_unhidden",
+            AuditAction::UserSuspended => "user_suspended",
+            AuditAction::UserUnsuspended => "user_unsuspended",
+            AuditAction::TagMerged => "tag_merged",
+            AuditAction::TagSynonymCreated => "tag_synonym_created",
+            AuditAction::TagSynonymDeleted => "tag_synonym_deleted",
+        }
+    }
+}
+
+/// One entry for the audit log, built up from the action that happened.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    action: AuditAction,
+    actor_id: Option<Uuid>,
+    target: Option<(&'static str, Uuid)>,
+    metadata: Value,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            action,
+            actor_id: None,
+            target: None,
+            metadata: Value::Object(Default::default()),
+        }
+    }
+
+    pub fn actor(mut self, actor_id: Uuid) -> Self {
+        self.actor_id = Some(actor_id);
+        self
+    }
+
+    pub fn target(mut self, target_type: &'static str, target_id: Uuid) -> Self {
+        self.target = Some((target_type, target_id));
+        self
+    }
+
+    pub fn metadata(mut self, metadata: Value) -> Self {
+        self.metadata = metadata;
+        self
+    }
+}
+
+/// Writes audit events. Registered as `web::Data<AuditLogger>`.
+pub struct AuditLogger {
+    pool: PgPool,
+}
+
+impl AuditLogger {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    // Failing to write an event is logged but never fails the action itself
+    pub async fn record(&self, req: &HttpRequest, event: AuditEvent) {
+        let (target_type, target_id) = event.target.unzip();
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO audit_events (action, actor_id, target_type, target_id, ip_address, metadata, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            event.action.as_str(),
+            event.actor_id,
+            target_type,
+            target_id,
+            client_ip(req).map(|ip| ip.to_string()),
+            event.metadata,
+            Utc::now()
+        )
+        .execute(&self.pool)
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to record audit event {}: {:?}", event.action.as_str(), e);
+        }
+    }
+}
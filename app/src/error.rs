@@ -0,0 +1,162 @@
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpResponse, ResponseError};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use thiserror::Error;
+use validator::ValidationErrors;
+
+use crate::utils::validation::{field_errors, FieldError};
+
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Error type for handlers. Rendered as an RFC 7807 `application/problem+json`
+/// document whose `code` member is stable for clients to match on.
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("{0}")]
+    BadRequest(String),
+    /// Reported with an `errors` member mapping each field to its failures
+    #[error("Request validation failed")]
+    Validation(#[from] ValidationErrors),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    /// A conflict that also reports the resource's current state, so the
+    /// client can reconcile without refetching
+    #[error("{message}")]
+    ConflictWithState { message: String, current: Value },
+    /// An error with its own code, e.g. `EMAIL_NOT_VERIFIED`
+    #[error("{message}")]
+    Coded {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+    },
+    /// A temporary refusal; sent with a `Retry-After` header
+    #[error("{message}")]
+    RetryLater {
+        status: StatusCode,
+        code: &'static str,
+        message: String,
+        retry_after: u64,
+    },
+    #[error("Database error")]
+    Database(#[from] sqlx::Error),
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(error: serde_json::Error) -> Self {
+        AppError::Internal(format!("Failed to serialize response: {}", error))
+    }
+}
+
+impl AppError {
+    pub fn coded(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        AppError::Coded {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn conflict_with_state(message: impl Into<String>, current: impl Serialize) -> Self {
+        AppError::ConflictWithState {
+            message: message.into(),
+            current: serde_json::to_value(current).unwrap_or_default(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::BadRequest(_) => "BAD_REQUEST",
+            AppError::Validation(_) => "VALIDATION_ERROR",
+            AppError::Unauthorized(_) => "UNAUTHORIZED",
+            AppError::Forbidden(_) => "FORBIDDEN",
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::Conflict(_) | AppError::ConflictWithState { .. } => "CONFLICT",
+            AppError::Coded { code, .. } | AppError::RetryLater { code, .. } => code,
+            AppError::Database(_) | AppError::Internal(_) => "INTERNAL_ERROR",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: &'static str,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current: Option<&'a Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    errors: Option<BTreeMap<String, Vec<FieldError>>>,
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) | AppError::Validation(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) | AppError::ConflictWithState { .. } => StatusCode::CONFLICT,
+            AppError::Coded { status, .. } | AppError::RetryLater { status, .. } => *status,
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if let AppError::Database(e) = self {
+            log::error!("Database error: {:?}", e);
+        }
+
+        let status = self.status_code();
+        let problem = Problem {
+            problem_type: "about:blank",
+            title: status.canonical_reason().unwrap_or("Error"),
+            status: status.as_u16(),
+            detail: self.to_string(),
+            code: self.code(),
+            current: match self {
+                AppError::ConflictWithState { current, .. } => Some(current),
+                _ => None,
+            },
+            errors: match self {
+                AppError::Validation(errors) => Some(field_errors(errors)),
+                _ => None,
+            },
+        };
+
+        let mut response = HttpResponse::build(status);
+        if let AppError::RetryLater { retry_after, .. } = self {
+            response.insert_header((header::RETRY_AFTER, retry_after.to_string()));
+        }
+        response
+            .content_type(PROBLEM_JSON)
+            .body(serde_json::to_string(&problem).unwrap_or_default())
+    }
+}
+
+// Malformed bodies, queries and path segments are rejected by the extractors
+// before a handler runs; these report them as problem documents too
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default().error_handler(|err, _| AppError::BadRequest(err.to_string()).into())
+}
+
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|err, _| AppError::BadRequest(err.to_string()).into())
+}
+
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|err, _| AppError::NotFound(err.to_string()).into())
+}
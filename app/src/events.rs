@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Events claimed by one dispatch pass
+pub const BATCH_SIZE: i64 = 50;
+// Failed deliveries are retried with exponential backoff, up to this many times
+const MAX_ATTEMPTS: i32 = 10;
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+/// Something that happened, recorded with `emit` in the transaction that
+/// made the change so subscribers hear about exactly the committed changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum DomainEvent {
+    #[serde(rename = "post.published")]
+    PostPublished { post_id: Uuid, author_id: Uuid },
+    #[serde(rename = "comment.created")]
+    CommentCreated {
+        comment_id: Uuid,
+        post_id: Uuid,
+        author_id: Uuid,
+        post_author_id: Uuid,
+        parent_author_id: Option<Uuid>,
+        // Users the comment mentions
+        mentioned_user_ids: Vec<Uuid>,
+    },
+    #[serde(rename = "user.followed")]
+    UserFollowed { follower_id: Uuid, following_id: Uuid },
+    // Not offered to webhooks; only the author is told
+    #[serde(rename = "post.draft_expiring")]
+    DraftExpiring {
+        post_id: Uuid,
+        author_id: Uuid,
+        // The earliest the cleanup job will delete it
+        deletes_at: DateTime<Utc>,
+    },
+}
+
+impl DomainEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::PostPublished { .. } => "post.published",
+            DomainEvent::CommentCreated { .. } => "comment.created",
+            DomainEvent::UserFollowed { .. } => "user.followed",
+            DomainEvent::DraftExpiring { .. } => "post.draft_expiring",
+        }
+    }
+}
+
+/// Adds `event` to the outbox. Pass the transaction making the change the
+/// event describes.
+pub async fn emit(conn: &mut PgConnection, event: &DomainEvent) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO outbox_events (event_type, payload) VALUES ($1, $2)",
+        event.event_type(),
+        Json(event) as _
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Receives every dispatched event. Delivery is at least once: an event is
+/// retried until each subscriber has handled it, and one that fails after
+/// handling it may see it again, so handlers must tolerate repeats; `event_id`
+/// is the same each time.
+#[async_trait]
+pub trait Subscriber: Send + Sync {
+    /// Recorded against each event the subscriber has handled; must not change.
+    fn name(&self) -> &'static str;
+
+    async fn handle(&self, event_id: Uuid, event: &DomainEvent) -> Result<(), String>;
+}
+
+/// Delivers outbox events to subscribers; run by `jobs::outbox`.
+pub struct Dispatcher {
+    subscribers: Vec<Arc<dyn Subscriber>>,
+}
+
+impl Dispatcher {
+    pub fn new(subscribers: Vec<Arc<dyn Subscriber>>) -> Self {
+        Self { subscribers }
+    }
+
+    /// Delivers up to `BATCH_SIZE` due events, returning how many were
+    /// claimed. Events are locked while being delivered, so every pod can
+    /// run this without handling the same event at once.
+    pub async fn dispatch_batch(&self, pool: &PgPool) -> Result<usize, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+
+        let events = sqlx::query!(
+            r#"
+            SELECT id, event_type, payload, attempts, delivered_to
+            FROM outbox_events
+            WHERE dispatched_at IS NULL AND failed_at IS NULL AND available_at <= NOW()
+            ORDER BY created_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            BATCH_SIZE
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let claimed = events.len();
+        for row in events {
+            let mut delivered_to = row.delivered_to;
+            let mut errors = Vec::new();
+
+            // Events this build doesn't know, e.g. from a newer pod mid-rollout,
+            // are left for a retry
+            match serde_json::from_value::<DomainEvent>(row.payload) {
+                Ok(event) => {
+                    for subscriber in &self.subscribers {
+                        let name = subscriber.name();
+                        if delivered_to.iter().any(|delivered| delivered == name) {
+                            continue;
+                        }
+                        match subscriber.handle(row.id, &event).await {
+                            Ok(()) => delivered_to.push(name.to_string()),
+                            Err(e) => errors.push(format!("{}: {}", name, e)),
+                        }
+                    }
+                }
+                Err(e) => errors.push(format!("unreadable {} event: {}", row.event_type, e)),
+            }
+
+            let attempts = row.attempts + 1;
+            if errors.is_empty() {
+                sqlx::query!(
+                    r#"
+                    UPDATE outbox_events
+                    SET attempts = $2, delivered_to = $3, last_error = NULL, dispatched_at = NOW()
+                    WHERE id = $1
+                    "#,
+                    row.id,
+                    attempts,
+                    &delivered_to
+                )
+                .execute(&mut *tx)
+                .await?;
+                continue;
+            }
+
+            let error = errors.join("; ");
+            let failed = attempts >= MAX_ATTEMPTS;
+            if failed {
+                log::error!("Giving up on {} event {} after {} attempts: {}", row.event_type, row.id, attempts, error);
+            } else {
+                log::warn!("Delivering {} event {} failed: {}", row.event_type, row.id, error);
+            }
+
+            let backoff = Duration::seconds((1i64 << attempts.min(12)).min(MAX_BACKOFF_SECS));
+            sqlx::query!(
+                r#"
+                UPDATE outbox_events
+                SET attempts = $2, delivered_to = $3, last_error = $4, available_at = $5,
+                    failed_at = CASE WHEN $6 THEN NOW() END
+                WHERE id = $1
+                "#,
+                row.id,
+                attempts,
+                &delivered_to,
+                error,
+                Utc::now() + backoff,
+                failed
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(claimed)
+    }
+}
@@ -0,0 +1,902 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::audit::{AuditAction, AuditEvent, AuditLogger};
+use crate::error::AppError;
+use crate::handlers::comments::publish_comment_deleted;
+use crate::handlers::posts::build_post_summaries;
+use crate::middleware::auth::{require_role, user_role, AuthenticatedUser};
+use crate::middleware::maintenance::MaintenanceMode;
+use crate::models::{
+    AdminComment, AdminCommentParams, AdminPost, AdminPostParams, AdminUser, AdminUserParams, ApiResponse,
+    AuditEventParams, AuditEventRecord, ModerationLogParams, PaginatedResponse, PaginationParams, Post, Role,
+    SuspendUserRequest, SuspensionResponse, UpdateRoleRequest, UserRoleResponse,
+};
+use crate::realtime::Broker;
+use crate::utils::pagination::Page;
+
+#[derive(Debug, Deserialize)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceStatus {
+    pub enabled: bool,
+}
+
+pub async fn get_maintenance(
+    pool: web::Data<PgPool>,
+    maintenance: web::Data<MaintenanceMode>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(MaintenanceStatus {
+        enabled: maintenance.is_enabled(),
+    })))
+}
+
+pub async fn set_maintenance(
+    pool: web::Data<PgPool>,
+    maintenance: web::Data<MaintenanceMode>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<MaintenanceRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    maintenance.set_enabled(&pool, req.enabled, Some(user_id)).await?;
+    log::warn!("Maintenance mode {}", if req.enabled { "enabled" } else { "disabled" });
+    let event = AuditEvent::new(AuditAction::MaintenanceChanged)
+        .actor(user_id)
+        .metadata(json!({ "enabled": req.enabled }));
+    audit.record(&http_req, event).await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(MaintenanceStatus {
+        enabled: req.enabled,
+    })))
+}
+
+// Published posts with no tags, oldest first so the backlog gets worked down
+pub async fn get_untagged_posts(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let total: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM posts p
+        WHERE p.is_published = true AND p.deleted_at IS NULL
+          AND NOT EXISTS (SELECT 1 FROM post_tags pt WHERE pt.post_id = p.id)
+        "#
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at
+        FROM posts p
+        WHERE p.is_published = true AND p.deleted_at IS NULL
+          AND NOT EXISTS (SELECT 1 FROM post_tags pt WHERE pt.post_id = p.id)
+        ORDER BY p.published_at ASC, p.id ASC
+        LIMIT $1 OFFSET $2
+        "#,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let post_responses = build_post_summaries(&pool, posts, Some(user_id)).await?;
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// Moderation takes the post down whoever wrote it. It is soft-deleted like
+// any other post, but its author can't restore it. False when there was no
+// post to take down.
+pub async fn take_down_post(pool: &PgPool, moderator_id: Uuid, post_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE posts SET deleted_at = $3, deleted_by = $2 WHERE id = $1 AND deleted_at IS NULL",
+        post_id,
+        moderator_id,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// False when there was no comment to take down
+pub async fn take_down_comment(pool: &PgPool, broker: &Broker, comment_id: Uuid) -> Result<bool, sqlx::Error> {
+    let post_id = sqlx::query_scalar!(
+        "UPDATE comments SET deleted_at = $2, pinned = false WHERE id = $1 AND deleted_at IS NULL RETURNING post_id",
+        comment_id,
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match post_id {
+        Some(post_id) => {
+            publish_comment_deleted(broker, post_id, comment_id).await;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+// Banning also revokes every token the user holds, so they are signed out
+// immediately rather than when their access token expires
+pub async fn ban_account(pool: &PgPool, admin_id: Uuid, target_id: Uuid) -> Result<(), AppError> {
+    if target_id == admin_id {
+        return Err(AppError::BadRequest("You can't ban yourself".to_string()));
+    }
+
+    if user_role(pool, target_id).await? == Role::Admin {
+        return Err(AppError::Forbidden("Admins can't be banned".to_string()));
+    }
+
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET banned_at = COALESCE(banned_at, $2), tokens_revoked_at = $2, updated_at = $2
+        WHERE id = $1
+        "#,
+        target_id,
+        now
+    )
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    Ok(())
+}
+
+pub async fn delete_post(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let post_id = path.into_inner();
+    if !take_down_post(&pool, user_id, post_id).await? {
+        return Err(AppError::NotFound("Post not found".to_string()));
+    }
+
+    log::warn!("Post {} deleted by moderator {}", post_id, user_id);
+    let event = AuditEvent::new(AuditAction::PostDeleted)
+        .actor(user_id)
+        .target("post", post_id)
+        .metadata(json!({ "moderation": true }));
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Post deleted")))
+}
+
+pub async fn delete_comment(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let comment_id = path.into_inner();
+    if !take_down_comment(&pool, &broker, comment_id).await? {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    log::warn!("Comment {} deleted by moderator {}", comment_id, user_id);
+    let event = AuditEvent::new(AuditAction::CommentDeleted)
+        .actor(user_id)
+        .target("comment", comment_id)
+        .metadata(json!({ "moderation": true }));
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Comment deleted")))
+}
+
+pub async fn ban_user(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let target_id = path.into_inner();
+    ban_account(&pool, user_id, target_id).await?;
+
+    log::warn!("User {} banned by admin {}", target_id, user_id);
+    let event = AuditEvent::new(AuditAction::UserBanned).actor(user_id).target("user", target_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("User banned")))
+}
+
+pub async fn unban_user(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let target_id = path.into_inner();
+    let result = sqlx::query!(
+        "UPDATE users SET banned_at = NULL, updated_at = $2 WHERE id = $1",
+        target_id,
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    log::warn!("User {} unbanned by admin {}", target_id, user_id);
+    let event = AuditEvent::new(AuditAction::UserUnbanned).actor(user_id).target("user", target_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("User unbanned")))
+}
+
+pub async fn set_user_role(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateRoleRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    // Keeps an admin from locking themselves out
+    let target_id = path.into_inner();
+    if target_id == user_id {
+        return Err(AppError::BadRequest("You can't change your own role".to_string()));
+    }
+
+    let result = sqlx::query!(
+        "UPDATE users SET role = $2, updated_at = $3 WHERE id = $1",
+        target_id,
+        req.role.as_str(),
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    log::warn!("User {} given role {} by admin {}", target_id, req.role.as_str(), user_id);
+    let event = AuditEvent::new(AuditAction::RoleChanged)
+        .actor(user_id)
+        .target("user", target_id)
+        .metadata(json!({ "role": req.role }));
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UserRoleResponse {
+        user_id: target_id,
+        role: req.role,
+    })))
+}
+
+// Newest first; every filter is optional
+pub async fn get_audit_events(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<AuditEventParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let params = query.into_inner();
+    let Page { page, limit, offset } = Page::with_limits(params.page, params.limit, 50, 200);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM audit_events
+        WHERE ($1::text IS NULL OR action = $1)
+          AND ($2::uuid IS NULL OR actor_id = $2)
+          AND ($3::uuid IS NULL OR target_id = $3)
+        "#,
+        params.action,
+        params.actor_id,
+        params.target_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let events = sqlx::query_as!(
+        AuditEventRecord,
+        r#"
+        SELECT id, action, actor_id, target_type, target_id, ip_address, metadata, created_at
+        FROM audit_events
+        WHERE ($1::text IS NULL OR action = $1)
+          AND ($2::uuid IS NULL OR actor_id = $2)
+          AND ($3::uuid IS NULL OR target_id = $3)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        params.action,
+        params.actor_id,
+        params.target_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PaginatedResponse {
+        data: events,
+        total,
+        page,
+        limit,
+        total_pages: (total as f64 / limit as f64).ceil() as u32,
+    })))
+}
+
+// Audit actions shown in the moderation log. Post and comment deletions only
+// count when a moderator took the content down.
+const MODERATION_ACTIONS: [AuditAction; 12] = [
+    AuditAction::PostDeleted,
+    AuditAction::PostUnpublished,
+    AuditAction::CommentDeleted,
+    AuditAction::CommentHidden,
+    AuditAction::CommentUnhidden,
+    AuditAction::UserBanned,
+    AuditAction::UserUnbanned,
+    AuditAction::UserSuspended,
+    AuditAction::UserUnsuspended,
+    AuditAction::RoleChanged,
+    AuditAction::ReportResolved,
+    AuditAction::ReportUpdated,
+];
+
+fn check_status(status: &str, allowed: &[&str]) -> Result<(), AppError> {
+    if allowed.contains(&status) {
+        return Ok(());
+    }
+    Err(AppError::BadRequest(format!(
+        "Invalid status '{}', expected one of: {}",
+        status,
+        allowed.join(", ")
+    )))
+}
+
+fn search_term(q: Option<String>) -> Option<String> {
+    q.map(|q| q.trim().to_string()).filter(|q| !q.is_empty())
+}
+
+// Every post whatever its state, drafts and deleted posts included, newest
+// first
+pub async fn get_posts(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<AdminPostParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let params = query.into_inner();
+    let status = params.status.unwrap_or_else(|| "all".to_string());
+    check_status(&status, &["published", "draft", "scheduled", "deleted", "all"])?;
+    let q = search_term(params.q);
+
+    let Page { page, limit, offset } = Page::new(params.page, params.limit);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts p
+        WHERE ($1 = 'all'
+               OR ($1 = 'published' AND p.is_published = true AND p.deleted_at IS NULL)
+               OR ($1 = 'draft' AND p.is_published = false AND p.publish_at IS NULL AND p.deleted_at IS NULL)
+               OR ($1 = 'scheduled' AND p.is_published = false AND p.publish_at IS NOT NULL AND p.deleted_at IS NULL)
+               OR ($1 = 'deleted' AND p.deleted_at IS NOT NULL))
+          AND ($2::uuid IS NULL OR p.author_id = $2)
+          AND ($3::text IS NULL OR strpos(lower(p.title), lower($3)) > 0)
+        "#,
+        status,
+        params.author_id,
+        q
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let posts = sqlx::query_as!(
+        AdminPost,
+        r#"
+        SELECT p.id, p.title, p.slug, p.author_id, u.username as author_username,
+               p.is_published as "is_published!", p.published_at, p.publish_at, p.deleted_at, p.deleted_by,
+               p.like_count, p.comment_count,
+               (SELECT COUNT(*) FROM reports r
+                WHERE r.target_type = 'post' AND r.target_id = p.id AND r.status = 'open') as "open_reports!",
+               p.created_at, p.updated_at
+        FROM posts p
+        INNER JOIN users u ON p.author_id = u.id
+        WHERE ($1 = 'all'
+               OR ($1 = 'published' AND p.is_published = true AND p.deleted_at IS NULL)
+               OR ($1 = 'draft' AND p.is_published = false AND p.publish_at IS NULL AND p.deleted_at IS NULL)
+               OR ($1 = 'scheduled' AND p.is_published = false AND p.publish_at IS NOT NULL AND p.deleted_at IS NULL)
+               OR ($1 = 'deleted' AND p.deleted_at IS NOT NULL))
+          AND ($2::uuid IS NULL OR p.author_id = $2)
+          AND ($3::text IS NULL OR strpos(lower(p.title), lower($3)) > 0)
+        ORDER BY p.created_at DESC, p.id DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        status,
+        params.author_id,
+        q,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PaginatedResponse {
+        data: posts,
+        total,
+        page,
+        limit,
+        total_pages: (total as f64 / limit as f64).ceil() as u32,
+    })))
+}
+
+// Every comment, with the content of deleted and hidden ones, newest first
+pub async fn get_comments(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<AdminCommentParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let params = query.into_inner();
+    let status = params.status.unwrap_or_else(|| "all".to_string());
+    check_status(&status, &["visible", "hidden", "deleted", "all"])?;
+    let q = search_term(params.q);
+
+    let Page { page, limit, offset } = Page::new(params.page, params.limit);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM comments c
+        WHERE ($1 = 'all'
+               OR ($1 = 'visible' AND c.hidden_at IS NULL AND c.deleted_at IS NULL)
+               OR ($1 = 'hidden' AND c.hidden_at IS NOT NULL AND c.deleted_at IS NULL)
+               OR ($1 = 'deleted' AND c.deleted_at IS NOT NULL))
+          AND ($2::uuid IS NULL OR c.post_id = $2)
+          AND ($3::uuid IS NULL OR c.author_id = $3)
+          AND ($4::text IS NULL OR strpos(lower(c.content), lower($4)) > 0)
+        "#,
+        status,
+        params.post_id,
+        params.author_id,
+        q
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let comments = sqlx::query_as!(
+        AdminComment,
+        r#"
+        SELECT c.id, c.post_id, c.parent_id, c.author_id, u.username as author_username, c.content,
+               c.like_count, c.hidden_at, c.hidden_by, c.deleted_at,
+               (SELECT COUNT(*) FROM reports r
+                WHERE r.target_type = 'comment' AND r.target_id = c.id AND r.status = 'open') as "open_reports!",
+               c.created_at, c.updated_at
+        FROM comments c
+        INNER JOIN users u ON c.author_id = u.id
+        WHERE ($1 = 'all'
+               OR ($1 = 'visible' AND c.hidden_at IS NULL AND c.deleted_at IS NULL)
+               OR ($1 = 'hidden' AND c.hidden_at IS NOT NULL AND c.deleted_at IS NULL)
+               OR ($1 = 'deleted' AND c.deleted_at IS NOT NULL))
+          AND ($2::uuid IS NULL OR c.post_id = $2)
+          AND ($3::uuid IS NULL OR c.author_id = $3)
+          AND ($4::text IS NULL OR strpos(lower(c.content), lower($4)) > 0)
+        ORDER BY c.created_at DESC, c.id DESC
+        LIMIT $5 OFFSET $6
+        "#,
+        status,
+        params.post_id,
+        params.author_id,
+        q,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PaginatedResponse {
+        data: comments,
+        total,
+        page,
+        limit,
+        total_pages: (total as f64 / limit as f64).ceil() as u32,
+    })))
+}
+
+// Every account, newest first. `q` matches usernames and email addresses.
+pub async fn get_users(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<AdminUserParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let params = query.into_inner();
+    let status = params.status.unwrap_or_else(|| "all".to_string());
+    check_status(&status, &["active", "suspended", "banned", "deactivated", "all"])?;
+    let role = params.role.map(|role| role.as_str());
+    let q = search_term(params.q);
+
+    let Page { page, limit, offset } = Page::new(params.page, params.limit);
+    let now = Utc::now();
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM users u
+        WHERE ($1 = 'all'
+               OR ($1 = 'active' AND u.banned_at IS NULL AND u.deactivated_at IS NULL
+                   AND (u.suspended_until IS NULL OR u.suspended_until <= $4))
+               OR ($1 = 'suspended' AND u.banned_at IS NULL AND u.suspended_until > $4)
+               OR ($1 = 'banned' AND u.banned_at IS NOT NULL)
+               OR ($1 = 'deactivated' AND u.deactivated_at IS NOT NULL))
+          AND ($2::text IS NULL OR u.role = $2)
+          AND ($3::text IS NULL OR strpos(lower(u.username), lower($3)) > 0 OR strpos(lower(u.email), lower($3)) > 0)
+        "#,
+        status,
+        role,
+        q,
+        now
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let users = sqlx::query_as!(
+        AdminUser,
+        r#"
+        SELECT u.id, u.username, u.email, u.role, u.is_verified, u.deactivated_at, u.banned_at, u.suspended_until,
+               (SELECT COUNT(*) FROM posts p WHERE p.author_id = u.id AND p.deleted_at IS NULL) as "post_count!",
+               (SELECT COUNT(*) FROM comments c WHERE c.author_id = u.id AND c.deleted_at IS NULL) as "comment_count!",
+               u.created_at
+        FROM users u
+        WHERE ($1 = 'all'
+               OR ($1 = 'active' AND u.banned_at IS NULL AND u.deactivated_at IS NULL
+                   AND (u.suspended_until IS NULL OR u.suspended_until <= $4))
+               OR ($1 = 'suspended' AND u.banned_at IS NULL AND u.suspended_until > $4)
+               OR ($1 = 'banned' AND u.banned_at IS NOT NULL)
+               OR ($1 = 'deactivated' AND u.deactivated_at IS NOT NULL))
+          AND ($2::text IS NULL OR u.role = $2)
+          AND ($3::text IS NULL OR strpos(lower(u.username), lower($3)) > 0 OR strpos(lower(u.email), lower($3)) > 0)
+        ORDER BY u.created_at DESC, u.id DESC
+        LIMIT $5 OFFSET $6
+        "#,
+        status,
+        role,
+        q,
+        now,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PaginatedResponse {
+        data: users,
+        total,
+        page,
+        limit,
+        total_pages: (total as f64 / limit as f64).ceil() as u32,
+    })))
+}
+
+// Takes a published or scheduled post back to draft. Unlike a takedown the
+// post isn't deleted, and its author can edit and publish it again.
+pub async fn unpublish_post(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let post_id = path.into_inner();
+    let result = sqlx::query!(
+        r#"
+        UPDATE posts SET is_published = false, published_at = NULL, publish_at = NULL, updated_at = $2
+        WHERE id = $1 AND deleted_at IS NULL AND (is_published = true OR publish_at IS NOT NULL)
+        "#,
+        post_id,
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let exists = sqlx::query!("SELECT id FROM posts WHERE id = $1 AND deleted_at IS NULL", post_id)
+            .fetch_optional(pool.get_ref())
+            .await?;
+        return match exists {
+            Some(_) => Err(AppError::Conflict("Post is not published".to_string())),
+            None => Err(AppError::NotFound("Post not found".to_string())),
+        };
+    }
+
+    log::warn!("Post {} unpublished by moderator {}", post_id, user_id);
+    let event = AuditEvent::new(AuditAction::PostUnpublished)
+        .actor(user_id)
+        .target("post", post_id)
+        .metadata(json!({ "moderation": true }));
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Post unpublished")))
+}
+
+// Shadow-hides a comment: its author still sees it as before, everyone else
+// doesn't see it at all
+pub async fn hide_comment(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let comment_id = path.into_inner();
+    let result = sqlx::query!(
+        r#"
+        UPDATE comments SET hidden_at = $2, hidden_by = $3, pinned = false
+        WHERE id = $1 AND deleted_at IS NULL AND hidden_at IS NULL
+        "#,
+        comment_id,
+        Utc::now(),
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(comment_state_error(&pool, comment_id, "Comment is already hidden").await);
+    }
+
+    log::warn!("Comment {} hidden by moderator {}", comment_id, user_id);
+    let event = AuditEvent::new(AuditAction::CommentHidden)
+        .actor(user_id)
+        .target("comment", comment_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Comment hidden")))
+}
+
+pub async fn unhide_comment(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let comment_id = path.into_inner();
+    let result = sqlx::query!(
+        r#"
+        UPDATE comments SET hidden_at = NULL, hidden_by = NULL
+        WHERE id = $1 AND deleted_at IS NULL AND hidden_at IS NOT NULL
+        "#,
+        comment_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(comment_state_error(&pool, comment_id, "Comment is not hidden").await);
+    }
+
+    log::warn!("Comment {} unhidden by moderator {}", comment_id, user_id);
+    let event = AuditEvent::new(AuditAction::CommentUnhidden)
+        .actor(user_id)
+        .target("comment", comment_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Comment unhidden")))
+}
+
+// Why hiding or unhiding changed nothing: no such comment, or it was
+// already in that state
+async fn comment_state_error(pool: &PgPool, comment_id: Uuid, conflict: &str) -> AppError {
+    let exists = sqlx::query!("SELECT id FROM comments WHERE id = $1 AND deleted_at IS NULL", comment_id)
+        .fetch_optional(pool)
+        .await;
+
+    match exists {
+        Ok(Some(_)) => AppError::Conflict(conflict.to_string()),
+        Ok(None) => AppError::NotFound("Comment not found".to_string()),
+        Err(e) => AppError::from(e),
+    }
+}
+
+// A suspension signs the user out and keeps them out until it ends.
+// Suspending again replaces the end date rather than adding to it.
+pub async fn suspend_user(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<SuspendUserRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+    req.validate()?;
+
+    let target_id = path.into_inner();
+    if target_id == user_id {
+        return Err(AppError::BadRequest("You can't suspend yourself".to_string()));
+    }
+
+    if user_role(&pool, target_id).await? == Role::Admin {
+        return Err(AppError::Forbidden("Admins can't be suspended".to_string()));
+    }
+
+    let now = Utc::now();
+    let suspended_until = now + Duration::days(i64::from(req.days));
+    let result = sqlx::query!(
+        "UPDATE users SET suspended_until = $2, tokens_revoked_at = $3, updated_at = $3 WHERE id = $1",
+        target_id,
+        suspended_until,
+        now
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    log::warn!("User {} suspended until {} by admin {}", target_id, suspended_until, user_id);
+    let reason = req.reason.as_deref().map(str::trim).filter(|reason| !reason.is_empty());
+    let event = AuditEvent::new(AuditAction::UserSuspended)
+        .actor(user_id)
+        .target("user", target_id)
+        .metadata(json!({ "days": req.days, "suspended_until": suspended_until, "reason": reason }));
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(SuspensionResponse {
+        user_id: target_id,
+        suspended_until,
+    })))
+}
+
+pub async fn unsuspend_user(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let target_id = path.into_inner();
+    let now = Utc::now();
+    let result = sqlx::query!(
+        "UPDATE users SET suspended_until = NULL, updated_at = $2 WHERE id = $1 AND suspended_until > $2",
+        target_id,
+        now
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let exists = sqlx::query!("SELECT id FROM users WHERE id = $1", target_id)
+            .fetch_optional(pool.get_ref())
+            .await?;
+        return match exists {
+            Some(_) => Err(AppError::Conflict("User is not suspended".to_string())),
+            None => Err(AppError::NotFound("User not found".to_string())),
+        };
+    }
+
+    log::warn!("User {} unsuspended by admin {}", target_id, user_id);
+    let event = AuditEvent::new(AuditAction::UserUnsuspended).actor(user_id).target("user", target_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("User unsuspended")))
+}
+
+// The audit trail narrowed to moderation actions, newest first
+pub async fn get_moderation_log(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<ModerationLogParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let params = query.into_inner();
+    let moderation_actions: Vec<&str> = MODERATION_ACTIONS.iter().map(AuditAction::as_str).collect();
+    let actions: Vec<String> = match params.action.as_deref() {
+        Some(action) if moderation_actions.contains(&action) => vec![action.to_string()],
+        Some(action) => {
+            return Err(AppError::BadRequest(format!(
+                "Invalid action '{}', expected one of: {}",
+                action,
+                moderation_actions.join(", ")
+            )))
+        }
+        None => moderation_actions.iter().map(|action| action.to_string()).collect(),
+    };
+
+    let Page { page, limit, offset } = Page::with_limits(params.page, params.limit, 50, 200);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM audit_events
+        WHERE action = ANY($1)
+          AND (action NOT IN ('post_deleted', 'comment_deleted') OR metadata->>'moderation' = 'true')
+          AND ($2::uuid IS NULL OR actor_id = $2)
+          AND ($3::uuid IS NULL OR target_id = $3)
+        "#,
+        &actions,
+        params.actor_id,
+        params.target_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let events = sqlx::query_as!(
+        AuditEventRecord,
+        r#"
+        SELECT id, action, actor_id, target_type, target_id, ip_address, metadata, created_at
+        FROM audit_events
+        WHERE action = ANY($1)
+          AND (action NOT IN ('post_deleted', 'comment_deleted') OR metadata->>'moderation' = 'true')
+          AND ($2::uuid IS NULL OR actor_id = $2)
+          AND ($3::uuid IS NULL OR target_id = $3)
+        ORDER BY created_at DESC, id DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        &actions,
+        params.actor_id,
+        params.target_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PaginatedResponse {
+        data: events,
+        total,
+        page,
+        limit,
+        total_pages: (total as f64 / limit as f64).ceil() as u32,
+    })))
+}
@@ -0,0 +1,146 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ActivityBucket, ActivityParams, ApiResponse, PostStatsBucket, PostStatsResponse};
+
+const ACTIVITY_INTERVALS: &[&str] = &["hour", "day", "week"];
+
+fn parse_interval(params: ActivityParams) -> Result<String, AppError> {
+    let interval = params.interval.unwrap_or_else(|| "day".to_string());
+    if !ACTIVITY_INTERVALS.contains(&interval.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "interval must be one of: {}",
+            ACTIVITY_INTERVALS.join(", ")
+        )));
+    }
+    Ok(interval)
+}
+
+async fn ensure_author(pool: &PgPool, post_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let post = sqlx::query!(
+        "SELECT author_id FROM posts WHERE id = $1 AND deleted_at IS NULL",
+        post_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match post {
+        Some(post) if post.author_id == user_id => Ok(()),
+        Some(_) => Err(AppError::Forbidden("Only the author can view this post's activity".to_string())),
+        None => Err(AppError::NotFound("Post not found".to_string())),
+    }
+}
+
+// Likes and comments on a post grouped into hour/day/week buckets, oldest
+// first. Only buckets with activity are returned.
+pub async fn get_post_activity(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    query: web::Query<ActivityParams>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+    let interval = parse_interval(query.into_inner())?;
+    ensure_author(&pool, post_id, user_id).await?;
+
+    let buckets = sqlx::query_as!(
+        ActivityBucket,
+        r#"
+        SELECT date_trunc($2, activity.created_at) as "bucket!",
+               COUNT(*) FILTER (WHERE activity.kind = 'like') as "like_count!",
+               COUNT(*) FILTER (WHERE activity.kind = 'comment') as "comment_count!"
+        FROM (
+            SELECT created_at, 'like' as kind FROM likes WHERE post_id = $1
+            UNION ALL
+            SELECT created_at, 'comment' as kind FROM comments WHERE post_id = $1 AND deleted_at IS NULL
+        ) activity
+        WHERE activity.created_at IS NOT NULL
+        GROUP BY 1
+        ORDER BY 1 ASC
+        "#,
+        post_id,
+        interval
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(buckets)))
+}
+
+// Totals for a post plus views, unique viewers, likes and comments per
+// hour/day/week bucket, oldest first. Recent views may take a few seconds to
+// appear since they are written in batches.
+pub async fn get_post_stats(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    query: web::Query<ActivityParams>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+    let interval = parse_interval(query.into_inner())?;
+    ensure_author(&pool, post_id, user_id).await?;
+
+    let totals = sqlx::query!(
+        r#"
+        SELECT p.like_count, p.comment_count,
+               (SELECT COUNT(*) FROM post_views v WHERE v.post_id = p.id) as "view_count!",
+               (SELECT COUNT(DISTINCT v.viewer_key) FROM post_views v WHERE v.post_id = p.id) as "unique_viewer_count!"
+        FROM posts p
+        WHERE p.id = $1
+        "#,
+        post_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    // Unique viewers are counted within each bucket, so they don't add up to
+    // the overall figure
+    let buckets = sqlx::query_as!(
+        PostStatsBucket,
+        r#"
+        WITH views AS (
+            SELECT date_trunc($2, viewed_at) as bucket,
+                   COUNT(*) as view_count,
+                   COUNT(DISTINCT viewer_key) as unique_viewer_count
+            FROM post_views
+            WHERE post_id = $1
+            GROUP BY 1
+        ),
+        activity AS (
+            SELECT date_trunc($2, activity.created_at) as bucket,
+                   COUNT(*) FILTER (WHERE activity.kind = 'like') as like_count,
+                   COUNT(*) FILTER (WHERE activity.kind = 'comment') as comment_count
+            FROM (
+                SELECT created_at, 'like' as kind FROM likes WHERE post_id = $1
+                UNION ALL
+                SELECT created_at, 'comment' as kind FROM comments WHERE post_id = $1 AND deleted_at IS NULL
+            ) activity
+            WHERE activity.created_at IS NOT NULL
+            GROUP BY 1
+        )
+        SELECT COALESCE(views.bucket, activity.bucket) as "bucket!",
+               COALESCE(views.view_count, 0) as "view_count!",
+               COALESCE(views.unique_viewer_count, 0) as "unique_viewer_count!",
+               COALESCE(activity.like_count, 0) as "like_count!",
+               COALESCE(activity.comment_count, 0) as "comment_count!"
+        FROM views
+        FULL OUTER JOIN activity ON activity.bucket = views.bucket
+        ORDER BY 1 ASC
+        "#,
+        post_id,
+        interval
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(PostStatsResponse {
+        view_count: totals.view_count,
+        unique_viewer_count: totals.unique_viewer_count,
+        like_count: totals.like_count,
+        comment_count: totals.comment_count,
+        buckets,
+    })))
+}
@@ -0,0 +1,101 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::AppError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ApiKey, ApiResponse, CreateApiKeyRequest, CreatedApiKeyResponse};
+use crate::utils::tokens::{generate_token, hash_token};
+
+// Lets leaked keys be recognised by secret scanners
+const API_KEY_PREFIX: &str = "blog_";
+const MAX_API_KEYS_PER_USER: i64 = 25;
+
+pub async fn create_api_key(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let key_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM api_keys WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if key_count >= MAX_API_KEYS_PER_USER {
+        return Err(AppError::BadRequest(format!(
+            "You can have at most {} API keys; revoke one first",
+            MAX_API_KEYS_PER_USER
+        )));
+    }
+
+    let key = format!("{}{}", API_KEY_PREFIX, generate_token());
+    let api_key = sqlx::query_as!(
+        ApiKey,
+        r#"
+        INSERT INTO api_keys (id, user_id, name, key_prefix, key_hash, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, name, key_prefix, last_used_at, created_at
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        req.name.trim(),
+        &key[..API_KEY_PREFIX.len() + 8],
+        hash_token(&key),
+        Utc::now()
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(CreatedApiKeyResponse {
+        api_key,
+        key,
+    })))
+}
+
+pub async fn get_api_keys(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let api_keys = sqlx::query_as!(
+        ApiKey,
+        r#"
+        SELECT id, name, key_prefix, last_used_at, created_at
+        FROM api_keys
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(api_keys)))
+}
+
+pub async fn revoke_api_key(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let key_id = path.into_inner();
+
+    let result = sqlx::query!(
+        "DELETE FROM api_keys WHERE id = $1 AND user_id = $2",
+        key_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("API key not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("API key revoked")))
+}
@@ -0,0 +1,130 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::handlers::posts::build_post_summaries;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ApiResponse, FieldsParams, PaginatedResponse, PaginationParams, Post};
+use crate::utils::fields::{shape, FieldSelection};
+use crate::utils::pagination::Page;
+
+#[derive(serde::Serialize)]
+struct BookmarkResponse {
+    is_bookmarked: bool,
+}
+
+pub async fn bookmark_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let post_exists = sqlx::query!(
+        r#"
+        SELECT id FROM posts
+        WHERE id = $1 AND is_published = true AND deleted_at IS NULL
+          AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if post_exists.is_none() {
+        return Err(AppError::NotFound("Post not found".to_string()));
+    }
+
+    let result = sqlx::query!(
+        "INSERT INTO bookmarks (user_id, post_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        user_id,
+        post_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::conflict_with_state(
+            "Post already bookmarked",
+            BookmarkResponse { is_bookmarked: true },
+        ));
+    }
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(BookmarkResponse { is_bookmarked: true })))
+}
+
+pub async fn remove_bookmark(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let result = sqlx::query!(
+        "DELETE FROM bookmarks WHERE user_id = $1 AND post_id = $2",
+        user_id,
+        post_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Bookmark not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(BookmarkResponse { is_bookmarked: false })))
+}
+
+// Most recently bookmarked first. Posts that were unpublished or deleted, or
+// whose author deactivated, are left out but stay bookmarked.
+pub async fn get_my_bookmarks(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN bookmarks b ON p.id = b.post_id
+        WHERE b.user_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY b.created_at DESC, p.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts p
+        INNER JOIN bookmarks b ON p.id = b.post_id
+        WHERE b.user_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, Some(user_id)).await?, fields.as_ref())?;
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
@@ -0,0 +1,286 @@
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::handlers::posts::load_users;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{
+    ApiResponse, CoauthorInvitationResponse, CoauthorResponse, InviteCoauthorRequest, PaginatedResponse,
+    PaginationParams,
+};
+use crate::utils::pagination::Page;
+
+// Co-authors and pending invitations per post
+const MAX_COAUTHORS_PER_POST: i64 = 10;
+
+struct CoauthorRow {
+    user_id: Uuid,
+    invited_by: Option<Uuid>,
+    created_at: DateTime<Utc>,
+    accepted_at: Option<DateTime<Utc>>,
+}
+
+// Only the post's author can invite co-authors, who can then edit the post
+// but not delete or publish it
+pub async fn invite_coauthor(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<InviteCoauthorRequest>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+    let invitee_id = req.user_id;
+
+    let post = sqlx::query!(
+        "SELECT author_id FROM posts WHERE id = $1 AND deleted_at IS NULL",
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    match post {
+        Some(post) if post.author_id == user_id => {}
+        Some(_) => return Err(AppError::Forbidden("Only the post's author can invite co-authors".to_string())),
+        None => return Err(AppError::NotFound("Post not found".to_string())),
+    }
+
+    if invitee_id == user_id {
+        return Err(AppError::BadRequest("You are already the post's author".to_string()));
+    }
+
+    let invitee = sqlx::query!(
+        "SELECT id FROM users WHERE id = $1 AND deactivated_at IS NULL AND banned_at IS NULL",
+        invitee_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if invitee.is_none() {
+        return Err(AppError::NotFound("User not found".to_string()));
+    }
+
+    let coauthor_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM post_authors WHERE post_id = $1"#,
+        post_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if coauthor_count >= MAX_COAUTHORS_PER_POST {
+        return Err(AppError::BadRequest(format!(
+            "A post can have at most {} co-authors and invitations",
+            MAX_COAUTHORS_PER_POST
+        )));
+    }
+
+    let row = sqlx::query_as!(
+        CoauthorRow,
+        r#"
+        INSERT INTO post_authors (post_id, user_id, invited_by)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        RETURNING user_id, invited_by, created_at, accepted_at
+        "#,
+        post_id,
+        invitee_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let Some(row) = row else {
+        return Err(AppError::Conflict("User is already a co-author or invited".to_string()));
+    };
+
+    let mut coauthors = coauthor_responses(&pool, vec![row]).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(coauthors.remove(0))))
+}
+
+// Accepted co-authors and pending invitations, visible to the post's author
+// and everyone on the list
+pub async fn get_coauthors(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let post = sqlx::query!(
+        r#"
+        SELECT author_id,
+               EXISTS(SELECT 1 FROM post_authors WHERE post_id = $1 AND user_id = $2) as "is_listed!"
+        FROM posts WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    match post {
+        Some(post) if post.author_id == user_id || post.is_listed => {}
+        Some(_) => {
+            return Err(AppError::Forbidden(
+                "Only the post's authors can view its co-authors".to_string(),
+            ))
+        }
+        None => return Err(AppError::NotFound("Post not found".to_string())),
+    }
+
+    let rows = sqlx::query_as!(
+        CoauthorRow,
+        r#"
+        SELECT user_id, invited_by, created_at, accepted_at FROM post_authors
+        WHERE post_id = $1
+        ORDER BY accepted_at NULLS LAST, created_at, user_id
+        "#,
+        post_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let coauthors = coauthor_responses(&pool, rows).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(coauthors)))
+}
+
+pub async fn accept_invitation(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let row = sqlx::query_as!(
+        CoauthorRow,
+        r#"
+        UPDATE post_authors SET accepted_at = NOW()
+        WHERE post_id = $1 AND user_id = $2 AND accepted_at IS NULL
+          AND post_id IN (SELECT id FROM posts WHERE deleted_at IS NULL)
+        RETURNING user_id, invited_by, created_at, accepted_at
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let Some(row) = row else {
+        return Err(AppError::NotFound("Invitation not found".to_string()));
+    };
+
+    let mut coauthors = coauthor_responses(&pool, vec![row]).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(coauthors.remove(0))))
+}
+
+// The post's author can remove anyone; co-authors can remove themselves,
+// which also declines a pending invitation
+pub async fn remove_coauthor(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, coauthor_id) = path.into_inner();
+
+    if coauthor_id != user_id {
+        let post = sqlx::query!(
+            "SELECT author_id FROM posts WHERE id = $1 AND deleted_at IS NULL",
+            post_id
+        )
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+        match post {
+            Some(post) if post.author_id == user_id => {}
+            Some(_) => {
+                return Err(AppError::Forbidden(
+                    "Only the post's author can remove other co-authors".to_string(),
+                ))
+            }
+            None => return Err(AppError::NotFound("Post not found".to_string())),
+        }
+    }
+
+    let result = sqlx::query!(
+        "DELETE FROM post_authors WHERE post_id = $1 AND user_id = $2",
+        post_id,
+        coauthor_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Co-author not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Co-author removed")))
+}
+
+// Invitations the current user hasn't answered yet, newest first
+pub async fn get_my_invitations(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let invitations = sqlx::query_as!(
+        CoauthorInvitationResponse,
+        r#"
+        SELECT pa.post_id, p.title as post_title, pa.invited_by, pa.created_at as invited_at
+        FROM post_authors pa
+        INNER JOIN posts p ON pa.post_id = p.id
+        WHERE pa.user_id = $1 AND pa.accepted_at IS NULL AND p.deleted_at IS NULL
+        ORDER BY pa.created_at DESC, pa.post_id
+        LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM post_authors pa
+        INNER JOIN posts p ON pa.post_id = p.id
+        WHERE pa.user_id = $1 AND pa.accepted_at IS NULL AND p.deleted_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: invitations,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+async fn coauthor_responses(pool: &PgPool, rows: Vec<CoauthorRow>) -> Result<Vec<CoauthorResponse>, AppError> {
+    let user_ids: Vec<Uuid> = rows.iter().map(|row| row.user_id).collect();
+    let users = load_users(pool, &user_ids).await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let user = users.get(&row.user_id).cloned().ok_or(sqlx::Error::RowNotFound)?;
+            Ok(CoauthorResponse {
+                user,
+                status: if row.accepted_at.is_some() { "accepted" } else { "invited" },
+                invited_by: row.invited_by,
+                invited_at: row.created_at,
+                accepted_at: row.accepted_at,
+            })
+        })
+        .collect()
+}
@@ -1,136 +1,305 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse};
 use chrono::Utc;
+use serde_json::json;
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::middleware::auth::get_user_id_from_request;
-use crate::models::{ApiResponse, Comment, CommentResponse, CreateCommentRequest, UserResponse};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::events::{emit, DomainEvent};
+use crate::handlers::mentions::sync_mentions;
+use crate::handlers::posts::load_users;
+use crate::middleware::auth::{has_role, require_role, AuthenticatedUser, MaybeUser};
+use crate::models::{
+    ApiResponse, Comment, CommentDepthParams, CommentListParams, CommentRepliesResponse, CommentResponse,
+    CommentRevision, CommentSort, CommentTreeResponse, CreateCommentRequest, MoreRepliesParams,
+    NotificationChannel, NotificationKind, PaginatedResponse, PaginationParams, Role,
+};
+use crate::notifications::opted_in;
+use crate::realtime::{Broker, Topic};
+use crate::utils::pagination::Page;
 
+// Replies included below each comment in the comment tree, at every level
+const INITIAL_REPLY_COUNT: usize = 3;
+const DEFAULT_MORE_REPLIES: u32 = 20;
+const MAX_MORE_REPLIES: u32 = 100;
+// Shown in place of a deleted comment's content
+const DELETED_PLACEHOLDER: &str = "[deleted]";
+
+// Top-level comments a page at a time, each with its reply thread. The
+// pinned comment leads the first page whatever the sort. Comments hidden by
+// a moderator are left out for everyone but their author.
 pub async fn get_comments(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    MaybeUser(user_id): MaybeUser,
     path: web::Path<Uuid>,
-) -> Result<HttpResponse> {
+    list_query: web::Query<CommentListParams>,
+    depth_query: web::Query<CommentDepthParams>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
+    let list_query = list_query.into_inner();
 
-    let comments = sqlx::query_as!(
+    let sort = match CommentSort::parse(list_query.sort.as_deref()) {
+        Ok(sort) => sort,
+        Err(message) => {
+            return Err(AppError::BadRequest(message));
+        }
+    };
+
+    if list_query.flat.unwrap_or(false) {
+        return get_flat_comments(&pool, post_id, sort, query.into_inner(), user_id).await;
+    }
+
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let totals = sqlx::query!(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE parent_id IS NULL) as "roots!", COUNT(*) as "all!"
+        FROM comments
+        WHERE post_id = $1 AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (hidden_at IS NULL OR author_id = $2)
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let roots = sqlx::query_as!(
         Comment,
-        "SELECT id, content, post_id, author_id, parent_id, created_at, updated_at FROM comments WHERE post_id = $1 ORDER BY created_at ASC",
-        post_id
+        r#"
+        SELECT c.id, c.content, c.post_id, c.author_id, c.parent_id, c.pinned, c.like_count, c.edited, c.deleted_at, c.created_at, c.updated_at FROM comments c
+        WHERE c.post_id = $1 AND c.parent_id IS NULL
+          AND c.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (c.hidden_at IS NULL OR c.author_id = $5)
+        ORDER BY c.pinned DESC,
+                 CASE WHEN $4 = 'top' THEN c.like_count END DESC,
+                 CASE WHEN $4 = 'newest' THEN c.created_at END DESC,
+                 CASE WHEN $4 = 'newest' THEN c.id END DESC,
+                 c.created_at ASC, c.id ASC
+        LIMIT $2 OFFSET $3
+        "#,
+        post_id,
+        limit as i64,
+        offset,
+        sort.as_str(),
+        user_id
     )
     .fetch_all(pool.get_ref())
-    .await;
+    .await?;
 
-    match comments {
-        Ok(comments) => {
-            let mut comment_responses = Vec::new();
+    let depth = render_depth(&config, &depth_query);
+    let comment_responses = build_threads(&pool, roots, depth, user_id).await?;
 
-            // Build tree structure (simplified - only handles one level of nesting)
-            let mut root_comments = Vec::new();
-            let mut reply_map: std::collections::HashMap<Uuid, Vec<CommentResponse>> = std::collections::HashMap::new();
+    let total_pages = (totals.roots as f64 / limit as f64).ceil() as u32;
 
-            for comment in comments {
-                let comment_response = build_comment_response(&pool, comment).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CommentTreeResponse {
+        page: PaginatedResponse {
+            data: comment_responses,
+            total: totals.roots,
+            page,
+            limit,
+            total_pages,
+        },
+        total_comments: totals.all,
+    })))
+}
 
-                if comment_response.parent_id.is_some() {
-                    let parent_id = comment_response.parent_id.unwrap();
-                    reply_map.entry(parent_id).or_insert_with(Vec::new).push(comment_response);
-                } else {
-                    root_comments.push(comment_response);
-                }
-            }
+// Every comment on the post, replies included, as one paginated list;
+// clients rebuild the tree from `parent_id`
+async fn get_flat_comments(
+    pool: &PgPool,
+    post_id: Uuid,
+    sort: CommentSort,
+    pagination: PaginationParams,
+    current_user_id: Option<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
-            // Attach replies to root comments
-            for mut comment in root_comments {
-                if let Some(replies) = reply_map.get(&comment.id) {
-                    comment.replies = replies.clone();
-                } else {
-                    comment.replies = Vec::new();
-                }
-                comment_responses.push(comment);
-            }
+    let total: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM comments WHERE post_id = $1 AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL) AND (hidden_at IS NULL OR author_id = $2)"
+    )
+    .bind(post_id)
+    .bind(current_user_id)
+    .fetch_one(pool)
+    .await?;
 
-            Ok(HttpResponse::Ok().json(ApiResponse::success(comment_responses)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
-        }
+    let comments = sqlx::query_as!(
+        Comment,
+        r#"
+        SELECT c.id, c.content, c.post_id, c.author_id, c.parent_id, c.pinned, c.like_count, c.edited, c.deleted_at, c.created_at, c.updated_at FROM comments c
+        WHERE c.post_id = $1 AND c.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (c.hidden_at IS NULL OR c.author_id = $5)
+        ORDER BY CASE WHEN $4 = 'top' THEN c.like_count END DESC,
+                 CASE WHEN $4 = 'newest' THEN c.created_at END DESC,
+                 CASE WHEN $4 = 'newest' THEN c.id END DESC,
+                 c.created_at ASC, c.id ASC
+        LIMIT $2 OFFSET $3
+        "#,
+        post_id,
+        limit as i64,
+        offset,
+        sort.as_str(),
+        current_user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let comment_responses = build_comment_responses(pool, comments, current_user_id).await?;
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: comment_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// One comment and the thread of replies below it, for expanding a branch
+// collapsed at the render depth
+pub async fn get_comment_thread(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<(Uuid, Uuid)>,
+    depth_query: web::Query<CommentDepthParams>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, comment_id) = path.into_inner();
+
+    let comment = sqlx::query_as!(
+        Comment,
+        r#"
+        SELECT id, content, post_id, author_id, parent_id, pinned, like_count, edited, deleted_at, created_at, updated_at FROM comments
+        WHERE id = $1 AND post_id = $2 AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (hidden_at IS NULL OR author_id = $3)
+        "#,
+        comment_id,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let Some(comment) = comment else {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    };
+
+    let depth = render_depth(&config, &depth_query);
+    let mut thread = build_threads(&pool, vec![comment], depth, user_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(thread.remove(0))))
+}
+
+// Next batch of replies to a comment, in the same order as the tree, each
+// with its own thread
+pub async fn get_more_replies(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<(Uuid, Uuid)>,
+    query: web::Query<MoreRepliesParams>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, root_id) = path.into_inner();
+    let params = query.into_inner();
+    let limit = params.limit.unwrap_or(DEFAULT_MORE_REPLIES).clamp(1, MAX_MORE_REPLIES);
+
+    let root = sqlx::query!(
+        "SELECT id FROM comments WHERE id = $1 AND post_id = $2 AND (hidden_at IS NULL OR author_id = $3)",
+        root_id,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if root.is_none() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
     }
+
+    // Without a cursor the batch starts after the replies already included in the tree
+    let mut replies = sqlx::query_as!(
+        Comment,
+        r#"
+        SELECT id, content, post_id, author_id, parent_id, pinned, like_count, edited, deleted_at, created_at, updated_at FROM comments
+        WHERE parent_id = $1
+            AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+            AND (hidden_at IS NULL OR author_id = $5)
+            AND ($2::uuid IS NULL OR (created_at, id) > (SELECT created_at, id FROM comments WHERE id = $2))
+        ORDER BY created_at ASC, id ASC
+        LIMIT $3 OFFSET $4
+        "#,
+        root_id,
+        params.after,
+        limit as i64 + 1,
+        if params.after.is_some() { 0 } else { INITIAL_REPLY_COUNT as i64 },
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let has_more = replies.len() > limit as usize;
+    replies.truncate(limit as usize);
+
+    // One level of the render depth is taken by the comment replied to
+    let reply_responses = build_threads(&pool, replies, config.comment_max_depth - 1, user_id).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(CommentRepliesResponse {
+        replies: reply_responses,
+        has_more,
+    })))
 }
 
 pub async fn create_comment(
     pool: web::Data<PgPool>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
     req: web::Json<CreateCommentRequest>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
-    }
+    req.validate()?;
 
     // Check if post exists
-    let post_exists = sqlx::query!(
-        "SELECT id FROM posts WHERE id = $1",
+    let post_author_id = sqlx::query_scalar!(
+        "SELECT author_id FROM posts WHERE id = $1 AND deleted_at IS NULL",
         post_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
-
-    match post_exists {
-        Ok(None) => {
-            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-                "Post not found".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(Some(_)) => {}
-    }
+    .await?
+    .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
 
     // Check if parent comment exists (if provided)
-    if let Some(parent_id) = req.parent_id {
-        let parent_exists = sqlx::query!(
-            "SELECT id FROM comments WHERE id = $1 AND post_id = $2",
-            parent_id,
-            post_id
-        )
-        .fetch_optional(pool.get_ref())
-        .await;
-
-        match parent_exists {
-            Ok(None) => {
-                return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-                    "Parent comment not found".to_string(),
-                )));
-            }
-            Err(e) => {
-                log::error!("Database error: {:?}", e);
-                return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                    "Database error".to_string(),
-                )));
+    let parent_author_id = match req.parent_id {
+        Some(parent_id) => {
+            let parent_author_id = sqlx::query_scalar!(
+                "SELECT author_id FROM comments WHERE id = $1 AND post_id = $2 AND deleted_at IS NULL",
+                parent_id,
+                post_id
+            )
+            .fetch_optional(pool.get_ref())
+            .await?;
+
+            if parent_author_id.is_none() {
+                return Err(AppError::BadRequest("Parent comment not found".to_string()));
             }
-            Ok(Some(_)) => {}
+            parent_author_id
         }
-    }
+        None => None,
+    };
+
+    // The comment, its mentions and the event announcing it are committed together
+    let mut tx = pool.begin().await?;
 
     let comment_id = Uuid::new_v4();
     let comment = sqlx::query_as!(
@@ -138,7 +307,7 @@ pub async fn create_comment(
         r#"
         INSERT INTO comments (id, content, post_id, author_id, parent_id, created_at, updated_at)
         VALUES ($1, $2, $3, $4, $5, $6, $6)
-        RETURNING id, content, post_id, author_id, parent_id, created_at, updated_at
+        RETURNING id, content, post_id, author_id, parent_id, pinned, like_count, edited, deleted_at, created_at, updated_at
         "#,
         comment_id,
         req.content,
@@ -147,43 +316,84 @@ pub async fn create_comment(
         req.parent_id,
         Utc::now()
     )
-    .fetch_one(pool.get_ref())
-    .await;
+    .fetch_one(&mut *tx)
+    .await?;
 
-    match comment {
-        Ok(comment) => {
-            let comment_response = build_comment_response(&pool, comment).await?;
-            Ok(HttpResponse::Created().json(ApiResponse::success(comment_response)))
-        }
-        Err(e) => {
-            log::error!("Failed to create comment: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to create comment".to_string(),
-            )))
-        }
-    }
+    let mentioned_user_ids = sync_mentions(&mut *tx, post_id, Some(comment.id), user_id, &comment.content).await?;
+    let event = DomainEvent::CommentCreated {
+        comment_id,
+        post_id,
+        author_id: user_id,
+        post_author_id,
+        parent_author_id,
+        mentioned_user_ids,
+    };
+    emit(&mut tx, &event).await?;
+    tx.commit().await?;
+
+    let comment_response = build_comment_response(&pool, comment, Some(user_id)).await?;
+    broker.publish(Topic::Post(post_id), "comment.created", &comment_response).await;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(comment_response)))
 }
 
+// The replaced content is kept as a revision. With COMMENT_EDIT_WINDOW_MINUTES
+// set, only the post's author can edit their comments after the window.
 pub async fn update_comment(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<(Uuid, Uuid)>,
     req: web::Json<CreateCommentRequest>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let (post_id, comment_id) = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
+
+    req.validate()?;
+
+    check_comment_owner(&pool, &config, post_id, comment_id, user_id, "update").await?;
+
+    let mut tx = pool.begin().await?;
+
+    let current = sqlx::query!(
+        r#"
+        SELECT c.content, c.created_at as "created_at!", p.author_id as post_author_id
+        FROM comments c
+        INNER JOIN posts p ON c.post_id = p.id
+        WHERE c.id = $1 AND c.post_id = $2 AND c.author_id = $3 AND c.deleted_at IS NULL
+        FOR UPDATE OF c
+        "#,
+        comment_id,
+        post_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let Some(current) = current else {
+        return Err(AppError::NotFound("Comment not found".to_string()));
     };
 
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
+    if let Some(window) = config.comment_edit_window {
+        if current.post_author_id != user_id && Utc::now() - current.created_at > window {
+            return Err(AppError::coded(
+                StatusCode::FORBIDDEN,
+                "EDIT_WINDOW_CLOSED",
+                format!("Comments can only be edited within {} minutes of posting", window.num_minutes()),
+            ));
+        }
+    }
+
+    let changed = current.content != req.content;
+    if changed {
+        sqlx::query!(
+            "INSERT INTO comment_revisions (comment_id, content, edited_by) VALUES ($1, $2, $3)",
+            comment_id,
+            current.content,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
     }
 
     let comment = sqlx::query_as!(
@@ -191,115 +401,549 @@ pub async fn update_comment(
         r#"
         UPDATE comments SET
             content = $4,
+            edited = edited OR $6,
             updated_at = $5
-        WHERE id = $1 AND post_id = $2 AND author_id = $3
-        RETURNING id, content, post_id, author_id, parent_id, created_at, updated_at
+        WHERE id = $1 AND post_id = $2 AND author_id = $3 AND deleted_at IS NULL
+        RETURNING id, content, post_id, author_id, parent_id, pinned, like_count, edited, deleted_at, created_at, updated_at
         "#,
         comment_id,
         post_id,
         user_id,
         req.content,
-        Utc::now()
+        Utc::now(),
+        changed
     )
-    .fetch_optional(pool.get_ref())
-    .await;
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
 
     match comment {
-        Ok(Some(comment)) => {
-            let comment_response = build_comment_response(&pool, comment).await?;
+        Some(comment) => {
+            let mentioned = sync_mentions(pool.get_ref(), post_id, Some(comment.id), user_id, &comment.content)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to record mentions: {:?}", e);
+                    Vec::new()
+                });
+
+            let comment_response = build_comment_response(&pool, comment, Some(user_id)).await?;
+            broker.publish(Topic::Post(post_id), "comment.updated", &comment_response).await;
+            let recipients = mentioned.into_iter().map(|user_id| (user_id, NotificationKind::Mention)).collect();
+            let recipients = opted_in(pool.get_ref(), NotificationChannel::InApp, recipients)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to load notification settings: {:?}", e);
+                    Vec::new()
+                });
+            broker.notify_all(user_id, Some(post_id), Some(comment_id), recipients).await;
+
             Ok(HttpResponse::Ok().json(ApiResponse::success(comment_response)))
         }
-        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Comment not found or you don't have permission to update it".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Failed to update comment: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to update comment".to_string(),
-            )))
-        }
+        None => Err(AppError::NotFound("Comment not found".to_string())),
     }
 }
 
+// Earlier versions of a comment, oldest first, for moderators. Deleted
+// comments' history stays available.
+pub async fn get_comment_revisions(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, comment_id) = path.into_inner();
+
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let comment = sqlx::query!(
+        "SELECT id FROM comments WHERE id = $1 AND post_id = $2",
+        comment_id,
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if comment.is_none() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    let revisions = sqlx::query_as!(
+        CommentRevision,
+        r#"
+        SELECT id, content, edited_by, created_at FROM comment_revisions
+        WHERE comment_id = $1
+        ORDER BY created_at ASC, id ASC
+        "#,
+        comment_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(revisions)))
+}
+
 pub async fn delete_comment(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<(Uuid, Uuid)>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let (post_id, comment_id) = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
+    check_comment_owner(&pool, &config, post_id, comment_id, user_id, "delete").await?;
+
+    // The row stays so replies keep their place; it is shown as a placeholder
     let result = sqlx::query!(
-        "DELETE FROM comments WHERE id = $1 AND post_id = $2 AND author_id = $3",
+        r#"
+        UPDATE comments SET deleted_at = $4, pinned = false
+        WHERE id = $1 AND post_id = $2 AND author_id = $3 AND deleted_at IS NULL
+        "#,
         comment_id,
         post_id,
-        user_id
+        user_id,
+        Utc::now()
     )
     .execute(pool.get_ref())
-    .await;
+    .await?;
 
-    match result {
-        Ok(result) if result.rows_affected() > 0 => {
-            Ok(HttpResponse::NoContent().finish())
-        }
-        Ok(_) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Comment not found or you don't have permission to delete it".to_string(),
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    publish_comment_deleted(&broker, post_id, comment_id).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Comment deleted")))
+}
+
+pub async fn publish_comment_deleted(broker: &Broker, post_id: Uuid, comment_id: Uuid) {
+    let data = json!({ "id": comment_id, "post_id": post_id });
+    broker.publish(Topic::Post(post_id), "comment.deleted", &data).await;
+}
+
+// Fails when the comment can't be modified: 404 if the post or comment
+// doesn't exist (or the comment belongs to another post), 403 if the caller
+// isn't its author, or 404 in privacy mode
+async fn check_comment_owner(
+    pool: &PgPool,
+    config: &Config,
+    post_id: Uuid,
+    comment_id: Uuid,
+    user_id: Uuid,
+    action: &str,
+) -> Result<(), AppError> {
+    let comment = sqlx::query!(
+        r#"
+        SELECT c.author_id, c.post_id, EXISTS(SELECT 1 FROM posts WHERE id = $2) as "post_exists!"
+        FROM comments c
+        WHERE c.id = $1 AND c.deleted_at IS NULL
+        "#,
+        comment_id,
+        post_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let not_found = |message: &str| Err(AppError::NotFound(message.to_string()));
+
+    match comment {
+        Some(comment) if !comment.post_exists => not_found("Post not found"),
+        Some(comment) if comment.post_id != post_id => not_found("Comment not found"),
+        Some(comment) if comment.author_id == user_id => Ok(()),
+        Some(_) if config.privacy_mode => not_found("Comment not found"),
+        Some(_) => Err(AppError::Forbidden(format!(
+            "You don't have permission to {} this comment",
+            action
         ))),
-        Err(e) => {
-            log::error!("Failed to delete comment: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to delete comment".to_string(),
-            )))
+        None => not_found("Comment not found"),
+    }
+}
+
+fn render_depth(config: &Config, params: &CommentDepthParams) -> u32 {
+    params.depth.unwrap_or(config.comment_max_depth).min(config.comment_max_depth)
+}
+
+// Renders each comment's replies `depth` levels down, the first few replies
+// at each level. Deeper replies are left out of the tree and only counted.
+async fn build_threads(
+    pool: &PgPool,
+    roots: Vec<Comment>,
+    depth: u32,
+    current_user_id: Option<Uuid>,
+) -> Result<Vec<CommentResponse>, AppError> {
+    let root_ids: Vec<Uuid> = roots.iter().map(|comment| comment.id).collect();
+
+    let replies = sqlx::query_as!(
+        Comment,
+        r#"
+        WITH RECURSIVE thread AS (
+            SELECT c.id, c.content, c.post_id, c.author_id, c.parent_id, c.pinned, c.like_count, c.edited, c.deleted_at, c.created_at, c.updated_at,
+                   0 as depth
+            FROM comments c
+            WHERE c.id = ANY($1)
+            UNION ALL
+            SELECT reply.*, thread.depth + 1 FROM thread
+            CROSS JOIN LATERAL (
+                SELECT r.id, r.content, r.post_id, r.author_id, r.parent_id, r.pinned, r.like_count, r.edited, r.deleted_at, r.created_at, r.updated_at
+                FROM comments r
+                WHERE r.parent_id = thread.id AND r.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+                  AND (r.hidden_at IS NULL OR r.author_id = $4)
+                ORDER BY r.created_at ASC, r.id ASC
+                LIMIT $3
+            ) reply
+            WHERE thread.depth < $2
+        )
+        SELECT id as "id!", content as "content!", post_id as "post_id!", author_id as "author_id!", parent_id,
+               pinned as "pinned!", like_count as "like_count!", edited as "edited!", deleted_at, created_at, updated_at
+        FROM thread
+        WHERE depth > 0
+        ORDER BY created_at ASC, id ASC
+        "#,
+        &root_ids,
+        depth as i32,
+        INITIAL_REPLY_COUNT as i64,
+        current_user_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut reply_map: HashMap<Uuid, Vec<CommentResponse>> = HashMap::new();
+    for reply in build_comment_responses(pool, replies, current_user_id).await? {
+        if let Some(parent_id) = reply.parent_id {
+            reply_map.entry(parent_id).or_default().push(reply);
         }
     }
+
+    let mut threads = build_comment_responses(pool, roots, current_user_id).await?;
+    for thread in &mut threads {
+        attach_replies(thread, &mut reply_map);
+    }
+    Ok(threads)
+}
+
+fn attach_replies(comment: &mut CommentResponse, reply_map: &mut HashMap<Uuid, Vec<CommentResponse>>) {
+    comment.replies = reply_map.remove(&comment.id).unwrap_or_default();
+    comment.has_more_replies = comment.reply_count > comment.replies.len() as i64;
+    for reply in &mut comment.replies {
+        attach_replies(reply, reply_map);
+    }
 }
 
 async fn build_comment_response(
     pool: &PgPool,
     comment: Comment,
-) -> Result<CommentResponse> {
-    let author = sqlx::query!(
+    current_user_id: Option<Uuid>,
+) -> Result<CommentResponse, AppError> {
+    let mut responses = build_comment_responses(pool, vec![comment], current_user_id).await?;
+    Ok(responses.remove(0))
+}
+
+// Loads the authors, reply counts and the current user's likes of all the
+// comments at once
+async fn build_comment_responses(
+    pool: &PgPool,
+    comments: Vec<Comment>,
+    current_user_id: Option<Uuid>,
+) -> Result<Vec<CommentResponse>, AppError> {
+    let author_ids: Vec<Uuid> = comments
+        .iter()
+        .filter(|comment| comment.deleted_at.is_none())
+        .map(|comment| comment.author_id)
+        .collect();
+    let authors = load_users(pool, &author_ids).await?;
+
+    let comment_ids: Vec<Uuid> = comments.iter().map(|comment| comment.id).collect();
+    let reply_counts: HashMap<Uuid, i64> = sqlx::query!(
         r#"
-        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at,
-               COUNT(DISTINCT f1.follower_id) as "follower_count!",
-               COUNT(DISTINCT f2.following_id) as "following_count!"
-        FROM users u
-        LEFT JOIN follows f1 ON u.id = f1.following_id
-        LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE u.id = $1
-        GROUP BY u.id
+        SELECT parent_id as "parent_id!", COUNT(*) as "count!" FROM comments
+        WHERE parent_id = ANY($1) AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (hidden_at IS NULL OR author_id = $2)
+        GROUP BY parent_id
         "#,
-        comment.author_id
+        &comment_ids,
+        current_user_id
     )
-    .fetch_one(pool)
-    .await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
-
-    Ok(CommentResponse {
-        id: comment.id,
-        content: comment.content,
-        author: UserResponse {
-            id: author.id,
-            username: author.username,
-            email: author.email,
-            full_name: author.full_name,
-            bio: author.bio,
-            avatar_url: author.avatar_url,
-            is_verified: author.is_verified.unwrap_or(false),
-            follower_count: author.follower_count,
-            following_count: author.following_count,
-            created_at: author.created_at.unwrap(),
-        },
-        parent_id: comment.parent_id,
-        replies: Vec::new(), // Will be populated by the calling function
-        created_at: comment.created_at.unwrap(),
-        updated_at: comment.updated_at.unwrap(),
-    })
-}
\ No newline at end of file
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| (row.parent_id, row.count))
+    .collect();
+
+    let liked: HashSet<Uuid> = match current_user_id {
+        Some(user_id) => sqlx::query_scalar!(
+            "SELECT comment_id FROM comment_likes WHERE user_id = $1 AND comment_id = ANY($2)",
+            user_id,
+            &comment_ids
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect(),
+        None => HashSet::new(),
+    };
+
+    comments
+        .into_iter()
+        .map(|comment| {
+            let is_deleted = comment.deleted_at.is_some();
+            let author = if is_deleted {
+                None
+            } else {
+                Some(authors.get(&comment.author_id).cloned().ok_or(sqlx::Error::RowNotFound)?)
+            };
+
+            Ok(CommentResponse {
+                id: comment.id,
+                content: if is_deleted { DELETED_PLACEHOLDER.to_string() } else { comment.content },
+                author,
+                is_deleted,
+                parent_id: comment.parent_id,
+                pinned: comment.pinned,
+                edited: comment.edited,
+                replies: Vec::new(), // Will be populated by the calling function
+                reply_count: reply_counts.get(&comment.id).copied().unwrap_or(0),
+                like_count: comment.like_count,
+                is_liked: liked.contains(&comment.id),
+                has_more_replies: false,
+                created_at: comment.created_at.unwrap(),
+                updated_at: comment.updated_at.unwrap(),
+            })
+        })
+        .collect()
+}
+
+pub async fn pin_comment(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    set_comment_pinned(&pool, path.into_inner(), user_id, true).await
+}
+
+pub async fn unpin_comment(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    set_comment_pinned(&pool, path.into_inner(), user_id, false).await
+}
+
+// Only the post's author or a moderator may pin. Pinning a comment replaces
+// whichever comment was pinned on the post before.
+async fn set_comment_pinned(
+    pool: &PgPool,
+    (post_id, comment_id): (Uuid, Uuid),
+    user_id: Uuid,
+    pinned: bool,
+) -> Result<HttpResponse, AppError> {
+    let target = sqlx::query!(
+        r#"
+        SELECT p.author_id as post_author_id, c.parent_id
+        FROM comments c
+        INNER JOIN posts p ON c.post_id = p.id
+        WHERE c.id = $1 AND c.post_id = $2 AND c.deleted_at IS NULL
+        "#,
+        comment_id,
+        post_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    let target = match target {
+        Some(target) => target,
+        None => {
+            return Err(AppError::NotFound("Comment not found".to_string()));
+        }
+    };
+
+    if target.post_author_id != user_id && !has_role(pool, user_id, Role::Admin).await? {
+        return Err(AppError::Forbidden("Only the post's author can pin comments".to_string()));
+    }
+
+    if pinned && target.parent_id.is_some() {
+        return Err(AppError::BadRequest("Only top-level comments can be pinned".to_string()));
+    }
+
+    let comment = update_pinned(pool, post_id, comment_id, pinned).await?;
+
+    let comment_response = build_comment_response(pool, comment, Some(user_id)).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(comment_response)))
+}
+
+async fn update_pinned(
+    pool: &PgPool,
+    post_id: Uuid,
+    comment_id: Uuid,
+    pinned: bool,
+) -> Result<Comment, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    if pinned {
+        sqlx::query!(
+            "UPDATE comments SET pinned = false WHERE post_id = $1 AND pinned AND id <> $2",
+            post_id,
+            comment_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let comment = sqlx::query_as!(
+        Comment,
+        r#"
+        UPDATE comments SET pinned = $2
+        WHERE id = $1
+        RETURNING id, content, post_id, author_id, parent_id, pinned, like_count, edited, deleted_at, created_at, updated_at
+        "#,
+        comment_id,
+        pinned
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(comment)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::Method;
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support;
+
+    struct Fixture {
+        config: web::Data<Config>,
+        post_id: Uuid,
+        comment_id: Uuid,
+        author_id: Uuid,
+        other_user_id: Uuid,
+    }
+
+    async fn fixture(pool: &PgPool) -> Fixture {
+        let author_id = test_support::insert_user(pool, "author", "").await;
+        let other_user_id = test_support::insert_user(pool, "other", "").await;
+        let post_id = test_support::insert_published_post(pool, author_id, "Commented", Utc::now()).await;
+        let comment_id = test_support::insert_comment(pool, post_id, author_id, "First!").await;
+        Fixture { config: test_support::config(), post_id, comment_id, author_id, other_user_id }
+    }
+
+    async fn comments_app(
+        pool: &PgPool,
+        config: web::Data<Config>,
+    ) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error> {
+        test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(config)
+                .app_data(web::Data::new(Broker::from_env().await.unwrap()))
+                .route("/posts/{post_id}/comments/{comment_id}", web::put().to(update_comment))
+                .route("/posts/{post_id}/comments/{comment_id}", web::delete().to(delete_comment)),
+        )
+        .await
+    }
+
+    // Sends an update or delete as `user_id` and returns the status and body
+    async fn send(
+        app: &impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error>,
+        fixture: &Fixture,
+        method: Method,
+        post_id: Uuid,
+        user_id: Uuid,
+    ) -> (StatusCode, Value) {
+        let req = test::TestRequest::default()
+            .method(method)
+            .uri(&format!("/posts/{}/comments/{}", post_id, fixture.comment_id))
+            .insert_header(("Authorization", test_support::bearer(&fixture.config, user_id)))
+            .set_json(json!({ "content": "Edited" }))
+            .to_request();
+        let resp = test::call_service(app, req).await;
+        let status = resp.status();
+        let body = test::read_body(resp).await;
+        (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+    }
+
+    #[sqlx::test]
+    async fn missing_post_is_not_found(pool: PgPool) {
+        let fixture = fixture(&pool).await;
+        let app = comments_app(&pool, fixture.config.clone()).await;
+
+        for method in [Method::PUT, Method::DELETE] {
+            let (status, body) = send(&app, &fixture, method, Uuid::new_v4(), fixture.author_id).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body["detail"], "Post not found");
+        }
+    }
+
+    #[sqlx::test]
+    async fn comment_on_another_post_is_not_found(pool: PgPool) {
+        let fixture = fixture(&pool).await;
+        let other_post_id = test_support::insert_published_post(&pool, fixture.author_id, "Other", Utc::now()).await;
+        let app = comments_app(&pool, fixture.config.clone()).await;
+
+        for method in [Method::PUT, Method::DELETE] {
+            let (status, body) = send(&app, &fixture, method, other_post_id, fixture.author_id).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body["detail"], "Comment not found");
+        }
+    }
+
+    #[sqlx::test]
+    async fn non_author_is_forbidden(pool: PgPool) {
+        let fixture = fixture(&pool).await;
+        let app = comments_app(&pool, fixture.config.clone()).await;
+
+        let (status, body) = send(&app, &fixture, Method::PUT, fixture.post_id, fixture.other_user_id).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["detail"], "You don't have permission to update this comment");
+        let (status, body) = send(&app, &fixture, Method::DELETE, fixture.post_id, fixture.other_user_id).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["detail"], "You don't have permission to delete this comment");
+    }
+
+    #[sqlx::test]
+    async fn non_author_is_not_found_in_privacy_mode(pool: PgPool) {
+        let fixture = fixture(&pool).await;
+        let mut settings = test_support::settings();
+        settings.privacy_mode = true;
+        let app = comments_app(&pool, web::Data::new(settings)).await;
+
+        for method in [Method::PUT, Method::DELETE] {
+            let (status, body) = send(&app, &fixture, method, fixture.post_id, fixture.other_user_id).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+            assert_eq!(body["detail"], "Comment not found");
+        }
+
+        let untouched = sqlx::query!("SELECT content, deleted_at FROM comments WHERE id = $1", fixture.comment_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(untouched.content, "First!");
+        assert!(untouched.deleted_at.is_none());
+    }
+
+    #[sqlx::test]
+    async fn author_can_update_and_delete(pool: PgPool) {
+        let fixture = fixture(&pool).await;
+        let app = comments_app(&pool, fixture.config.clone()).await;
+
+        let (status, _) = send(&app, &fixture, Method::PUT, fixture.post_id, fixture.author_id).await;
+        assert_eq!(status, StatusCode::OK);
+        let content = sqlx::query_scalar!("SELECT content FROM comments WHERE id = $1", fixture.comment_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(content, "Edited");
+
+        let (status, _) = send(&app, &fixture, Method::DELETE, fixture.post_id, fixture.author_id).await;
+        assert_eq!(status, StatusCode::OK);
+        let deleted_at = sqlx::query_scalar!("SELECT deleted_at FROM comments WHERE id = $1", fixture.comment_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(deleted_at.is_some());
+    }
+}
@@ -1,129 +1,100 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use chrono::Utc;
 use sqlx::PgPool;
+use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::middleware::auth::get_user_id_from_request;
-use crate::models::{ApiResponse, PaginatedResponse, PaginationParams, UserResponse};
+use crate::error::AppError;
+use crate::middleware::auth::{AuthenticatedUser, MaybeUser};
+use crate::events::{emit, DomainEvent};
+use crate::models::{ApiResponse, FieldsParams, PaginatedResponse, PaginationParams, Relationship, UserResponse};
+use crate::utils::fields::{shape, FieldSelection};
+use crate::utils::pagination::Page;
+
+#[derive(serde::Serialize)]
+struct FollowResponse {
+    following: bool,
+    follower_count: i64,
+}
+
+async fn count_followers(pool: &PgPool, user_id: Uuid) -> i64 {
+    let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM follows WHERE following_id = $1")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .unwrap_or((0,));
+    count.0
+}
 
 pub async fn follow_user(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(follower_id): AuthenticatedUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let following_id = path.into_inner();
-    let follower_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
     // Can't follow yourself
     if follower_id == following_id {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            "You cannot follow yourself".to_string(),
-        )));
+        return Err(AppError::BadRequest("You cannot follow yourself".to_string()));
     }
 
     // Check if user exists
     let user_exists = sqlx::query!(
-        "SELECT id FROM users WHERE id = $1",
-        following_id
-    )
-    .fetch_optional(pool.get_ref())
-    .await;
-
-    match user_exists {
-        Ok(None) => {
-            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-                "User not found".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(Some(_)) => {}
-    }
-
-    // Check if already following
-    let existing_follow = sqlx::query!(
-        "SELECT id FROM follows WHERE follower_id = $1 AND following_id = $2",
-        follower_id,
+        "SELECT id FROM users WHERE id = $1 AND deactivated_at IS NULL",
         following_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?;
 
-    match existing_follow {
-        Ok(Some(_)) => {
-            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
-                "Already following this user".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(None) => {}
+    if user_exists.is_none() {
+        return Err(AppError::NotFound("User not found".to_string()));
     }
 
-    // Create follow relationship
-    let follow_id = Uuid::new_v4();
+    // A concurrent request can follow between any check and the insert, so
+    // the unique constraint decides
+    let mut tx = pool.begin().await?;
     let result = sqlx::query!(
         r#"
         INSERT INTO follows (id, follower_id, following_id, created_at)
         VALUES ($1, $2, $3, $4)
+        ON CONFLICT (follower_id, following_id) DO NOTHING
         "#,
-        follow_id,
+        Uuid::new_v4(),
         follower_id,
         following_id,
         Utc::now()
     )
-    .execute(pool.get_ref())
-    .await;
-
-    match result {
-        Ok(_) => {
-            #[derive(serde::Serialize)]
-            struct FollowResponse {
-                following: bool,
-            }
+    .execute(&mut *tx)
+    .await?;
 
-            Ok(HttpResponse::Created().json(ApiResponse::success(FollowResponse {
+    if result.rows_affected() == 0 {
+        // Give the connection back before counting on another one
+        tx.rollback().await?;
+        // Return the current state so the client can reconcile without refetching
+        return Err(AppError::conflict_with_state(
+            "Already following this user",
+            FollowResponse {
                 following: true,
-            })))
-        }
-        Err(e) => {
-            log::error!("Failed to create follow: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to follow user".to_string(),
-            )))
-        }
+                follower_count: count_followers(&pool, following_id).await,
+            },
+        ));
     }
+
+    emit(&mut tx, &DomainEvent::UserFollowed { follower_id, following_id }).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(FollowResponse {
+        following: true,
+        follower_count: count_followers(&pool, following_id).await,
+    })))
 }
 
 pub async fn unfollow_user(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(follower_id): AuthenticatedUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let following_id = path.into_inner();
-    let follower_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
     let result = sqlx::query!(
         "DELETE FROM follows WHERE follower_id = $1 AND following_id = $2",
@@ -131,53 +102,61 @@ pub async fn unfollow_user(
         following_id
     )
     .execute(pool.get_ref())
-    .await;
+    .await?;
 
-    match result {
-        Ok(result) if result.rows_affected() > 0 => {
-            #[derive(serde::Serialize)]
-            struct FollowResponse {
-                following: bool,
-            }
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Follow relationship not found".to_string()));
+    }
 
-            Ok(HttpResponse::Ok().json(ApiResponse::success(FollowResponse {
-                following: false,
-            })))
-        }
-        Ok(_) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Follow relationship not found".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Failed to unfollow user: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to unfollow user".to_string(),
-            )))
-        }
+    Ok(HttpResponse::Ok().json(ApiResponse::success(FollowResponse {
+        following: false,
+        follower_count: count_followers(&pool, following_id).await,
+    })))
+}
+
+// Removes someone from the current user's followers without blocking them;
+// they can follow again
+pub async fn remove_follower(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let follower_id = path.into_inner();
+
+    let result = sqlx::query!(
+        "DELETE FROM follows WHERE follower_id = $1 AND following_id = $2",
+        follower_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Follower not found".to_string()));
     }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Follower removed")))
 }
 
 pub async fn get_followers(
     pool: web::Data<PgPool>,
+    MaybeUser(viewer_id): MaybeUser,
     path: web::Path<Uuid>,
     query: web::Query<PaginationParams>,
-) -> Result<HttpResponse> {
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
     let user_id = path.into_inner();
     let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
     // Get total count
     let total: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM follows WHERE following_id = $1"
+        "SELECT COUNT(*) FROM follows f INNER JOIN users u ON u.id = f.follower_id WHERE f.following_id = $1 AND u.deactivated_at IS NULL"
     )
     .bind(user_id)
     .fetch_one(pool.get_ref())
-    .await
-    .map_err(|e| {
-        log::error!("Database error: {:?}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+    .await?;
 
     // Get followers
     let followers = sqlx::query!(
@@ -189,79 +168,68 @@ pub async fn get_followers(
         INNER JOIN follows f ON u.id = f.follower_id
         LEFT JOIN follows f1 ON u.id = f1.following_id
         LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE f.following_id = $1
+        WHERE f.following_id = $1 AND u.deactivated_at IS NULL
         GROUP BY u.id, f.created_at
-        ORDER BY f.created_at DESC
+        ORDER BY f.created_at DESC, u.id DESC
         LIMIT $2 OFFSET $3
         "#,
         user_id,
         limit as i64,
-        offset as i64
+        offset
     )
     .fetch_all(pool.get_ref())
-    .await;
-
-    match followers {
-        Ok(followers) => {
-            let user_responses: Vec<UserResponse> = followers
-                .into_iter()
-                .map(|user| UserResponse {
-                    id: user.id,
-                    username: user.username,
-                    email: user.email,
-                    full_name: user.full_name,
-                    bio: user.bio,
-                    avatar_url: user.avatar_url,
-                    is_verified: user.is_verified.unwrap_or(false),
-                    follower_count: user.follower_count,
-                    following_count: user.following_count,
-                    created_at: user.created_at.unwrap(),
-                })
-                .collect();
-
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
-
-            let paginated_response = PaginatedResponse {
-                data: user_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
-
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
-        }
-    }
+    .await?;
+
+    let mut user_responses: Vec<UserResponse> = followers
+        .into_iter()
+        .map(|user| UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            follower_count: user.follower_count,
+            following_count: user.following_count,
+            created_at: user.created_at.unwrap(),
+            relationship: None,
+        })
+        .collect();
+    attach_relationships(&pool, viewer_id, &mut user_responses).await?;
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: shape(user_responses, fields.as_ref())?,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
 }
 
 pub async fn get_following(
     pool: web::Data<PgPool>,
+    MaybeUser(viewer_id): MaybeUser,
     path: web::Path<Uuid>,
     query: web::Query<PaginationParams>,
-) -> Result<HttpResponse> {
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
     let user_id = path.into_inner();
     let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
     // Get total count
     let total: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM follows WHERE follower_id = $1"
+        "SELECT COUNT(*) FROM follows f INNER JOIN users u ON u.id = f.following_id WHERE f.follower_id = $1 AND u.deactivated_at IS NULL"
     )
     .bind(user_id)
     .fetch_one(pool.get_ref())
-    .await
-    .map_err(|e| {
-        log::error!("Database error: {:?}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+    .await?;
 
     // Get following
     let following = sqlx::query!(
@@ -273,53 +241,109 @@ pub async fn get_following(
         INNER JOIN follows f ON u.id = f.following_id
         LEFT JOIN follows f1 ON u.id = f1.following_id
         LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE f.follower_id = $1
+        WHERE f.follower_id = $1 AND u.deactivated_at IS NULL
         GROUP BY u.id, f.created_at
-        ORDER BY f.created_at DESC
+        ORDER BY f.created_at DESC, u.id DESC
         LIMIT $2 OFFSET $3
         "#,
         user_id,
         limit as i64,
-        offset as i64
+        offset
     )
     .fetch_all(pool.get_ref())
-    .await;
-
-    match following {
-        Ok(following) => {
-            let user_responses: Vec<UserResponse> = following
-                .into_iter()
-                .map(|user| UserResponse {
-                    id: user.id,
-                    username: user.username,
-                    email: user.email,
-                    full_name: user.full_name,
-                    bio: user.bio,
-                    avatar_url: user.avatar_url,
-                    is_verified: user.is_verified.unwrap_or(false),
-                    follower_count: user.follower_count,
-                    following_count: user.following_count,
-                    created_at: user.created_at.unwrap(),
-                })
-                .collect();
-
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
-
-            let paginated_response = PaginatedResponse {
-                data: user_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
-
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
+    .await?;
+
+    let mut user_responses: Vec<UserResponse> = following
+        .into_iter()
+        .map(|user| UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            follower_count: user.follower_count,
+            following_count: user.following_count,
+            created_at: user.created_at.unwrap(),
+            relationship: None,
+        })
+        .collect();
+    attach_relationships(&pool, viewer_id, &mut user_responses).await?;
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: shape(user_responses, fields.as_ref())?,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+pub async fn get_my_followers(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    get_followers(pool, MaybeUser(Some(user_id)), web::Path::from(user_id), query, fields_query).await
+}
+
+pub async fn get_my_following(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    get_following(pool, MaybeUser(Some(user_id)), web::Path::from(user_id), query, fields_query).await
+}
+
+/// Fills in how `viewer_id` relates to each user, with one query for the
+/// whole list. Signed-out viewers and the viewer's own entry get none.
+pub async fn attach_relationships(
+    pool: &PgPool,
+    viewer_id: Option<Uuid>,
+    users: &mut [UserResponse],
+) -> Result<(), sqlx::Error> {
+    let Some(viewer_id) = viewer_id else {
+        return Ok(());
+    };
+
+    let user_ids: Vec<Uuid> = users.iter().map(|user| user.id).filter(|id| *id != viewer_id).collect();
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let follows = sqlx::query!(
+        r#"
+        SELECT follower_id, following_id FROM follows
+        WHERE (follower_id = $1 AND following_id = ANY($2))
+           OR (following_id = $1 AND follower_id = ANY($2))
+        "#,
+        viewer_id,
+        &user_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut relationships: HashMap<Uuid, Relationship> =
+        user_ids.into_iter().map(|id| (id, Relationship::default())).collect();
+    for follow in follows {
+        if follow.follower_id == viewer_id {
+            if let Some(relationship) = relationships.get_mut(&follow.following_id) {
+                relationship.is_following = true;
+            }
+        } else if let Some(relationship) = relationships.get_mut(&follow.follower_id) {
+            relationship.is_followed_by = true;
         }
     }
+
+    for user in users {
+        user.relationship = relationships.get(&user.id).copied();
+    }
+    Ok(())
 }
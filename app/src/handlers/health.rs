@@ -0,0 +1,117 @@
+use actix_web::{web, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::time::Duration;
+
+use crate::MIGRATOR;
+
+// A probe that hangs is worse than one that fails, so the database gets a
+// short deadline
+const DATABASE_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+struct HealthReport {
+    status: &'static str,
+    checks: Vec<ComponentCheck>,
+}
+
+#[derive(Serialize)]
+struct ComponentCheck {
+    component: &'static str,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+impl ComponentCheck {
+    fn ok(component: &'static str) -> Self {
+        Self {
+            component,
+            status: "ok",
+            detail: None,
+        }
+    }
+
+    fn failed(component: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            component,
+            status: "failed",
+            detail: Some(detail.into()),
+        }
+    }
+}
+
+fn report(checks: Vec<ComponentCheck>) -> HttpResponse {
+    let healthy = checks.iter().all(|check| check.status == "ok");
+    let report = HealthReport {
+        status: if healthy { "ok" } else { "unavailable" },
+        checks,
+    };
+
+    if healthy {
+        HttpResponse::Ok().json(report)
+    } else {
+        HttpResponse::ServiceUnavailable().json(report)
+    }
+}
+
+// Liveness: answers as long as the process can serve requests at all
+pub async fn liveness() -> HttpResponse {
+    report(vec![ComponentCheck::ok("process")])
+}
+
+// Readiness: only route traffic here while the database is reachable and
+// on the schema this build expects
+pub async fn readiness(pool: web::Data<PgPool>) -> HttpResponse {
+    let database = check_database(&pool).await;
+    let migrations = if database.status == "ok" {
+        check_migrations(&pool).await
+    } else {
+        ComponentCheck::failed("migrations", "database unavailable")
+    };
+
+    report(vec![database, migrations])
+}
+
+// Startup: migrations run before the server binds, so this passes as soon
+// as the server is listening with the schema in place
+pub async fn startup(pool: web::Data<PgPool>) -> HttpResponse {
+    report(vec![check_migrations(&pool).await])
+}
+
+async fn check_database(pool: &PgPool) -> ComponentCheck {
+    let ping = sqlx::query("SELECT 1").execute(pool);
+    match tokio::time::timeout(DATABASE_TIMEOUT, ping).await {
+        Ok(Ok(_)) => ComponentCheck::ok("database"),
+        Ok(Err(e)) => {
+            log::warn!("Readiness database check failed: {:?}", e);
+            ComponentCheck::failed("database", "query failed")
+        }
+        Err(_) => ComponentCheck::failed("database", "timed out"),
+    }
+}
+
+async fn check_migrations(pool: &PgPool) -> ComponentCheck {
+    let applied = sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success = true")
+        .fetch_all(pool);
+    let applied: HashSet<i64> = match tokio::time::timeout(DATABASE_TIMEOUT, applied).await {
+        Ok(Ok(versions)) => versions.into_iter().collect(),
+        Ok(Err(e)) => {
+            log::warn!("Migration check failed: {:?}", e);
+            return ComponentCheck::failed("migrations", "query failed");
+        }
+        Err(_) => return ComponentCheck::failed("migrations", "timed out"),
+    };
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .count();
+
+    if pending == 0 {
+        ComponentCheck::ok("migrations")
+    } else {
+        ComponentCheck::failed("migrations", format!("{} pending", pending))
+    }
+}
@@ -0,0 +1,13 @@
+use actix_web::http::header;
+use actix_web::{web, HttpResponse};
+
+use crate::config::Config;
+use crate::error::AppError;
+
+// Public keys other services use to verify our access tokens. Empty while
+// tokens are signed with the shared HS256 secret.
+pub async fn get_jwks(config: web::Data<Config>) -> Result<HttpResponse, AppError> {
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .json(config.jwt.keys.jwks()))
+}
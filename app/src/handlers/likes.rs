@@ -1,135 +1,96 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::{web, HttpResponse};
 use chrono::Utc;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::middleware::auth::get_user_id_from_request;
+use crate::error::AppError;
+use crate::middleware::auth::AuthenticatedUser;
 use crate::models::ApiResponse;
 
+#[derive(serde::Serialize)]
+struct LikeResponse {
+    like_count: i64,
+    is_liked: bool,
+}
+
+async fn like_count(pool: &PgPool, post_id: Uuid) -> i64 {
+    let count: (i64,) = sqlx::query_as("SELECT like_count FROM posts WHERE id = $1")
+        .bind(post_id)
+        .fetch_one(pool)
+        .await
+        .unwrap_or((0,));
+    count.0
+}
+
+async fn comment_like_count(pool: &PgPool, comment_id: Uuid) -> i64 {
+    let count: (i64,) = sqlx::query_as("SELECT like_count FROM comments WHERE id = $1")
+        .bind(comment_id)
+        .fetch_one(pool)
+        .await
+        .unwrap_or((0,));
+    count.0
+}
+
 pub async fn like_post(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
     // Check if post exists
     let post_exists = sqlx::query!(
-        "SELECT id FROM posts WHERE id = $1 AND is_published = true",
+        "SELECT id FROM posts WHERE id = $1 AND is_published = true AND deleted_at IS NULL",
         post_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
-
-    match post_exists {
-        Ok(None) => {
-            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-                "Post not found".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(Some(_)) => {}
-    }
+    .await?;
 
-    // Check if user already liked the post
-    let existing_like = sqlx::query!(
-        "SELECT id FROM likes WHERE post_id = $1 AND user_id = $2",
-        post_id,
-        user_id
-    )
-    .fetch_optional(pool.get_ref())
-    .await;
-
-    match existing_like {
-        Ok(Some(_)) => {
-            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
-                "Post already liked".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(None) => {}
+    if post_exists.is_none() {
+        return Err(AppError::NotFound("Post not found".to_string()));
     }
 
-    // Create like
-    let like_id = Uuid::new_v4();
+    // A concurrent request can like the post between any check and the
+    // insert, so the unique constraint decides
     let result = sqlx::query!(
         r#"
         INSERT INTO likes (id, user_id, post_id, created_at)
         VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, post_id) DO NOTHING
         "#,
-        like_id,
+        Uuid::new_v4(),
         user_id,
         post_id,
         Utc::now()
     )
     .execute(pool.get_ref())
-    .await;
-
-    match result {
-        Ok(_) => {
-            // Get updated like count
-            let like_count: (i64,) = sqlx::query_as(
-                "SELECT COUNT(*) FROM likes WHERE post_id = $1"
-            )
-            .bind(post_id)
-            .fetch_one(pool.get_ref())
-            .await
-            .unwrap_or((0,));
-
-            #[derive(serde::Serialize)]
-            struct LikeResponse {
-                like_count: i64,
-                is_liked: bool,
-            }
-
-            let response = LikeResponse {
-                like_count: like_count.0,
+    .await?;
+
+    if result.rows_affected() == 0 {
+        // Return the current state so the client can reconcile without refetching
+        return Err(AppError::conflict_with_state(
+            "Post already liked",
+            LikeResponse {
+                like_count: like_count(&pool, post_id).await,
                 is_liked: true,
-            };
-
-            Ok(HttpResponse::Created().json(ApiResponse::success(response)))
-        }
-        Err(e) => {
-            log::error!("Failed to create like: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to like post".to_string(),
-            )))
-        }
+            },
+        ));
     }
+
+    let response = LikeResponse {
+        like_count: like_count(&pool, post_id).await,
+        is_liked: true,
+    };
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(response)))
 }
 
 pub async fn unlike_post(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
     let result = sqlx::query!(
         "DELETE FROM likes WHERE post_id = $1 AND user_id = $2",
@@ -137,40 +98,103 @@ pub async fn unlike_post(
         user_id
     )
     .execute(pool.get_ref())
-    .await;
-
-    match result {
-        Ok(result) if result.rows_affected() > 0 => {
-            // Get updated like count
-            let like_count: (i64,) = sqlx::query_as(
-                "SELECT COUNT(*) FROM likes WHERE post_id = $1"
-            )
-            .bind(post_id)
-            .fetch_one(pool.get_ref())
-            .await
-            .unwrap_or((0,));
-
-            #[derive(serde::Serialize)]
-            struct LikeResponse {
-                like_count: i64,
-                is_liked: bool,
-            }
-
-            let response = LikeResponse {
-                like_count: like_count.0,
-                is_liked: false,
-            };
-
-            Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
-        }
-        Ok(_) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Like not found".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Failed to unlike post: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to unlike post".to_string(),
-            )))
-        }
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Like not found".to_string()));
+    }
+
+    let response = LikeResponse {
+        like_count: like_count(&pool, post_id).await,
+        is_liked: false,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+pub async fn like_comment(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, comment_id) = path.into_inner();
+
+    // Deleted or hidden comments and comments on posts that aren't public
+    // can't be liked
+    let comment_exists = sqlx::query!(
+        r#"
+        SELECT c.id FROM comments c
+        INNER JOIN posts p ON c.post_id = p.id
+        WHERE c.id = $1 AND c.post_id = $2 AND c.deleted_at IS NULL
+          AND c.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND (c.hidden_at IS NULL OR c.author_id = $3)
+          AND p.is_published = true AND p.deleted_at IS NULL
+        "#,
+        comment_id,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if comment_exists.is_none() {
+        return Err(AppError::NotFound("Comment not found".to_string()));
+    }
+
+    let result = sqlx::query!(
+        "INSERT INTO comment_likes (comment_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        comment_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::conflict_with_state(
+            "Comment already liked",
+            LikeResponse {
+                like_count: comment_like_count(&pool, comment_id).await,
+                is_liked: true,
+            },
+        ));
     }
+
+    let response = LikeResponse {
+        like_count: comment_like_count(&pool, comment_id).await,
+        is_liked: true,
+    };
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(response)))
+}
+
+pub async fn unlike_comment(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, comment_id) = path.into_inner();
+
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM comment_likes
+        WHERE comment_id = $1 AND user_id = $2
+          AND comment_id IN (SELECT id FROM comments WHERE post_id = $3)
+        "#,
+        comment_id,
+        user_id,
+        post_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Like not found".to_string()));
+    }
+
+    let response = LikeResponse {
+        like_count: comment_like_count(&pool, comment_id).await,
+        is_liked: false,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
 }
@@ -0,0 +1,344 @@
+use actix_multipart::form::{MultipartForm, MultipartFormConfig};
+use actix_multipart::MultipartError;
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use std::env;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::images::{render_presets, spawn_variant_generation, AVATAR_PRESETS};
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ApiResponse, Media, MediaResponse, MediaUploadForm};
+use crate::storage::Storage;
+use crate::utils::media::absolute_media_url;
+
+// Uploads are checked by their content rather than the declared type
+const ALLOWED_TYPES: &[(&str, &str)] = &[
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/gif", "gif"),
+    ("image/webp", "webp"),
+];
+
+// Everything is stored under this prefix, which is also the path the files
+// are served from
+const KEY_PREFIX: &str = "media";
+
+/// Limits for the upload form; bodies over `max_bytes` are rejected with 413
+/// before they are fully read.
+pub fn upload_config(max_bytes: usize) -> MultipartFormConfig {
+    MultipartFormConfig::default()
+        .total_limit(max_bytes)
+        .memory_limit(max_bytes)
+        .error_handler(move |err, _| match err {
+            MultipartError::Payload(_) => AppError::coded(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "MEDIA_TOO_LARGE",
+                format!("Uploads are limited to {} bytes", max_bytes),
+            )
+            .into(),
+            err => AppError::BadRequest(err.to_string()).into(),
+        })
+}
+
+// With MEDIA_BASE_URL unset, files are assumed to be served by this API
+fn media_url(config: &Config, key: &str) -> String {
+    if env::var("MEDIA_BASE_URL").is_ok_and(|base| !base.trim().is_empty()) {
+        absolute_media_url(key)
+    } else {
+        format!("{}/{}", config.oauth.public_api_url, key)
+    }
+}
+
+async fn media_response(pool: &PgPool, config: &Config, media: Media) -> Result<MediaResponse, AppError> {
+    let variants = sqlx::query!(
+        "SELECT name, storage_key FROM media_variants WHERE media_id = $1",
+        media.id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|variant| (variant.name, media_url(config, &variant.storage_key)))
+    .collect::<BTreeMap<_, _>>();
+
+    Ok(MediaResponse {
+        id: media.id,
+        url: media_url(config, &media.storage_key),
+        content_type: media.content_type,
+        size_bytes: media.size_bytes,
+        variants,
+        created_at: media.created_at,
+    })
+}
+
+async fn find_media(pool: &PgPool, media_id: Uuid) -> Result<Media, AppError> {
+    sqlx::query_as!(
+        Media,
+        "SELECT id, owner_id, storage_key, content_type, size_bytes, created_at FROM media WHERE id = $1",
+        media_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))
+}
+
+fn detect_image_type(data: &[u8]) -> Result<(&'static str, &'static str), AppError> {
+    let detected = infer::get(data).map(|kind| kind.mime_type());
+    ALLOWED_TYPES
+        .iter()
+        .find(|(mime, _)| Some(*mime) == detected)
+        .copied()
+        .ok_or_else(|| {
+            AppError::coded(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "UNSUPPORTED_MEDIA_TYPE",
+                "Only JPEG, PNG, GIF and WebP images can be uploaded",
+            )
+        })
+}
+
+// Takes a single image in the `file` field of a multipart form. Variants are
+// generated in the background; poll GET /media/{media_id} for them.
+pub async fn upload_media(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    storage: web::Data<dyn Storage>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    MultipartForm(form): MultipartForm<MediaUploadForm>,
+) -> Result<HttpResponse, AppError> {
+    let data = form.file.data;
+    let (content_type, extension) = detect_image_type(&data)?;
+
+    let media_id = Uuid::new_v4();
+    let key = format!("{}/{}.{}", KEY_PREFIX, media_id, extension);
+    let size_bytes = data.len() as i64;
+
+    storage.put(&key, content_type, data.clone()).await.map_err(|e| {
+        log::error!("Failed to store upload {}: {}", key, e);
+        AppError::Internal("Failed to store upload".to_string())
+    })?;
+
+    let media = sqlx::query_as!(
+        Media,
+        r#"
+        INSERT INTO media (id, owner_id, storage_key, content_type, size_bytes, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, owner_id, storage_key, content_type, size_bytes, created_at
+        "#,
+        media_id,
+        user_id,
+        key,
+        content_type,
+        size_bytes,
+        Utc::now()
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    spawn_variant_generation(
+        pool.get_ref().clone(),
+        storage.into_inner(),
+        media.id,
+        media.storage_key.clone(),
+        data,
+    );
+
+    let response = media_response(pool.get_ref(), &config, media).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(response)))
+}
+
+// Crops the image in the `file` field to the avatar sizes and makes it the
+// user's avatar. The previous avatar's upload, if any, is kept.
+pub async fn upload_avatar(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    storage: web::Data<dyn Storage>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    MultipartForm(form): MultipartForm<MediaUploadForm>,
+) -> Result<HttpResponse, AppError> {
+    let data = form.file.data;
+    detect_image_type(&data)?;
+
+    let rendered = render_presets(data, AVATAR_PRESETS).await.map_err(|e| {
+        log::warn!("Failed to process avatar: {}", e);
+        AppError::BadRequest("The image could not be processed".to_string())
+    })?;
+
+    // The first size is the avatar itself, the rest are stored as its variants
+    let media_id = Uuid::new_v4();
+    let files = rendered
+        .into_iter()
+        .enumerate()
+        .map(|(i, (preset, image))| {
+            let key = match i {
+                0 => format!("{}/{}.{}", KEY_PREFIX, media_id, image.extension),
+                _ => format!("{}/{}_{}.{}", KEY_PREFIX, media_id, preset.name, image.extension),
+            };
+            (key, preset, image)
+        })
+        .collect::<Vec<_>>();
+    let keys = files.iter().map(|(key, _, _)| key.as_str()).collect::<Vec<_>>();
+
+    for (key, _, image) in &files {
+        if let Err(e) = storage.put(key, image.content_type, image.data.clone()).await {
+            log::error!("Failed to store avatar {}: {}", key, e);
+            discard(storage.get_ref(), &keys).await;
+            return Err(AppError::Internal("Failed to store upload".to_string()));
+        }
+    }
+
+    // The avatar only changes once every size is stored and recorded
+    let recorded = async {
+        let mut tx = pool.begin().await?;
+
+        let media = sqlx::query_as!(
+            Media,
+            r#"
+            INSERT INTO media (id, owner_id, storage_key, content_type, size_bytes, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id, owner_id, storage_key, content_type, size_bytes, created_at
+            "#,
+            media_id,
+            user_id,
+            files[0].0,
+            files[0].2.content_type,
+            files[0].2.data.len() as i64,
+            Utc::now()
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for (key, preset, image) in &files[1..] {
+            sqlx::query!(
+                r#"
+                INSERT INTO media_variants (media_id, name, storage_key, content_type, width, height, size_bytes)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                media_id,
+                preset.name,
+                key,
+                image.content_type,
+                image.width as i32,
+                image.height as i32,
+                image.data.len() as i64
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE users SET avatar_url = $2, updated_at = $3 WHERE id = $1",
+            user_id,
+            media_url(&config, &media.storage_key),
+            Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>(media)
+    }
+    .await;
+
+    let media = match recorded {
+        Ok(media) => media,
+        Err(e) => {
+            discard(storage.get_ref(), &keys).await;
+            return Err(e.into());
+        }
+    };
+
+    let response = media_response(pool.get_ref(), &config, media).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+// Best-effort cleanup of files stored for a request that then failed
+async fn discard(storage: &dyn Storage, keys: &[&str]) {
+    for key in keys {
+        if let Err(e) = storage.delete(key).await {
+            log::warn!("Failed to remove orphaned upload {}: {}", key, e);
+        }
+    }
+}
+
+pub async fn get_media(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let media = find_media(pool.get_ref(), path.into_inner()).await?;
+    let response = media_response(pool.get_ref(), &config, media).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(response)))
+}
+
+// Posts or profiles still pointing at the file are left as they are
+pub async fn delete_media(
+    pool: web::Data<PgPool>,
+    storage: web::Data<dyn Storage>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let media = find_media(pool.get_ref(), path.into_inner()).await?;
+    if media.owner_id != user_id {
+        return Err(AppError::Forbidden("You can only delete your own uploads".to_string()));
+    }
+
+    let variant_keys = sqlx::query_scalar!("SELECT storage_key FROM media_variants WHERE media_id = $1", media.id)
+        .fetch_all(pool.get_ref())
+        .await?;
+
+    for key in variant_keys.iter().chain([&media.storage_key]) {
+        storage.delete(key).await.map_err(|e| {
+            log::error!("Failed to delete upload {}: {}", key, e);
+            AppError::Internal("Failed to delete upload".to_string())
+        })?;
+    }
+
+    sqlx::query!("DELETE FROM media WHERE id = $1", media.id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Media deleted")))
+}
+
+// Serves uploads and their variants when the API itself is their public
+// origin. Only keys recorded in the database are looked up.
+pub async fn serve_media(
+    pool: web::Data<PgPool>,
+    storage: web::Data<dyn Storage>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let key = format!("{}/{}", KEY_PREFIX, path.into_inner());
+
+    let content_type = sqlx::query_scalar!(
+        r#"
+        SELECT content_type AS "content_type!" FROM media WHERE storage_key = $1
+        UNION ALL
+        SELECT content_type FROM media_variants WHERE storage_key = $1
+        "#,
+        key
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+
+    let data = storage
+        .get(&key)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read upload {}: {}", key, e);
+            AppError::Internal("Failed to read upload".to_string())
+        })?
+        .ok_or_else(|| AppError::NotFound("Media not found".to_string()))?;
+
+    // Keys are never reused, so the content can be cached indefinitely
+    Ok(HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .body(data))
+}
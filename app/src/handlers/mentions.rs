@@ -0,0 +1,159 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use sqlx::{Acquire, PgPool, Postgres};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ApiResponse, MentionResponse, PaginatedResponse, PaginationParams};
+use crate::utils::mentions::extract_mentions;
+use crate::utils::pagination::Page;
+
+const SNIPPET_LENGTH: usize = 200;
+
+pub async fn get_my_mentions(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let total: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM mentions m
+        INNER JOIN posts p ON m.post_id = p.id
+        LEFT JOIN comments c ON m.comment_id = c.id
+        INNER JOIN users u ON m.author_id = u.id
+        WHERE m.mentioned_user_id = $1 AND p.is_published = true AND p.deleted_at IS NULL
+          AND c.deleted_at IS NULL AND c.hidden_at IS NULL AND u.deactivated_at IS NULL
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    // Mentions in drafts stay hidden until the post is published, and ones in
+    // comments a moderator hid stay hidden with the comment
+    let mentions = sqlx::query!(
+        r#"
+        SELECT m.id, m.post_id, m.comment_id, m.created_at,
+               p.title as post_title, p.slug as post_slug,
+               COALESCE(c.content, p.content) as "content!",
+               u.id as author_id, u.username as author_username
+        FROM mentions m
+        INNER JOIN posts p ON m.post_id = p.id
+        LEFT JOIN comments c ON m.comment_id = c.id
+        INNER JOIN users u ON m.author_id = u.id
+        WHERE m.mentioned_user_id = $1 AND p.is_published = true AND p.deleted_at IS NULL
+          AND c.deleted_at IS NULL AND c.hidden_at IS NULL AND u.deactivated_at IS NULL
+        ORDER BY m.created_at DESC, m.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let mention_responses: Vec<MentionResponse> = mentions
+        .into_iter()
+        .map(|mention| MentionResponse {
+            id: mention.id,
+            source: if mention.comment_id.is_some() { "comment" } else { "post" }.to_string(),
+            post_id: mention.post_id,
+            post_title: mention.post_title,
+            post_slug: mention.post_slug,
+            comment_id: mention.comment_id,
+            snippet: mention.content.chars().take(SNIPPET_LENGTH).collect(),
+            author_id: mention.author_id,
+            author_username: mention.author_username,
+            created_at: mention.created_at.unwrap(),
+        })
+        .collect();
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: mention_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// Brings the mentions recorded for a post body (comment_id = None) or a
+// comment in line with its content, returning the users who weren't
+// mentioned in it before. Mentions that stay keep their original time, so an
+// edit doesn't bump them in the mentioned user's list. Takes the pool or a
+// transaction.
+pub async fn sync_mentions<'c>(
+    conn: impl Acquire<'c, Database = Postgres>,
+    post_id: Uuid,
+    comment_id: Option<Uuid>,
+    author_id: Uuid,
+    content: &str,
+) -> Result<Vec<Uuid>, sqlx::Error> {
+    let mut conn = conn.acquire().await?;
+    let usernames = extract_mentions(content);
+
+    // Mentions of users no longer named in the content
+    match comment_id {
+        Some(comment_id) => {
+            sqlx::query!(
+                r#"
+                DELETE FROM mentions m
+                WHERE m.comment_id = $1
+                  AND NOT EXISTS (SELECT 1 FROM users u WHERE u.id = m.mentioned_user_id AND u.username = ANY($2))
+                "#,
+                comment_id,
+                &usernames[..]
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        None => {
+            sqlx::query!(
+                r#"
+                DELETE FROM mentions m
+                WHERE m.post_id = $1 AND m.comment_id IS NULL
+                  AND NOT EXISTS (SELECT 1 FROM users u WHERE u.id = m.mentioned_user_id AND u.username = ANY($2))
+                "#,
+                post_id,
+                &usernames[..]
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+    }
+
+    if usernames.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Unknown usernames and self-mentions are silently ignored, and users
+    // already mentioned are left as they are
+    let mentioned = sqlx::query_scalar!(
+        r#"
+        INSERT INTO mentions (id, mentioned_user_id, author_id, post_id, comment_id, created_at)
+        SELECT uuid_generate_v4(), u.id, $1, $2, $3, $4
+        FROM users u
+        WHERE u.username = ANY($5) AND u.id <> $1
+        ON CONFLICT DO NOTHING
+        RETURNING mentioned_user_id
+        "#,
+        author_id,
+        post_id,
+        comment_id,
+        Utc::now(),
+        &usernames[..]
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    Ok(mentioned)
+}
@@ -0,0 +1,11 @@
+use actix_web::{web, HttpResponse};
+use prometheus::TEXT_FORMAT;
+use sqlx::PgPool;
+
+use crate::metrics::Metrics;
+
+pub async fn get_metrics(metrics: web::Data<Metrics>, pool: web::Data<PgPool>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(TEXT_FORMAT)
+        .body(metrics.render(&pool))
+}
@@ -4,4 +4,20 @@ pub mod users;
 pub mod comments;
 pub mod likes;
 pub mod follows;
-pub mod tags;
\ No newline at end of file
+pub mod tags;
+pub mod mentions;
+pub mod admin;
+pub mod analytics;
+pub mod oauth;
+pub mod api_keys;
+pub mod jwks;
+pub mod health;
+pub mod metrics;
+pub mod media;
+pub mod realtime;
+pub mod webhooks;
+pub mod notification_settings;
+pub mod bookmarks;
+pub mod reading_lists;
+pub mod coauthors;
+pub mod reports;
@@ -0,0 +1,84 @@
+use actix_web::{web, HttpResponse};
+use sqlx::types::Json;
+use sqlx::PgPool;
+
+use crate::error::AppError;
+use crate::middleware::auth::AuthenticatedUser;
+use crate::models::{ApiResponse, ChannelPreferences, NotificationSettingsResponse, UpdateNotificationSettingsRequest};
+use crate::notifications::NotificationSettings;
+
+pub async fn get_notification_settings(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let settings = NotificationSettings::load(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(NotificationSettingsResponse {
+        channels: settings.resolved_channels(),
+        digest: settings.digest_frequency,
+    })))
+}
+
+// Merged into the stored settings, so clients can send just what changed
+pub async fn update_notification_settings(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<UpdateNotificationSettingsRequest>,
+) -> Result<HttpResponse, AppError> {
+    let req = req.into_inner();
+    let mut tx = pool.begin().await?;
+
+    // Created first so concurrent updates queue on the row lock below
+    sqlx::query!(
+        "INSERT INTO notification_settings (user_id) VALUES ($1) ON CONFLICT (user_id) DO NOTHING",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let current = sqlx::query!(
+        r#"
+        SELECT channels as "channels: Json<ChannelPreferences>", digest_frequency
+        FROM notification_settings
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let mut channels = current.channels.0;
+    for (channel, kinds) in req.channels {
+        channels.entry(channel).or_default().extend(kinds);
+    }
+
+    let digest = req.digest.map(|digest| digest.as_str().to_string()).unwrap_or(current.digest_frequency.clone());
+    // A changed digest schedule starts over rather than catching up on the
+    // period it was off or on a different schedule
+    let restart_digest = digest != current.digest_frequency;
+
+    sqlx::query!(
+        r#"
+        UPDATE notification_settings
+        SET channels = $2, digest_frequency = $3,
+            last_digest_at = CASE WHEN $4 THEN NULL ELSE last_digest_at END,
+            updated_at = NOW()
+        WHERE user_id = $1
+        "#,
+        user_id,
+        Json(&channels) as _,
+        digest,
+        restart_digest
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let settings = NotificationSettings::load(pool.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(NotificationSettingsResponse {
+        channels: settings.resolved_channels(),
+        digest: settings.digest_frequency,
+    })))
+}
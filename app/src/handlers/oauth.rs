@@ -0,0 +1,458 @@
+use actix_web::cookie::{time::Duration as CookieDuration, Cookie, SameSite};
+use actix_web::http::{header, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use reqwest::header::{ACCEPT, USER_AGENT};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Transaction};
+use url::Url;
+use uuid::Uuid;
+
+use crate::config::{Config, OAuthClientConfig};
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::middleware::auth::{account_banned, account_suspended, client_fingerprint, create_default_user_data, issue_tokens, AuthResponse};
+use crate::models::{ApiResponse, OAuthCallbackParams, User};
+use crate::utils::tokens::generate_token;
+
+// Ties the callback to the browser that started the sign-in
+const STATE_COOKIE: &str = "oauth_state";
+const STATE_COOKIE_PATH: &str = "/api/v1/auth/oauth";
+const STATE_MAX_AGE_MINUTES: i64 = 10;
+
+// Accounts created through a provider have no password; this never matches a
+// bcrypt hash, and one can be set later through the password reset flow
+const NO_PASSWORD_HASH: &str = "!";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Provider {
+    GitHub,
+    Google,
+}
+
+impl Provider {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "github" => Some(Self::GitHub),
+            "google" => Some(Self::Google),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::GitHub => "github",
+            Self::Google => "google",
+        }
+    }
+
+    fn client(self, config: &Config) -> Option<&OAuthClientConfig> {
+        match self {
+            Self::GitHub => config.oauth.github.as_ref(),
+            Self::Google => config.oauth.google.as_ref(),
+        }
+    }
+
+    fn authorize_url(self) -> &'static str {
+        match self {
+            Self::GitHub => "https://github.com/login/oauth/authorize",
+            Self::Google => "https://accounts.google.com/o/oauth2/v2/auth",
+        }
+    }
+
+    fn token_url(self) -> &'static str {
+        match self {
+            Self::GitHub => "https://github.com/login/oauth/access_token",
+            Self::Google => "https://oauth2.googleapis.com/token",
+        }
+    }
+
+    fn scope(self) -> &'static str {
+        match self {
+            Self::GitHub => "read:user user:email",
+            Self::Google => "openid email profile",
+        }
+    }
+}
+
+// What we keep from the provider's user info; `email` is only set when the
+// provider has verified it
+struct OAuthProfile {
+    provider_user_id: String,
+    email: Option<String>,
+    username: String,
+    full_name: Option<String>,
+    avatar_url: Option<String>,
+}
+
+enum SignInOutcome {
+    SignedIn(Box<User>),
+    EmailRequired,
+    EmailTaken,
+}
+
+pub async fn oauth_redirect(
+    config: web::Data<Config>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let (provider, client) = resolve_provider(&config, &path)?;
+
+    let state = generate_token();
+    let mut authorize_url = Url::parse(provider.authorize_url()).expect("provider URLs are valid");
+    authorize_url
+        .query_pairs_mut()
+        .append_pair("client_id", &client.client_id)
+        .append_pair("redirect_uri", &callback_url(&config, provider))
+        .append_pair("response_type", "code")
+        .append_pair("scope", provider.scope())
+        .append_pair("state", &state);
+
+    let cookie = Cookie::build(STATE_COOKIE, state)
+        .path(STATE_COOKIE_PATH)
+        .http_only(true)
+        .secure(config.oauth.public_api_url.starts_with("https://"))
+        // Lax so the cookie comes back on the provider's top-level redirect
+        .same_site(SameSite::Lax)
+        .max_age(CookieDuration::minutes(STATE_MAX_AGE_MINUTES))
+        .finish();
+
+    Ok(HttpResponse::Found()
+        .insert_header((header::LOCATION, authorize_url.to_string()))
+        .cookie(cookie)
+        .finish())
+}
+
+pub async fn oauth_callback(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    http: web::Data<reqwest::Client>,
+    metrics: web::Data<Metrics>,
+    path: web::Path<String>,
+    query: web::Query<OAuthCallbackParams>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let (provider, client) = resolve_provider(&config, &path)?;
+
+    let params = query.into_inner();
+    if let Some(error) = params.error {
+        return Err(AppError::coded(
+            StatusCode::BAD_REQUEST,
+            "OAUTH_DENIED",
+            format!("Sign-in was cancelled or denied: {}", error),
+        ));
+    }
+
+    let expected_state = http_req.cookie(STATE_COOKIE).map(|cookie| cookie.value().to_string());
+    let code = match (params.code, params.state, expected_state) {
+        (Some(code), Some(state), Some(expected_state)) if state == expected_state => code,
+        _ => {
+            return Err(AppError::coded(
+                StatusCode::BAD_REQUEST,
+                "INVALID_OAUTH_STATE",
+                "The sign-in session is invalid or has expired; start again",
+            ));
+        }
+    };
+
+    let profile = match fetch_profile(&http, &config, provider, client, &code).await {
+        Ok(profile) => profile,
+        Err(e) => {
+            log::error!("Sign-in with {} failed: {:?}", provider.name(), e);
+            return Err(AppError::coded(
+                StatusCode::BAD_GATEWAY,
+                "OAUTH_PROVIDER_ERROR",
+                format!("Failed to sign in with {}", provider.name()),
+            ));
+        }
+    };
+
+    let mut tx = pool.begin().await?;
+
+    let user = match sign_in(&mut tx, provider, &profile, &config.default_follow_user_ids).await? {
+        SignInOutcome::SignedIn(user) if user.banned_at.is_some() => return Err(account_banned()),
+        SignInOutcome::SignedIn(user) => *user,
+        SignInOutcome::EmailRequired => {
+            return Err(AppError::coded(
+                StatusCode::BAD_REQUEST,
+                "OAUTH_EMAIL_REQUIRED",
+                format!("Your {} account has no verified email address", provider.name()),
+            ));
+        }
+        SignInOutcome::EmailTaken => {
+            return Err(AppError::coded(
+                StatusCode::CONFLICT,
+                "OAUTH_EMAIL_TAKEN",
+                "An account with this email already exists; log in and verify its email first",
+            ));
+        }
+    };
+
+    if let Some(error) = account_suspended(user.suspended_until) {
+        return Err(error);
+    }
+
+    let fingerprint = client_fingerprint(&http_req);
+    let (access_token, refresh_token) = issue_tokens(&mut *tx, &config.jwt, &user, Uuid::new_v4(), &fingerprint).await?;
+
+    tx.commit().await?;
+    metrics.login(provider.name(), true);
+
+    let mut state_cookie = Cookie::build(STATE_COOKIE, "").path(STATE_COOKIE_PATH).finish();
+    state_cookie.make_removal();
+
+    Ok(HttpResponse::Ok().cookie(state_cookie).json(ApiResponse::success(AuthResponse {
+        user: user.into(),
+        access_token,
+        refresh_token,
+    })))
+}
+
+fn resolve_provider<'a>(
+    config: &'a Config,
+    name: &str,
+) -> Result<(Provider, &'a OAuthClientConfig), AppError> {
+    Provider::parse(name)
+        .and_then(|provider| provider.client(config).map(|client| (provider, client)))
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "Sign-in with '{}' is not available",
+                name
+            ))
+        })
+}
+
+fn callback_url(config: &Config, provider: Provider) -> String {
+    format!(
+        "{}/api/v1/auth/oauth/{}/callback",
+        config.oauth.public_api_url,
+        provider.name()
+    )
+}
+
+async fn fetch_profile(
+    http: &reqwest::Client,
+    config: &Config,
+    provider: Provider,
+    client: &OAuthClientConfig,
+    code: &str,
+) -> Result<OAuthProfile, reqwest::Error> {
+    #[derive(Deserialize)]
+    struct TokenResponse {
+        access_token: String,
+    }
+
+    let redirect_uri = callback_url(config, provider);
+    let token: TokenResponse = http
+        .post(provider.token_url())
+        .header(ACCEPT, "application/json")
+        .form(&[
+            ("client_id", client.client_id.as_str()),
+            ("client_secret", client.client_secret.as_str()),
+            ("code", code),
+            ("redirect_uri", redirect_uri.as_str()),
+            ("grant_type", "authorization_code"),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    match provider {
+        Provider::GitHub => {
+            #[derive(Deserialize)]
+            struct GitHubUser {
+                id: i64,
+                login: String,
+                name: Option<String>,
+                avatar_url: Option<String>,
+            }
+
+            #[derive(Deserialize)]
+            struct GitHubEmail {
+                email: String,
+                primary: bool,
+                verified: bool,
+            }
+
+            // GitHub requires a User-Agent on API requests
+            let user: GitHubUser = http
+                .get("https://api.github.com/user")
+                .bearer_auth(&token.access_token)
+                .header(USER_AGENT, "blog-backend")
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let emails: Vec<GitHubEmail> = http
+                .get("https://api.github.com/user/emails")
+                .bearer_auth(&token.access_token)
+                .header(USER_AGENT, "blog-backend")
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            Ok(OAuthProfile {
+                provider_user_id: user.id.to_string(),
+                email: emails
+                    .into_iter()
+                    .find(|email| email.primary && email.verified)
+                    .map(|email| email.email),
+                username: user.login,
+                full_name: user.name,
+                avatar_url: user.avatar_url,
+            })
+        }
+        Provider::Google => {
+            #[derive(Deserialize)]
+            struct GoogleUser {
+                sub: String,
+                email: Option<String>,
+                #[serde(default)]
+                email_verified: bool,
+                name: Option<String>,
+                picture: Option<String>,
+            }
+
+            let user: GoogleUser = http
+                .get("https://openidconnect.googleapis.com/v1/userinfo")
+                .bearer_auth(&token.access_token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            let email = user.email.filter(|_| user.email_verified);
+            let username = email
+                .as_deref()
+                .and_then(|email| email.split('@').next())
+                .unwrap_or("user")
+                .to_string();
+
+            Ok(OAuthProfile {
+                provider_user_id: user.sub,
+                email,
+                username,
+                full_name: user.name,
+                avatar_url: user.picture,
+            })
+        }
+    }
+}
+
+// Finds the account linked to this provider identity, otherwise links or
+// creates one by the provider's verified email. Existing accounts are only
+// linked once their own email is verified, so nobody can pre-register a
+// victim's address and wait for them to sign in with a provider.
+async fn sign_in(
+    tx: &mut Transaction<'_, Postgres>,
+    provider: Provider,
+    profile: &OAuthProfile,
+    default_follow_ids: &[Uuid],
+) -> Result<SignInOutcome, sqlx::Error> {
+    let linked = sqlx::query_as!(
+        User,
+        r#"
+        SELECT u.id, u.username, u.email, u.password_hash, u.full_name, u.bio, u.avatar_url, u.is_verified, u.deactivated_at, u.role, u.banned_at, u.suspended_until, u.created_at, u.updated_at
+        FROM users u
+        JOIN oauth_accounts oa ON oa.user_id = u.id
+        WHERE oa.provider = $1 AND oa.provider_user_id = $2
+        "#,
+        provider.name(),
+        profile.provider_user_id
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(user) = linked {
+        return Ok(SignInOutcome::SignedIn(Box::new(user)));
+    }
+
+    let email = match &profile.email {
+        Some(email) => email,
+        None => return Ok(SignInOutcome::EmailRequired),
+    };
+
+    let existing = sqlx::query_as!(
+        User,
+        "SELECT id, username, email, password_hash, full_name, bio, avatar_url, is_verified, deactivated_at, role, banned_at, suspended_until, created_at, updated_at FROM users WHERE LOWER(email) = LOWER($1)",
+        email
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    let user = match existing {
+        Some(user) if user.is_verified.unwrap_or(false) => user,
+        Some(_) => return Ok(SignInOutcome::EmailTaken),
+        None => create_oauth_user(tx, profile, email, default_follow_ids).await?,
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO oauth_accounts (user_id, provider, provider_user_id, created_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user.id,
+        provider.name(),
+        profile.provider_user_id,
+        Utc::now()
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(SignInOutcome::SignedIn(Box::new(user)))
+}
+
+async fn create_oauth_user(
+    tx: &mut Transaction<'_, Postgres>,
+    profile: &OAuthProfile,
+    email: &str,
+    default_follow_ids: &[Uuid],
+) -> Result<User, sqlx::Error> {
+    let mut username: String = profile
+        .username
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+        .take(40)
+        .collect();
+    if username.len() < 3 {
+        username = format!("user-{}", username);
+    }
+
+    let taken = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($1)) as "taken!""#,
+        username
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+    if taken {
+        username = format!("{}-{}", username, &generate_token()[..6]);
+    }
+
+    let user = sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO users (id, username, email, password_hash, full_name, avatar_url, is_verified, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, true, $7, $7)
+        RETURNING id, username, email, password_hash, full_name, bio, avatar_url, is_verified, deactivated_at, role, banned_at, suspended_until, created_at, updated_at
+        "#,
+        Uuid::new_v4(),
+        username,
+        email,
+        NO_PASSWORD_HASH,
+        profile.full_name,
+        profile.avatar_url,
+        Utc::now()
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    create_default_user_data(tx, &user, default_follow_ids).await?;
+
+    Ok(user)
+}
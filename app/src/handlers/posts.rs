@@ -1,648 +1,1586 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
-use chrono::Utc;
-use slug::slugify;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
-use crate::middleware::auth::{extract_optional_user_id, get_user_id_from_request};
+use crate::audit::{AuditAction, AuditEvent, AuditLogger};
+use crate::config::Config;
+use crate::error::AppError;
+use crate::handlers::mentions::sync_mentions;
+use crate::handlers::tags::{resolve_tag_alias, TRENDING_SORT_PERIOD};
+use crate::jobs::deleted_posts::DELETED_POST_RETENTION_DAYS;
+use crate::metrics::Metrics;
+use crate::middleware::auth::{can_publish, AuthenticatedUser, MaybeUser};
+use crate::middleware::client_ip::client_ip;
+use crate::middleware::render::html_requested;
 use crate::models::{
-    ApiResponse, CreatePostRequest, PaginatedResponse, PaginationParams, 
-    Post, PostResponse, UpdatePostRequest, UserResponse
+    AddTagRequest, ApiResponse, CreatePostRequest, DraftFilterParams, FieldsParams, PaginatedResponse,
+    PaginationParams, Post, PostFilterParams, PostResponse, PostSort, PostSummaryResponse, PublishPostRequest,
+    RelatedPostsParams, SortParams, TrendingParams, TrendingPeriod, UpdatePostRequest, UserResponse
 };
+use crate::utils::conditional::CacheValidators;
+use crate::utils::fields::{shape, FieldSelection};
+use crate::utils::front_matter::{self, FrontMatter};
+use crate::utils::markdown;
+use crate::utils::pagination::Page;
+use crate::utils::language::{detect_language, normalize_language};
+use crate::utils::tags::{normalize_tag_name, normalize_tags, MAX_TAGS_PER_POST};
+use crate::utils::validation::too_many_tags;
+use crate::events::{emit, DomainEvent};
+use crate::realtime::{Broker, Topic};
+use crate::slugs;
+use crate::views::ViewRecorder;
+
+const DEFAULT_RELATED_POSTS: u32 = 5;
+const MAX_RELATED_POSTS: u32 = 20;
 
 pub async fn create_post(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    metrics: web::Data<Metrics>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     req: web::Json<CreatePostRequest>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
-
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+    if let Some(publish_at) = req.publish_at {
+        check_publish_at(publish_at)?;
+        ensure_can_publish(&pool, &config, user_id).await?;
     }
 
     let post_id = Uuid::new_v4();
-    let slug = slugify(&req.title);
+    let mut slug = slugs::unique_post_slug(&pool, &req.title, None).await?;
+    let word_count = markdown::word_count(&req.content);
+    let excerpt = match req.excerpt.as_deref().map(str::trim) {
+        Some(excerpt) if !excerpt.is_empty() => Some(excerpt.to_string()),
+        _ => markdown::excerpt(&req.content),
+    };
+    let language = match &req.language {
+        Some(language) => normalize_language(language),
+        None => detect_language(&format!("{}\n{}", req.title, req.content)),
+    };
 
-    let post = sqlx::query_as!(
-        Post,
-        r#"
-        INSERT INTO posts (id, title, slug, content, excerpt, cover_image, author_id, is_published, created_at, updated_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, false, $8, $8)
-        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at
-        "#,
-        post_id,
-        req.title,
-        slug,
-        req.content,
-        req.excerpt,
-        req.cover_image,
-        user_id,
-        Utc::now()
-    )
-    .fetch_one(pool.get_ref())
-    .await;
+    // Another post can take the slug between picking and inserting it
+    let mut retried = false;
+    let post = loop {
+        let inserted = sqlx::query_as!(
+            Post,
+            r#"
+            INSERT INTO posts (id, title, slug, content, excerpt, cover_image, author_id, is_published, publish_at, language, word_count, reading_time_minutes, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, false, $8, $9, $11, $12, $10, $10)
+            RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
+            "#,
+            post_id,
+            req.title,
+            slug,
+            req.content,
+            excerpt,
+            req.cover_image,
+            user_id,
+            req.publish_at,
+            language,
+            Utc::now(),
+            word_count as i32,
+            markdown::reading_time_minutes(word_count) as i32
+        )
+        .fetch_one(pool.get_ref())
+        .await;
 
-    match post {
-        Ok(post) => {
-            // Handle tags if provided
-            if let Some(tags) = &req.tags {
-                for tag_name in tags {
-                    let _ = add_tag_to_post(&pool, post.id, tag_name).await;
-                }
+        match inserted {
+            Err(e) if !retried && slugs::is_post_slug_conflict(&e) => {
+                slug = slugs::with_short_hash(&slugs::base_slug(&req.title, "post"));
+                retried = true;
             }
-
-            let post_response = build_post_response(&pool, post, None).await?;
-            Ok(HttpResponse::Created().json(ApiResponse::success(post_response)))
+            inserted => break inserted?,
         }
-        Err(e) => {
-            log::error!("Failed to create post: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to create post".to_string(),
-            )))
+    };
+    metrics.post_created();
+
+    // Handle tags if provided
+    if let Some(tags) = &req.tags {
+        for tag_name in normalize_tags(tags) {
+            let _ = add_tag_to_post(&pool, post.id, &tag_name).await;
         }
     }
+
+    if let Err(e) = sync_mentions(pool.get_ref(), post.id, None, user_id, &post.content).await {
+        log::error!("Failed to record mentions: {:?}", e);
+    }
+
+    let post_response = build_post_response(&pool, post, None).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(post_response)))
 }
 
 pub async fn get_post(
     pool: web::Data<PgPool>,
+    views: web::Data<ViewRecorder>,
+    req: HttpRequest,
+    MaybeUser(user_id): MaybeUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = extract_optional_user_id(&http_req);
 
     let post = sqlx::query_as!(
         Post,
-        "SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at FROM posts WHERE id = $1 AND is_published = true",
+        "SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at FROM posts WHERE id = $1 AND is_published = true AND deleted_at IS NULL AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)",
         post_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?;
 
     match post {
-        Ok(Some(post)) => {
+        Some(post) => {
+            views.record(post.id, post.author_id, user_id, client_ip(&req));
             let post_response = build_post_response(&pool, post, user_id).await?;
-            Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
+            respond_with_validators(&req, post_response)
         }
-        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Post not found".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
+        None => {
+            let deleted = sqlx::query_scalar!(
+                r#"SELECT EXISTS(SELECT 1 FROM posts WHERE id = $1 AND is_published = true AND deleted_at IS NOT NULL) as "deleted!""#,
+                post_id
+            )
+            .fetch_one(pool.get_ref())
+            .await?;
+            Err(post_missing(deleted))
+        }
+    }
+}
+
+// Like counts and the viewer's likes and bookmarks change without touching
+// updated_at, so there's no Last-Modified
+fn respond_with_validators(req: &HttpRequest, post_response: PostResponse) -> Result<HttpResponse, AppError> {
+    let validators = CacheValidators::for_body(&post_response, None)?.private();
+    if let Some(response) = validators.not_modified(req) {
+        return Ok(response);
+    }
+    Ok(validators.apply(&mut HttpResponse::Ok()).json(ApiResponse::success(post_response)))
+}
+
+pub async fn get_post_by_slug(
+    pool: web::Data<PgPool>,
+    views: web::Data<ViewRecorder>,
+    req: HttpRequest,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let slug = path.into_inner();
+
+    match find_published_post_by_slug(&pool, &slug).await? {
+        Some((post, redirected)) => {
+            views.record(post.id, post.author_id, user_id, client_ip(&req));
+            let canonical_slug = post.slug.clone();
+            let mut post_response = build_post_response(&pool, post, user_id).await?;
+            if redirected {
+                post_response.canonical_slug = Some(canonical_slug);
+            }
+            respond_with_validators(&req, post_response)
+        }
+        None => {
+            let deleted = sqlx::query_scalar!(
+                r#"
+                SELECT EXISTS(
+                    SELECT 1 FROM posts
+                    WHERE is_published = true AND deleted_at IS NOT NULL
+                      AND (slug = $1 OR id IN (SELECT post_id FROM post_slug_redirects WHERE old_slug = $1))
+                ) as "deleted!"
+                "#,
+                slug
+            )
+            .fetch_one(pool.get_ref())
+            .await?;
+            Err(post_missing(deleted))
         }
     }
 }
 
+// Published posts that have since been deleted answer 410 rather than 404
+fn post_missing(deleted: bool) -> AppError {
+    if deleted {
+        AppError::coded(StatusCode::GONE, "POST_DELETED", "Post has been deleted")
+    } else {
+        AppError::NotFound("Post not found".to_string())
+    }
+}
+
+// Permanently redirects any current or old slug to the canonical by-slug URL
+pub async fn redirect_slug(
+    pool: web::Data<PgPool>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let slug = path.into_inner();
+
+    match find_published_post_by_slug(&pool, &slug).await? {
+        Some((post, _)) => Ok(HttpResponse::MovedPermanently()
+            .insert_header((
+                actix_web::http::header::LOCATION,
+                format!("/api/v1/posts/by-slug/{}", post.slug),
+            ))
+            .finish()),
+        None => Err(AppError::NotFound("Post not found".to_string())),
+    }
+}
+
+// Published posts, narrowed by any combination of filters
 pub async fn get_posts(
     pool: web::Data<PgPool>,
+    MaybeUser(user_id): MaybeUser,
     query: web::Query<PaginationParams>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = extract_optional_user_id(&http_req);
+    filter: web::Query<PostFilterParams>,
+    sort_query: web::Query<SortParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
     let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
-    // Get total count
-    let total: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM posts WHERE is_published = true"
+    let filter = filter.into_inner();
+    let sort = PostSort::parse(sort_query.sort.as_deref()).map_err(AppError::BadRequest)?;
+
+    let language = match filter.lang.as_deref() {
+        Some(lang) => match normalize_language(lang) {
+            Some(language) => Some(language),
+            None => {
+                return Err(AppError::BadRequest("lang must be an ISO 639-1 code".to_string()));
+            }
+        },
+        None => None,
+    };
+
+    if let (Some(after), Some(before)) = (filter.published_after, filter.published_before) {
+        if after >= before {
+            return Err(AppError::BadRequest("published_after must be before published_before".to_string()));
+        }
+    }
+
+    let author = filter.author.as_deref().map(str::trim).filter(|author| !author.is_empty());
+    let q = filter.q.as_deref().map(str::trim).filter(|q| !q.is_empty());
+    let tag = match filter.tag.as_deref().map(str::trim).filter(|tag| !tag.is_empty()) {
+        Some(tag) => Some(canonical_tag_name(&pool, tag).await?),
+        None => None,
+    };
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts p
+        WHERE p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND ($1::text IS NULL OR p.language = $1)
+          AND ($2::text IS NULL OR p.author_id IN (SELECT id FROM users WHERE username = $2 OR id::text = $2))
+          AND ($3::text IS NULL OR EXISTS (
+              SELECT 1 FROM post_tags pt INNER JOIN tags t ON pt.tag_id = t.id
+              WHERE pt.post_id = p.id AND t.name = $3))
+          AND ($4::text IS NULL OR strpos(lower(p.title), lower($4)) > 0 OR strpos(lower(p.content), lower($4)) > 0)
+          AND ($5::timestamptz IS NULL OR p.published_at >= $5)
+          AND ($6::timestamptz IS NULL OR p.published_at < $6)
+        "#,
+        language.as_deref(),
+        author,
+        tag.as_deref(),
+        q,
+        filter.published_after,
+        filter.published_before
     )
     .fetch_one(pool.get_ref())
-    .await
-    .map_err(|e| {
-        log::error!("Database error: {:?}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+    .await?;
 
-    // Get posts
+    // One query for every sort; the CASE arms that don't apply are NULL for
+    // every row and leave the order to the next key
     let posts = sqlx::query_as!(
         Post,
         r#"
-        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at FROM posts
-        WHERE is_published = true
-        ORDER BY published_at DESC
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        LEFT JOIN trending_posts tp ON tp.post_id = p.id AND tp.period = $10
+        WHERE p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND ($3::text IS NULL OR p.language = $3)
+          AND ($4::text IS NULL OR p.author_id IN (SELECT id FROM users WHERE username = $4 OR id::text = $4))
+          AND ($5::text IS NULL OR EXISTS (
+              SELECT 1 FROM post_tags pt INNER JOIN tags t ON pt.tag_id = t.id
+              WHERE pt.post_id = p.id AND t.name = $5))
+          AND ($6::text IS NULL OR strpos(lower(p.title), lower($6)) > 0 OR strpos(lower(p.content), lower($6)) > 0)
+          AND ($7::timestamptz IS NULL OR p.published_at >= $7)
+          AND ($8::timestamptz IS NULL OR p.published_at < $8)
+        ORDER BY CASE WHEN $9 = 'most_liked' THEN p.like_count END DESC,
+                 CASE WHEN $9 = 'most_commented' THEN p.comment_count END DESC,
+                 CASE WHEN $9 = 'trending' THEN COALESCE(tp.score, 0) END DESC,
+                 p.published_at DESC, p.id DESC
         LIMIT $1 OFFSET $2
         "#,
         limit as i64,
-        offset as i64
+        offset,
+        language.as_deref(),
+        author,
+        tag.as_deref(),
+        q,
+        filter.published_after,
+        filter.published_before,
+        sort.as_str(),
+        TRENDING_SORT_PERIOD.as_str()
     )
     .fetch_all(pool.get_ref())
-    .await;
-
-    match posts {
-        Ok(posts) => {
-            let mut post_responses = Vec::new();
-            for post in posts {
-                let post_response = build_post_response(&pool, post, user_id).await?;
-                post_responses.push(post_response);
-            }
+    .await?;
 
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+    let post_responses = shape(build_post_summaries(&pool, posts, user_id).await?, fields.as_ref())?;
 
-            let paginated_response = PaginatedResponse {
-                data: post_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
 
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
-        }
-    }
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
 }
 
 pub async fn update_post(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
     req: web::Json<UpdatePostRequest>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
-    }
+    req.validate()?;
 
-    // Check if post exists and user owns it
+    // Check if post exists and user is one of its authors
     let existing_post = sqlx::query!(
-        "SELECT author_id FROM posts WHERE id = $1",
-        post_id
+        r#"
+        SELECT author_id, content, EXISTS(SELECT 1 FROM post_authors WHERE post_id = $1 AND user_id = $2 AND accepted_at IS NOT NULL) as "is_coauthor!"
+        FROM posts WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        post_id,
+        user_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?;
 
     match existing_post {
-        Ok(Some(post)) if post.author_id == user_id => {
-            // For simplicity, let's use a more straightforward approach
-            let updated_post = if req.title.is_some() || req.content.is_some() || req.excerpt.is_some() || req.cover_image.is_some() {
+        Some(post) if post.author_id == user_id || post.is_coauthor => {
+            if req.regenerate_excerpt && req.excerpt.is_some() {
+                return Err(AppError::BadRequest(
+                    "Pass either excerpt or regenerate_excerpt, not both".to_string(),
+                ));
+            }
+
+            let word_count = req.content.as_deref().map(markdown::word_count);
+            let excerpt = if req.regenerate_excerpt {
+                markdown::excerpt(req.content.as_deref().unwrap_or(&post.content))
+            } else {
+                req.excerpt.clone()
+            };
+
+            // The slug is left alone on rename; see regenerate_slug
+            let post = if req.title.is_some() || req.content.is_some() || req.excerpt.is_some() || req.regenerate_excerpt || req.cover_image.is_some() || req.language.is_some() {
                 sqlx::query_as!(
                     Post,
                     r#"
                     UPDATE posts SET
                         title = COALESCE($2, title),
-                        slug = COALESCE($3, slug),
-                        content = COALESCE($4, content),
-                        excerpt = COALESCE($5, excerpt),
-                        cover_image = COALESCE($6, cover_image),
-                        updated_at = $7
+                        content = COALESCE($3, content),
+                        excerpt = CASE WHEN $10 THEN $4 ELSE COALESCE($4, excerpt) END,
+                        cover_image = COALESCE($5, cover_image),
+                        language = COALESCE($7, language),
+                        word_count = COALESCE($8, word_count),
+                        reading_time_minutes = COALESCE($9, reading_time_minutes),
+                        updated_at = $6
                     WHERE id = $1
-                    RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at
+                    RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
                     "#,
                     post_id,
                     req.title.as_deref(),
-                    req.title.as_ref().map(|t| slugify(t)),
                     req.content.as_deref(),
-                    req.excerpt.as_deref(),
+                    excerpt.as_deref(),
                     req.cover_image.as_deref(),
-                    Utc::now()
+                    Utc::now(),
+                    req.language.as_deref().and_then(normalize_language),
+                    word_count.map(|count| count as i32),
+                    word_count.map(|count| markdown::reading_time_minutes(count) as i32),
+                    req.regenerate_excerpt
                 )
                 .fetch_one(pool.get_ref())
-                .await
+                .await?
             } else {
-                return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-                    "No fields to update".to_string(),
-                )));
+                return Err(AppError::BadRequest("No fields to update".to_string()));
             };
 
-            match updated_post {
-                Ok(post) => {
-                    // Handle tags if provided
-                    if let Some(tags) = &req.tags {
-                        // Remove existing tags
-                        let _ = sqlx::query!(
-                            "DELETE FROM post_tags WHERE post_id = $1",
-                            post_id
-                        )
-                        .execute(pool.get_ref())
-                        .await;
-
-                        // Add new tags
-                        for tag_name in tags {
-                            let _ = add_tag_to_post(&pool, post.id, tag_name).await;
-                        }
-                    }
-
-                    let post_response = build_post_response(&pool, post, Some(user_id)).await?;
-                    Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
+            // Handle tags if provided
+            if let Some(tags) = &req.tags {
+                // Remove existing tags
+                let _ = sqlx::query!(
+                    "DELETE FROM post_tags WHERE post_id = $1",
+                    post_id
+                )
+                .execute(pool.get_ref())
+                .await;
+
+                // Add new tags
+                for tag_name in normalize_tags(tags) {
+                    let _ = add_tag_to_post(&pool, post.id, &tag_name).await;
                 }
-                Err(e) => {
-                    log::error!("Failed to update post: {:?}", e);
-                    Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                        "Failed to update post".to_string(),
-                    )))
+            }
+
+            if req.content.is_some() {
+                if let Err(e) = sync_mentions(pool.get_ref(), post.id, None, user_id, &post.content).await {
+                    log::error!("Failed to record mentions: {:?}", e);
                 }
             }
+
+            let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
         }
-        Ok(Some(_)) => Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
-            "You don't have permission to update this post".to_string(),
-        ))),
-        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Post not found".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
-        }
+        Some(_) => Err(AppError::Forbidden("You don't have permission to update this post".to_string())),
+        None => Err(AppError::NotFound("Post not found".to_string())),
     }
 }
 
 pub async fn delete_post(
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
     http_req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
 
+    // Kept for DELETED_POST_RETENTION_DAYS so the author can restore it
     let result = sqlx::query!(
-        "DELETE FROM posts WHERE id = $1 AND author_id = $2",
+        r#"
+        UPDATE posts SET deleted_at = $3, deleted_by = $2
+        WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL
+        "#,
         post_id,
-        user_id
+        user_id,
+        Utc::now()
     )
     .execute(pool.get_ref())
-    .await;
+    .await?;
 
-    match result {
-        Ok(result) if result.rows_affected() > 0 => {
-            Ok(HttpResponse::NoContent().finish())
-        }
-        Ok(_) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Post not found or you don't have permission to delete it".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Failed to delete post: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to delete post".to_string(),
-            )))
+    if result.rows_affected() == 0 {
+        if is_coauthor(pool.get_ref(), post_id, user_id).await? {
+            return Err(AppError::Forbidden("Only the post's author can delete it".to_string()));
         }
+        return Err(AppError::NotFound("Post not found or you don't have permission to delete it".to_string()));
     }
+
+    let event = AuditEvent::new(AuditAction::PostDeleted).actor(user_id).target("post", post_id);
+    audit.record(&http_req, event).await;
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Post deleted")))
 }
 
+// Publishes the post now, or with a `publish_at` in the body schedules it to
+// be published by `jobs::scheduled_publishing`. The body is optional.
 pub async fn publish_post(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<Uuid>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
+    body: web::Bytes,
+) -> Result<HttpResponse, AppError> {
     let post_id = path.into_inner();
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
+    let req: PublishPostRequest = if body.is_empty() {
+        PublishPostRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| AppError::BadRequest(e.to_string()))?
     };
 
+    ensure_can_publish(&pool, &config, user_id).await?;
+
+    if let Some(publish_at) = req.publish_at {
+        return schedule_post(&pool, post_id, user_id, publish_at).await;
+    }
+
+    // Publishing again only moves published_at, so followers aren't told twice
+    let mut tx = pool.begin().await?;
+    let was_published = sqlx::query_scalar!(
+        "SELECT is_published FROM posts WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL FOR UPDATE",
+        post_id,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .flatten()
+    .unwrap_or(false);
+
     let post = sqlx::query_as!(
         Post,
         r#"
         UPDATE posts SET
             is_published = true,
             published_at = $3,
+            publish_at = NULL,
             updated_at = $3
-        WHERE id = $1 AND author_id = $2
-        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at
+        WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
         "#,
         post_id,
         user_id,
         Utc::now()
     )
-    .fetch_optional(pool.get_ref())
-    .await;
+    .fetch_optional(&mut *tx)
+    .await?;
 
     match post {
-        Ok(Some(post)) => {
+        Some(post) => {
+            if !was_published {
+                let event = DomainEvent::PostPublished {
+                    post_id: post.id,
+                    author_id: post.author_id,
+                };
+                emit(&mut tx, &event).await?;
+            }
+            tx.commit().await?;
+
             let post_response = build_post_response(&pool, post, Some(user_id)).await?;
             Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
         }
-        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "Post not found or you don't have permission to publish it".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Failed to publish post: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to publish post".to_string(),
-            )))
-        }
+        None => Err(AppError::NotFound("Post not found or you don't have permission to publish it".to_string())),
     }
 }
 
-pub async fn get_drafts(
-    pool: web::Data<PgPool>,
-    query: web::Query<PaginationParams>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
-
-    let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+/// Sends newly published posts to their authors' followers' feed streams.
+pub async fn announce_published(pool: &PgPool, broker: &Broker, posts: Vec<Post>) -> Result<(), AppError> {
+    for post in build_post_responses(pool, posts, None).await? {
+        broker.publish(Topic::Author(post.author.id), "post.published", &post).await;
+    }
+    Ok(())
+}
 
-    let total: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM posts WHERE author_id = $1 AND is_published = false"
-    )
-    .bind(user_id)
-    .fetch_one(pool.get_ref())
-    .await
-    .map_err(|e| {
-        log::error!("Database error: {:?}", e);
-        actix_web::error::ErrorInternalServerError("Database error")
-    })?;
+async fn schedule_post(
+    pool: &PgPool,
+    post_id: Uuid,
+    user_id: Uuid,
+    publish_at: DateTime<Utc>,
+) -> Result<HttpResponse, AppError> {
+    check_publish_at(publish_at)?;
 
-    let posts = sqlx::query_as!(
+    let post = sqlx::query_as!(
         Post,
         r#"
-        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, created_at, updated_at FROM posts
-        WHERE author_id = $1 AND is_published = false
-        ORDER BY created_at DESC
-        LIMIT $2 OFFSET $3
+        UPDATE posts SET publish_at = $3, updated_at = $4
+        WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL AND is_published = false
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
         "#,
+        post_id,
         user_id,
-        limit as i64,
-        offset as i64
+        publish_at,
+        Utc::now()
     )
-    .fetch_all(pool.get_ref())
-    .await;
-
-    match posts {
-        Ok(posts) => {
-            let mut post_responses = Vec::new();
-            for post in posts {
-                let post_response = build_post_response(&pool, post, Some(user_id)).await?;
-                post_responses.push(post_response);
-            }
+    .fetch_optional(pool)
+    .await?;
 
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+    if let Some(post) = post {
+        let post_response = build_post_response(pool, post, Some(user_id)).await?;
+        return Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)));
+    }
 
-            let paginated_response = PaginatedResponse {
-                data: post_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
+    let published = sqlx::query_scalar!(
+        "SELECT is_published FROM posts WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL",
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
 
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
-        }
+    match published {
+        Some(_) => Err(AppError::Conflict("Post is already published".to_string())),
+        None => Err(AppError::NotFound("Post not found or you don't have permission to publish it".to_string())),
     }
 }
 
-pub async fn get_feed(
+// Clears a pending `publish_at`, leaving the post as a plain draft
+pub async fn cancel_scheduled_publish(
     pool: web::Data<PgPool>,
-    query: web::Query<PaginationParams>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
-
-    let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
 
-    // Get posts from followed users
-    let posts = sqlx::query_as!(
+    let post = sqlx::query_as!(
         Post,
         r#"
-        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.created_at, p.updated_at FROM posts p
-        INNER JOIN follows f ON p.author_id = f.following_id
-        WHERE f.follower_id = $1 AND p.is_published = true
-        ORDER BY p.published_at DESC
-        LIMIT $2 OFFSET $3
+        UPDATE posts SET publish_at = NULL, updated_at = $3
+        WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL
+          AND is_published = false AND publish_at IS NOT NULL
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
         "#,
+        post_id,
         user_id,
-        limit as i64,
-        offset as i64
+        Utc::now()
     )
-    .fetch_all(pool.get_ref())
-    .await;
-
-    match posts {
-        Ok(posts) => {
-            let mut post_responses = Vec::new();
-            for post in posts {
-                let post_response = build_post_response(&pool, post, Some(user_id)).await?;
-                post_responses.push(post_response);
-            }
-
-            let total: (i64,) = sqlx::query_as(
-                r#"
-                SELECT COUNT(*) FROM posts p
-                INNER JOIN follows f ON p.author_id = f.following_id
-                WHERE f.follower_id = $1 AND p.is_published = true
-                "#
-            )
-            .bind(user_id)
-            .fetch_one(pool.get_ref())
-.await
-            .unwrap_or((0,));
-
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
-
-            let paginated_response = PaginatedResponse {
-                data: post_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
+    .fetch_optional(pool.get_ref())
+    .await?;
 
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
+    match post {
+        Some(post) => {
+            let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
         }
+        None => Err(AppError::NotFound("No scheduled publication found for this post".to_string())),
     }
 }
 
-// Helper functions
-async fn build_post_response(
-    pool: &PgPool,
-    post: Post,
-    current_user_id: Option<Uuid>,
-) -> Result<PostResponse> {
-    // Get author info
-    let author = sqlx::query!(
-        r#"
-        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at,
-               COUNT(DISTINCT f1.follower_id) as "follower_count!",
-               COUNT(DISTINCT f2.following_id) as "following_count!"
-        FROM users u
-        LEFT JOIN follows f1 ON u.id = f1.following_id
-        LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE u.id = $1
-        GROUP BY u.id
-        "#,
-        post.author_id
-    )
-    .fetch_one(pool)
-    .await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+// Takes a published post down and returns it to the author's drafts. Likes,
+// comments and views are kept, so republishing restores it as it was.
+pub async fn unpublish_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
 
-    // Get tags
-    let tags = sqlx::query!(
+    let post = sqlx::query_as!(
+        Post,
         r#"
-        SELECT t.name FROM tags t
-        INNER JOIN post_tags pt ON t.id = pt.tag_id
-        WHERE pt.post_id = $1
+        UPDATE posts SET
+            is_published = false,
+            published_at = NULL,
+            updated_at = $3
+        WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL AND is_published = true
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
         "#,
-        post.id
+        post_id,
+        user_id,
+        Utc::now()
     )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    .fetch_optional(pool.get_ref())
+    .await?;
 
-    // Get like count
-    let like_count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM likes WHERE post_id = $1"
-    )
-    .bind(post.id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or((0,));
+    if let Some(post) = post {
+        let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+        return Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)));
+    }
 
-    // Get comment count
-    let comment_count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM comments WHERE post_id = $1"
+    let exists = sqlx::query_scalar!(
+        "SELECT is_published FROM posts WHERE id = $1 AND author_id = $2 AND deleted_at IS NULL",
+        post_id,
+        user_id
     )
-    .bind(post.id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or((0,));
+    .fetch_optional(pool.get_ref())
+    .await?;
 
-    // Check if current user liked the post
-    let is_liked = if let Some(user_id) = current_user_id {
-        sqlx::query!(
-            "SELECT id FROM likes WHERE post_id = $1 AND user_id = $2",
-            post.id,
-            user_id
-        )
-        .fetch_optional(pool)
-        .await
-        .unwrap_or(None)
-        .is_some()
-    } else {
-        false
+    match exists {
+        Some(_) => Err(AppError::Conflict("Post is not published".to_string())),
+        None => Err(AppError::NotFound("Post not found or you don't have permission to unpublish it".to_string())),
+    }
+}
+
+// Undoes a delete within DELETED_POST_RETENTION_DAYS. Posts taken down by a
+// moderator can't be restored by their author.
+pub async fn restore_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let post = sqlx::query_as!(
+        Post,
+        r#"
+        UPDATE posts SET deleted_at = NULL, deleted_by = NULL
+        WHERE id = $1 AND author_id = $2 AND deleted_at > $3
+          AND (deleted_by IS NULL OR deleted_by = author_id)
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
+        "#,
+        post_id,
+        user_id,
+        Utc::now() - Duration::days(DELETED_POST_RETENTION_DAYS)
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    match post {
+        Some(post) => {
+            let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
+        }
+        None => Err(AppError::NotFound("No restorable deleted post found".to_string())),
+    }
+}
+
+async fn ensure_can_publish(pool: &PgPool, config: &Config, user_id: Uuid) -> Result<(), AppError> {
+    if !can_publish(pool, config, user_id).await? {
+        return Err(AppError::coded(
+            StatusCode::FORBIDDEN,
+            "EMAIL_NOT_VERIFIED",
+            "Verify your email address before publishing",
+        ));
+    }
+    Ok(())
+}
+
+fn check_publish_at(publish_at: DateTime<Utc>) -> Result<(), AppError> {
+    if publish_at <= Utc::now() {
+        return Err(AppError::BadRequest("publish_at must be in the future".to_string()));
+    }
+    Ok(())
+}
+
+// Lists the caller's unpublished posts. `status=draft` keeps only posts
+// without a schedule, `status=scheduled` only scheduled ones (soonest first).
+pub async fn get_drafts(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+    filter: web::Query<DraftFilterParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let status = filter.into_inner().status;
+    if let Some(status) = status.as_deref() {
+        if status != "draft" && status != "scheduled" {
+            return Err(AppError::BadRequest(format!(
+                "Invalid status '{}', expected one of: draft, scheduled",
+                status
+            )));
+        }
+    }
+
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let total: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM posts
+        WHERE (author_id = $1 OR id IN (SELECT post_id FROM post_authors WHERE user_id = $1 AND accepted_at IS NOT NULL))
+          AND is_published = false AND deleted_at IS NULL
+          AND ($2::text IS NULL
+               OR ($2 = 'draft' AND publish_at IS NULL)
+               OR ($2 = 'scheduled' AND publish_at IS NOT NULL))
+        "#
+    )
+    .bind(user_id)
+    .bind(status.as_deref())
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at FROM posts
+        WHERE (author_id = $1 OR id IN (SELECT post_id FROM post_authors WHERE user_id = $1 AND accepted_at IS NOT NULL))
+          AND is_published = false AND deleted_at IS NULL
+          AND ($4::text IS NULL
+               OR ($4 = 'draft' AND publish_at IS NULL)
+               OR ($4 = 'scheduled' AND publish_at IS NOT NULL))
+        ORDER BY CASE WHEN $4 = 'scheduled' THEN publish_at END ASC, created_at DESC, id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        user_id,
+        limit as i64,
+        offset,
+        status.as_deref()
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, Some(user_id)).await?, fields.as_ref())?;
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
     };
 
-    Ok(PostResponse {
-        id: post.id,
-        title: post.title,
-        slug: post.slug,
-        content: post.content,
-        excerpt: post.excerpt,
-        cover_image: post.cover_image,
-        author: UserResponse {
-            id: author.id,
-            username: author.username,
-            email: author.email,
-            full_name: author.full_name,
-            bio: author.bio,
-            avatar_url: author.avatar_url,
-            is_verified: author.is_verified.unwrap_or(false),
-            follower_count: author.follower_count,
-            following_count: author.following_count,
-            created_at: author.created_at.unwrap(),
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+pub async fn get_draft_by_slug(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<String>,
+) -> Result<HttpResponse, AppError> {
+    let slug = path.into_inner();
+
+    // Drafts are only ever visible to their authors
+    let post = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at FROM posts
+        WHERE (author_id = $1 OR id IN (SELECT post_id FROM post_authors WHERE user_id = $1 AND accepted_at IS NOT NULL))
+          AND slug = $2 AND is_published = false AND deleted_at IS NULL
+        ORDER BY created_at DESC, id DESC
+        LIMIT 1
+        "#,
+        user_id,
+        slug
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    match post {
+        Some(post) => {
+            let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+            Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
+        }
+        None => Err(AppError::NotFound("Draft not found".to_string())),
+    }
+}
+
+// Published posts are public; drafts can only be exported by their authors
+pub async fn export_post_markdown(
+    pool: web::Data<PgPool>,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let post = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at FROM posts
+        WHERE id = $1 AND deleted_at IS NULL
+          AND ((is_published = true AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL))
+               OR author_id = $2
+               OR id IN (SELECT post_id FROM post_authors WHERE user_id = $2 AND accepted_at IS NOT NULL))
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let post = match post {
+        Some(post) => post,
+        None => {
+            return Err(AppError::NotFound("Post not found".to_string()));
+        }
+    };
+
+    let tags = get_post_tags(&pool, post.id).await?;
+
+    let markdown = front_matter::to_markdown(
+        &FrontMatter {
+            title: &post.title,
+            tags: &tags,
+            excerpt: post.excerpt.as_deref(),
+            published_at: post.published_at,
         },
-        tags: tags.into_iter().map(|t| t.name).collect(),
-        like_count: like_count.0,
-        comment_count: comment_count.0,
-        is_liked,
-        is_published: post.is_published.unwrap_or(false),
-        published_at: post.published_at,
-        created_at: post.created_at.unwrap(),
-        updated_at: post.updated_at.unwrap(),
-    })
+        &post.content,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/markdown; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(format!("{}.md", post.slug))],
+        })
+        .body(markdown))
 }
 
-async fn add_tag_to_post(pool: &PgPool, post_id: Uuid, tag_name: &str) -> Result<(), sqlx::Error> {
-    // Insert or get tag
-    let tag = sqlx::query!(
+pub async fn get_feed(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    // Get posts from followed users
+    let posts = sqlx::query_as!(
+        Post,
         r#"
-        INSERT INTO tags (id, name, created_at)
-        VALUES ($1, $2, $3)
-        ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
-        RETURNING id
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN follows f ON p.author_id = f.following_id
+        WHERE f.follower_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY p.published_at DESC, p.id DESC
+        LIMIT $2 OFFSET $3
         "#,
-        Uuid::new_v4(),
-        tag_name,
-        Utc::now()
+        user_id,
+        limit as i64,
+        offset
     )
-    .fetch_one(pool)
+    .fetch_all(pool.get_ref())
     .await?;
 
+    let post_responses = shape(build_post_summaries(&pool, posts, Some(user_id)).await?, fields.as_ref())?;
+
+    let total: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM posts p
+        INNER JOIN follows f ON p.author_id = f.following_id
+        WHERE f.follower_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+.await
+    .unwrap_or((0,));
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// Highest trending score first, as of the last jobs::trending refresh.
+// Posts unpublished or deleted since are left out.
+pub async fn get_trending_posts(
+    pool: web::Data<PgPool>,
+    MaybeUser(user_id): MaybeUser,
+    trending_query: web::Query<TrendingParams>,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let period = TrendingPeriod::parse(trending_query.period.as_deref()).map_err(AppError::BadRequest)?;
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN trending_posts tp ON p.id = tp.post_id
+        WHERE tp.period = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY tp.score DESC, p.published_at DESC, p.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        period.as_str(),
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, user_id).await?, fields.as_ref())?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts p
+        INNER JOIN trending_posts tp ON p.id = tp.post_id
+        WHERE tp.period = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        period.as_str()
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// Published posts for a "read next" section: each shared tag counts one
+// and the same author one more, with newer posts first among equals
+pub async fn get_related_posts(
+    pool: web::Data<PgPool>,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<Uuid>,
+    query: web::Query<RelatedPostsParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let post_id = path.into_inner();
+    let limit = query.into_inner().limit.unwrap_or(DEFAULT_RELATED_POSTS);
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be greater than 0".to_string()));
+    }
+
+    let author_id = sqlx::query_scalar!(
+        "SELECT author_id FROM posts WHERE id = $1 AND is_published = true AND deleted_at IS NULL AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)",
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        LEFT JOIN (
+            SELECT other.post_id, COUNT(*) as shared_tags
+            FROM post_tags source
+            INNER JOIN post_tags other ON source.tag_id = other.tag_id AND other.post_id <> source.post_id
+            WHERE source.post_id = $1
+            GROUP BY other.post_id
+        ) overlap ON p.id = overlap.post_id
+        WHERE p.id <> $1 AND (overlap.post_id IS NOT NULL OR p.author_id = $2)
+          AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY COALESCE(overlap.shared_tags, 0) + CASE WHEN p.author_id = $2 THEN 1 ELSE 0 END DESC,
+                 p.published_at DESC, p.id DESC
+        LIMIT $3
+        "#,
+        post_id,
+        author_id,
+        limit.min(MAX_RELATED_POSTS) as i64
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, user_id).await?, fields.as_ref())?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post_responses)))
+}
+
+pub async fn regenerate_slug(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    let existing_post = sqlx::query!(
+        r#"
+        SELECT author_id, title, slug, EXISTS(SELECT 1 FROM post_authors WHERE post_id = $1 AND user_id = $2 AND accepted_at IS NOT NULL) as "is_coauthor!"
+        FROM posts WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    let existing_post = match existing_post {
+        Some(post) if post.author_id == user_id || post.is_coauthor => post,
+        Some(_) => {
+            return Err(AppError::Forbidden("You don't have permission to update this post".to_string()));
+        }
+        None => {
+            return Err(AppError::NotFound("Post not found".to_string()));
+        }
+    };
+
+    let new_slug = slugs::unique_post_slug(&pool, &existing_post.title, Some(post_id)).await?;
+
+    let post = match slugs::replace_post_slug(&pool, post_id, &existing_post.slug, &new_slug).await {
+        Err(e) if slugs::is_post_slug_conflict(&e) => {
+            let new_slug = slugs::with_short_hash(&slugs::base_slug(&existing_post.title, "post"));
+            slugs::replace_post_slug(&pool, post_id, &existing_post.slug, &new_slug).await?
+        }
+        replaced => replaced?,
+    };
+
+    let post_response = build_post_response(&pool, post, Some(user_id)).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(post_response)))
+}
+
+pub async fn add_post_tag(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<AddTagRequest>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    req.validate()?;
+
+    check_can_edit(&pool, post_id, user_id).await?;
+
+    let tag_name = canonical_tag_name(&pool, &req.name).await?;
+    let tags = get_post_tags(&pool, post_id).await?;
+    if !tags.contains(&tag_name) && tags.len() >= MAX_TAGS_PER_POST {
+        let mut errors = ValidationErrors::new();
+        errors.add("name", too_many_tags());
+        return Err(errors.into());
+    }
+
+    add_tag_to_post(&pool, post_id, &tag_name).await?;
+
+    let tags = get_post_tags(&pool, post_id).await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(tags)))
+}
+
+pub async fn remove_post_tag(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, String)>,
+) -> Result<HttpResponse, AppError> {
+    let (post_id, tag_name) = path.into_inner();
+
+    check_can_edit(&pool, post_id, user_id).await?;
+
+    let tag_name = canonical_tag_name(&pool, &tag_name).await?;
+
+    let result = sqlx::query!(
+        r#"
+        DELETE FROM post_tags
+        WHERE post_id = $1 AND tag_id = (SELECT id FROM tags WHERE name = $2)
+        "#,
+        post_id,
+        tag_name
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Tag not found on this post".to_string()));
+    }
+
+    let tags = get_post_tags(&pool, post_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(tags)))
+}
+
+// Helper functions
+pub async fn build_post_response(
+    pool: &PgPool,
+    post: Post,
+    current_user_id: Option<Uuid>,
+) -> Result<PostResponse, AppError> {
+    let mut responses = build_post_responses(pool, vec![post], current_user_id).await?;
+    Ok(responses.remove(0))
+}
+
+/// Builds the responses for a page of posts with one query each for authors,
+/// tags and the caller's likes and bookmarks, rather than running them per post.
+pub async fn build_post_responses(
+    pool: &PgPool,
+    posts: Vec<Post>,
+    current_user_id: Option<Uuid>,
+) -> Result<Vec<PostResponse>, AppError> {
+    load_post_responses(pool, posts, current_user_id, html_requested()).await
+}
+
+/// `build_post_responses` for list endpoints, which leave the content out.
+pub async fn build_post_summaries(
+    pool: &PgPool,
+    posts: Vec<Post>,
+    current_user_id: Option<Uuid>,
+) -> Result<Vec<PostSummaryResponse>, AppError> {
+    let responses = load_post_responses(pool, posts, current_user_id, false).await?;
+    Ok(responses.into_iter().map(PostSummaryResponse::from).collect())
+}
+
+async fn load_post_responses(
+    pool: &PgPool,
+    posts: Vec<Post>,
+    current_user_id: Option<Uuid>,
+    render_html: bool,
+) -> Result<Vec<PostResponse>, AppError> {
+    if posts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let post_ids: Vec<Uuid> = posts.iter().map(|post| post.id).collect();
+
+    // Accepted co-authors whose accounts are still active, in order of joining
+    let mut coauthors: HashMap<Uuid, Vec<Uuid>> = HashMap::new();
+    let coauthor_rows = sqlx::query!(
+        r#"
+        SELECT pa.post_id, pa.user_id FROM post_authors pa
+        INNER JOIN users u ON pa.user_id = u.id
+        WHERE pa.post_id = ANY($1) AND pa.accepted_at IS NOT NULL AND u.deactivated_at IS NULL
+        ORDER BY pa.accepted_at, pa.user_id
+        "#,
+        &post_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in coauthor_rows {
+        coauthors.entry(row.post_id).or_default().push(row.user_id);
+    }
+
+    let author_ids: Vec<Uuid> = posts
+        .iter()
+        .map(|post| post.author_id)
+        .chain(coauthors.values().flatten().copied())
+        .collect();
+    let authors = load_users(pool, &author_ids).await?;
+
+    let mut tags: HashMap<Uuid, Vec<String>> = HashMap::new();
+    let tag_rows = sqlx::query!(
+        r#"
+        SELECT pt.post_id, t.name FROM tags t
+        INNER JOIN post_tags pt ON t.id = pt.tag_id
+        WHERE pt.post_id = ANY($1)
+        "#,
+        &post_ids
+    )
+    .fetch_all(pool)
+    .await?;
+    for row in tag_rows {
+        tags.entry(row.post_id).or_default().push(row.name);
+    }
+
+    // Posts the current user has liked
+    let liked: HashSet<Uuid> = match current_user_id {
+        Some(user_id) => sqlx::query_scalar!(
+            "SELECT post_id FROM likes WHERE user_id = $1 AND post_id = ANY($2)",
+            user_id,
+            &post_ids
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect(),
+        None => HashSet::new(),
+    };
+
+    // ...and bookmarked
+    let bookmarked: HashSet<Uuid> = match current_user_id {
+        Some(user_id) => sqlx::query_scalar!(
+            "SELECT post_id FROM bookmarks WHERE user_id = $1 AND post_id = ANY($2)",
+            user_id,
+            &post_ids
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect(),
+        None => HashSet::new(),
+    };
+
+    posts
+        .into_iter()
+        .map(|post| {
+            let author = authors
+                .get(&post.author_id)
+                .cloned()
+                .ok_or(sqlx::Error::RowNotFound)?;
+            let post_authors = std::iter::once(author.clone())
+                .chain(
+                    coauthors
+                        .remove(&post.id)
+                        .unwrap_or_default()
+                        .iter()
+                        .filter_map(|user_id| authors.get(user_id).cloned()),
+                )
+                .collect();
+
+            Ok(PostResponse {
+                id: post.id,
+                title: post.title,
+                slug: post.slug,
+                content_html: render_html.then(|| markdown::render_html(&post.content)),
+                content: post.content,
+                excerpt: post.excerpt,
+                cover_image: post.cover_image,
+                author,
+                authors: post_authors,
+                tags: tags.remove(&post.id).unwrap_or_default(),
+                like_count: post.like_count,
+                comment_count: post.comment_count,
+                word_count: post.word_count,
+                reading_time_minutes: post.reading_time_minutes,
+                is_liked: liked.contains(&post.id),
+                is_bookmarked: bookmarked.contains(&post.id),
+                is_published: post.is_published.unwrap_or(false),
+                published_at: post.published_at,
+                publish_at: post.publish_at,
+                language: post.language,
+                created_at: post.created_at.unwrap(),
+                updated_at: post.updated_at.unwrap(),
+                canonical_slug: None,
+            })
+        })
+        .collect()
+}
+
+/// Whether `user_id` is an accepted co-author of the post.
+pub async fn is_coauthor(pool: &PgPool, post_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM post_authors WHERE post_id = $1 AND user_id = $2 AND accepted_at IS NOT NULL
+        ) as "exists!"
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Users by id, as shown on posts and comments.
+pub async fn load_users(pool: &PgPool, user_ids: &[Uuid]) -> Result<HashMap<Uuid, UserResponse>, sqlx::Error> {
+    let users = sqlx::query!(
+        r#"
+        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at,
+               (SELECT COUNT(*) FROM follows WHERE following_id = u.id) as "follower_count!",
+               (SELECT COUNT(*) FROM follows WHERE follower_id = u.id) as "following_count!"
+        FROM users u
+        WHERE u.id = ANY($1)
+        "#,
+        user_ids
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|user| {
+        let response = UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            follower_count: user.follower_count,
+            following_count: user.following_count,
+            created_at: user.created_at.unwrap(),
+            relationship: None,
+        };
+        (user.id, response)
+    })
+    .collect();
+
+    Ok(users)
+}
+
+// The name a tag is stored under: normalized, with synonyms resolved
+async fn canonical_tag_name(pool: &PgPool, tag_name: &str) -> Result<String, sqlx::Error> {
+    let tag_name = normalize_tag_name(tag_name);
+    Ok(resolve_tag_alias(pool, &tag_name).await?.unwrap_or(tag_name))
+}
+
+async fn add_tag_to_post(pool: &PgPool, post_id: Uuid, tag_name: &str) -> Result<(), sqlx::Error> {
+    let tag_name = canonical_tag_name(pool, tag_name).await?;
+    let tag_name = tag_name.as_str();
+
+    let existing = sqlx::query_scalar!("SELECT id FROM tags WHERE name = $1", tag_name)
+        .fetch_optional(pool)
+        .await?;
+
+    let tag_id = match existing {
+        Some(tag_id) => tag_id,
+        None => {
+            let slug = slugs::unique_tag_slug(pool, tag_name).await?;
+            sqlx::query_scalar!(
+                r#"
+                INSERT INTO tags (id, name, slug, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                RETURNING id
+                "#,
+                Uuid::new_v4(),
+                tag_name,
+                slug,
+                Utc::now()
+            )
+            .fetch_one(pool)
+            .await?
+        }
+    };
+
     // Link tag to post
     sqlx::query!(
         "INSERT INTO post_tags (post_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
         post_id,
-        tag.id
+        tag_id
     )
     .execute(pool)
     .await?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Looks a published post up by its current slug, falling back to the slug
+// redirects; the flag is true when the post was found through an old slug
+async fn find_published_post_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<(Post, bool)>, sqlx::Error> {
+    let post = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at FROM posts
+        WHERE slug = $1 AND is_published = true AND deleted_at IS NULL AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY published_at DESC, id DESC
+        LIMIT 1
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(post) = post {
+        return Ok(Some((post, false)));
+    }
+
+    let redirected_post = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN post_slug_redirects r ON p.id = r.post_id
+        WHERE r.old_slug = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(redirected_post.map(|post| (post, true)))
+}
+
+// Fails when `user_id` can't edit the post, being neither its author nor a
+// co-author
+async fn check_can_edit(pool: &PgPool, post_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let post = sqlx::query!(
+        r#"
+        SELECT author_id, EXISTS(SELECT 1 FROM post_authors WHERE post_id = $1 AND user_id = $2 AND accepted_at IS NOT NULL) as "is_coauthor!"
+        FROM posts WHERE id = $1 AND deleted_at IS NULL
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match post {
+        Some(post) if post.author_id == user_id || post.is_coauthor => Ok(()),
+        Some(_) => Err(AppError::Forbidden("You don't have permission to update this post".to_string())),
+        None => Err(AppError::NotFound("Post not found".to_string())),
+    }
+}
+
+async fn get_post_tags(pool: &PgPool, post_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let tags = sqlx::query!(
+        r#"
+        SELECT t.name FROM tags t
+        INNER JOIN post_tags pt ON t.id = pt.tag_id
+        WHERE pt.post_id = $1
+        ORDER BY t.name ASC
+        "#,
+        post_id
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags.into_iter().map(|t| t.name).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn pages_over_tied_posts_are_disjoint_and_complete(pool: PgPool) {
+        let author_id = test_support::insert_user(&pool, "prolific", "").await;
+        let published_at = Utc::now() - Duration::hours(1);
+        let mut seeded = HashSet::new();
+        for n in 0..25 {
+            seeded.insert(test_support::insert_published_post(&pool, author_id, &format!("Post {}", n), published_at).await);
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(test_support::config())
+                .route("/posts", web::get().to(get_posts)),
+        )
+        .await;
+
+        // Every post ties on every sort key, so only the id keeps pages apart
+        for sort in ["newest", "most_liked", "most_commented", "trending"] {
+            let mut seen = HashSet::new();
+            for page in 1..=7 {
+                let req = test::TestRequest::get()
+                    .uri(&format!("/posts?sort={}&page={}&limit=4", sort, page))
+                    .to_request();
+                let body: Value = test::call_and_read_body_json(&app, req).await;
+                for post in body["data"]["data"].as_array().unwrap() {
+                    let id: Uuid = post["id"].as_str().unwrap().parse().unwrap();
+                    assert!(seen.insert(id), "post {} repeated across pages for sort={}", id, sort);
+                }
+            }
+            assert_eq!(seen, seeded, "pages missed posts for sort={}", sort);
+        }
+    }
+
+    #[sqlx::test]
+    async fn trending_sort_ranks_the_feed_and_tag_pages_alike(pool: PgPool) {
+        let author_id = test_support::insert_user(&pool, "trendsetter", "").await;
+        let published_at = Utc::now() - Duration::days(1);
+        let quiet = test_support::insert_published_post(&pool, author_id, "Quiet", published_at).await;
+        let hot = test_support::insert_published_post(&pool, author_id, "Hot", published_at - Duration::hours(2)).await;
+        let warm = test_support::insert_published_post(&pool, author_id, "Warm", published_at - Duration::hours(1)).await;
+
+        let tag_id = sqlx::query_scalar!("INSERT INTO tags (name, slug) VALUES ('rust', 'rust') RETURNING id")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        for (post_id, score) in [(hot, 9.0), (warm, 3.0), (quiet, 0.0)] {
+            sqlx::query!("INSERT INTO post_tags (post_id, tag_id) VALUES ($1, $2)", post_id, tag_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            if score > 0.0 {
+                sqlx::query!(
+                    "INSERT INTO trending_posts (period, post_id, score, computed_at) VALUES ($1, $2, $3, NOW())",
+                    TRENDING_SORT_PERIOD.as_str(),
+                    post_id,
+                    score
+                )
+                .execute(&pool)
+                .await
+                .unwrap();
+            }
+        }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(test_support::config())
+                .route("/posts", web::get().to(get_posts))
+                .route("/tags/{tag_name}/posts", web::get().to(crate::handlers::tags::get_posts_by_tag)),
+        )
+        .await;
+
+        for uri in ["/posts?sort=trending", "/tags/rust/posts?sort=trending"] {
+            let body: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(uri).to_request()).await;
+            let ids: Vec<Uuid> = body["data"]["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|post| post["id"].as_str().unwrap().parse().unwrap())
+                .collect();
+            assert_eq!(ids, vec![hot, warm, quiet], "{}", uri);
+        }
+    }
+
+    #[sqlx::test]
+    async fn engagement_score_skips_deleted_and_hidden_comments(pool: PgPool) {
+        let author_id = test_support::insert_user(&pool, "talker", "").await;
+        let post_id = test_support::insert_published_post(&pool, author_id, "Discussed", Utc::now()).await;
+        test_support::insert_comment(&pool, post_id, author_id, "Kept").await;
+        let deleted = test_support::insert_comment(&pool, post_id, author_id, "Deleted").await;
+        let hidden = test_support::insert_comment(&pool, post_id, author_id, "Hidden").await;
+        sqlx::query!("UPDATE comments SET deleted_at = NOW() WHERE id = $1", deleted)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query!("UPDATE comments SET hidden_at = NOW() WHERE id = $1", hidden)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let score = sqlx::query_scalar!(
+            r#"SELECT post_engagement_score($1, NOW() - INTERVAL '1 day') as "score!""#,
+            post_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(score, 2);
+    }
+}
+
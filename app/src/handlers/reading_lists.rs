@@ -0,0 +1,384 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::AppError;
+use crate::handlers::posts::build_post_summaries;
+use crate::middleware::auth::{AuthenticatedUser, MaybeUser};
+use crate::models::{
+    ApiResponse, CreateReadingListRequest, FieldsParams, PaginatedResponse, PaginationParams, Post,
+    ReadingListResponse, UpdateReadingListRequest,
+};
+use crate::utils::fields::{shape, FieldSelection};
+use crate::utils::pagination::Page;
+
+const MAX_LISTS_PER_USER: i64 = 50;
+const MAX_POSTS_PER_LIST: i64 = 500;
+
+pub async fn create_list(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<CreateReadingListRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+    let name = list_name(&req.name)?;
+
+    let list_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM reading_lists WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if list_count >= MAX_LISTS_PER_USER {
+        return Err(AppError::BadRequest(format!(
+            "You can have at most {} lists; delete one first",
+            MAX_LISTS_PER_USER
+        )));
+    }
+
+    let list_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO reading_lists (user_id, name, description, is_public)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        user_id,
+        name,
+        req.description.as_deref(),
+        req.is_public.unwrap_or(false)
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let list = fetch_list(&pool, list_id).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(list)))
+}
+
+pub async fn get_my_lists(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let lists = sqlx::query_as!(
+        ReadingListResponse,
+        r#"
+        SELECT l.id, l.user_id, l.name, l.description, l.is_public, l.created_at, l.updated_at,
+               (SELECT COUNT(*) FROM reading_list_posts lp
+                INNER JOIN posts p ON lp.post_id = p.id
+                WHERE lp.list_id = l.id AND p.is_published = true AND p.deleted_at IS NULL) as "post_count!"
+        FROM reading_lists l
+        WHERE l.user_id = $1
+        ORDER BY l.created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(lists)))
+}
+
+// Only public lists, and none for deactivated accounts
+pub async fn get_user_lists(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let user_id = path.into_inner();
+
+    let lists = sqlx::query_as!(
+        ReadingListResponse,
+        r#"
+        SELECT l.id, l.user_id, l.name, l.description, l.is_public, l.created_at, l.updated_at,
+               (SELECT COUNT(*) FROM reading_list_posts lp
+                INNER JOIN posts p ON lp.post_id = p.id
+                WHERE lp.list_id = l.id AND p.is_published = true AND p.deleted_at IS NULL) as "post_count!"
+        FROM reading_lists l
+        INNER JOIN users u ON l.user_id = u.id
+        WHERE l.user_id = $1 AND l.is_public = true AND u.deactivated_at IS NULL
+        ORDER BY l.created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(lists)))
+}
+
+pub async fn get_list(
+    pool: web::Data<PgPool>,
+    MaybeUser(current_user_id): MaybeUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let list_id = path.into_inner();
+    ensure_visible(&pool, list_id, current_user_id).await?;
+
+    let list = fetch_list(&pool, list_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(list)))
+}
+
+// Most recently added first
+pub async fn get_list_posts(
+    pool: web::Data<PgPool>,
+    MaybeUser(current_user_id): MaybeUser,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let list_id = path.into_inner();
+    ensure_visible(&pool, list_id, current_user_id).await?;
+
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN reading_list_posts lp ON p.id = lp.post_id
+        WHERE lp.list_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY lp.added_at DESC, p.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        list_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts p
+        INNER JOIN reading_list_posts lp ON p.id = lp.post_id
+        WHERE lp.list_id = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        list_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, current_user_id).await?, fields.as_ref())?;
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+pub async fn update_list(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateReadingListRequest>,
+) -> Result<HttpResponse, AppError> {
+    let list_id = path.into_inner();
+    req.validate()?;
+    let name = req.name.as_deref().map(list_name).transpose()?;
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE reading_lists SET
+            name = COALESCE($3, name),
+            description = COALESCE($4, description),
+            is_public = COALESCE($5, is_public),
+            updated_at = NOW()
+        WHERE id = $1 AND user_id = $2
+        "#,
+        list_id,
+        user_id,
+        name,
+        req.description.as_deref(),
+        req.is_public
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("List not found".to_string()));
+    }
+
+    let list = fetch_list(&pool, list_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(list)))
+}
+
+pub async fn delete_list(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let list_id = path.into_inner();
+
+    let result = sqlx::query!(
+        "DELETE FROM reading_lists WHERE id = $1 AND user_id = $2",
+        list_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("List not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("List deleted")))
+}
+
+pub async fn add_list_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (list_id, post_id) = path.into_inner();
+    ensure_owner(&pool, list_id, user_id).await?;
+
+    let post_exists = sqlx::query!(
+        r#"
+        SELECT id FROM posts
+        WHERE id = $1 AND is_published = true AND deleted_at IS NULL
+          AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    if post_exists.is_none() {
+        return Err(AppError::NotFound("Post not found".to_string()));
+    }
+
+    let post_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM reading_list_posts WHERE list_id = $1"#,
+        list_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if post_count >= MAX_POSTS_PER_LIST {
+        return Err(AppError::BadRequest(format!(
+            "A list can hold at most {} posts",
+            MAX_POSTS_PER_LIST
+        )));
+    }
+
+    let result = sqlx::query!(
+        "INSERT INTO reading_list_posts (list_id, post_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        list_id,
+        post_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::Conflict("Post is already in this list".to_string()));
+    }
+
+    touch_list(&pool, list_id).await?;
+    let list = fetch_list(&pool, list_id).await?;
+    Ok(HttpResponse::Created().json(ApiResponse::success(list)))
+}
+
+pub async fn remove_list_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<(Uuid, Uuid)>,
+) -> Result<HttpResponse, AppError> {
+    let (list_id, post_id) = path.into_inner();
+    ensure_owner(&pool, list_id, user_id).await?;
+
+    let result = sqlx::query!(
+        "DELETE FROM reading_list_posts WHERE list_id = $1 AND post_id = $2",
+        list_id,
+        post_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Post is not in this list".to_string()));
+    }
+
+    touch_list(&pool, list_id).await?;
+    let list = fetch_list(&pool, list_id).await?;
+    Ok(HttpResponse::Ok().json(ApiResponse::success(list)))
+}
+
+fn list_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("List name must not be blank".to_string()));
+    }
+    Ok(name)
+}
+
+async fn fetch_list(pool: &PgPool, list_id: Uuid) -> Result<ReadingListResponse, AppError> {
+    sqlx::query_as!(
+        ReadingListResponse,
+        r#"
+        SELECT l.id, l.user_id, l.name, l.description, l.is_public, l.created_at, l.updated_at,
+               (SELECT COUNT(*) FROM reading_list_posts lp
+                INNER JOIN posts p ON lp.post_id = p.id
+                WHERE lp.list_id = l.id AND p.is_published = true AND p.deleted_at IS NULL) as "post_count!"
+        FROM reading_lists l
+        WHERE l.id = $1
+        "#,
+        list_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound("List not found".to_string()))
+}
+
+async fn touch_list(pool: &PgPool, list_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!("UPDATE reading_lists SET updated_at = NOW() WHERE id = $1", list_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+// Other users' lists are reported as missing rather than forbidden
+async fn ensure_owner(pool: &PgPool, list_id: Uuid, user_id: Uuid) -> Result<(), AppError> {
+    let owned = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM reading_lists WHERE id = $1 AND user_id = $2) AS "exists!""#,
+        list_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !owned {
+        return Err(AppError::NotFound("List not found".to_string()));
+    }
+    Ok(())
+}
+
+// Public lists of active accounts, and the caller's own lists
+async fn ensure_visible(pool: &PgPool, list_id: Uuid, current_user_id: Option<Uuid>) -> Result<(), AppError> {
+    let visible = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM reading_lists l
+            INNER JOIN users u ON l.user_id = u.id
+            WHERE l.id = $1
+              AND (l.user_id = $2 OR (l.is_public = true AND u.deactivated_at IS NULL))
+        ) AS "exists!"
+        "#,
+        list_id,
+        current_user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !visible {
+        return Err(AppError::NotFound("List not found".to_string()));
+    }
+    Ok(())
+}
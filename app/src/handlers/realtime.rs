@@ -0,0 +1,322 @@
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{rt, web, HttpRequest, HttpResponse};
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, Session};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+use tokio::time::Interval;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::middleware::account_status::check_token_account;
+use crate::middleware::auth::{get_user_id_from_request, AuthenticatedUser};
+use crate::realtime::{Broker, RealtimeMessage, Topic};
+use crate::utils::jwt::validate_token;
+
+// Clients that didn't authenticate during the handshake must send an `auth`
+// message within this long
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(30);
+// Connections nothing has been heard from for this long are closed
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(90);
+const MAX_MESSAGE_BYTES: usize = 64 * 1024;
+const MAX_POST_SUBSCRIPTIONS: usize = 50;
+// Comment lines sent on idle feed streams so proxies don't drop them
+const FEED_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+// How stale a feed stream's list of followed authors may get
+const FOLLOWING_REFRESH: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    Auth { token: String },
+    Subscribe { post_id: Uuid },
+    Unsubscribe { post_id: Uuid },
+}
+
+// Authenticated with a bearer token or API key on the handshake or, for
+// browsers that can't set headers, with an `auth` message sent first.
+// Accounts are only checked when the connection is opened.
+pub async fn connect(
+    req: HttpRequest,
+    body: web::Payload,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    broker: web::Data<Broker>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = get_user_id_from_request(&req);
+    let (response, session, stream) = actix_ws::handle(&req, body)?;
+    let stream = stream
+        .max_frame_size(MAX_MESSAGE_BYTES)
+        .aggregate_continuations()
+        .max_continuation_size(MAX_MESSAGE_BYTES);
+
+    rt::spawn(run(session, stream, user_id, pool, config, broker));
+    Ok(response)
+}
+
+async fn run(
+    mut session: Session,
+    mut stream: AggregatedMessageStream,
+    user_id: Option<Uuid>,
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    broker: web::Data<Broker>,
+) {
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => match authenticate(&mut session, &mut stream, &pool, &config).await {
+            Some(user_id) => user_id,
+            None => {
+                let _ = session.close(Some(CloseCode::Policy.into())).await;
+                return;
+            }
+        },
+    };
+
+    let mut events = broker.subscribe();
+    let mut posts = HashSet::new();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT_INTERVAL);
+    let mut last_heard = Instant::now();
+
+    if send(&mut session, "ready", json!({ "user_id": user_id })).await.is_err() {
+        return;
+    }
+
+    let reason = loop {
+        tokio::select! {
+            message = stream.recv() => {
+                last_heard = Instant::now();
+                let sent = match message {
+                    Some(Ok(AggregatedMessage::Text(text))) => {
+                        handle_message(&mut session, &pool, user_id, &mut posts, &text).await
+                    }
+                    Some(Ok(AggregatedMessage::Binary(_))) => {
+                        send_error(&mut session, "Only text messages are supported").await
+                    }
+                    Some(Ok(AggregatedMessage::Ping(data))) => session.pong(&data).await,
+                    Some(Ok(AggregatedMessage::Pong(_))) => Ok(()),
+                    Some(Ok(AggregatedMessage::Close(reason))) => break reason,
+                    Some(Err(_)) | None => break None,
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+            event = events.recv() => {
+                let sent = match event {
+                    Ok(message) => match message.topic {
+                        Topic::User(id) if id == user_id => session.text(message.event.to_string()).await,
+                        Topic::Post(id) if posts.contains(&id) => session.text(message.event.to_string()).await,
+                        _ => Ok(()),
+                    },
+                    // The client fell behind; it should refetch what it shows
+                    Err(RecvError::Lagged(missed)) => send(&mut session, "lagged", json!({ "missed": missed })).await,
+                    Err(RecvError::Closed) => break None,
+                };
+                if sent.is_err() {
+                    return;
+                }
+            }
+            _ = heartbeat.tick() => {
+                if last_heard.elapsed() > CLIENT_TIMEOUT {
+                    break Some(CloseReason {
+                        code: CloseCode::Away,
+                        description: Some("Connection timed out".to_string()),
+                    });
+                }
+                if session.ping(b"").await.is_err() {
+                    return;
+                }
+            }
+        }
+    };
+
+    let _ = session.close(reason).await;
+}
+
+// Waits for the `auth` message of a client that didn't authenticate on the
+// handshake, applying the same account checks as other requests
+async fn authenticate(
+    session: &mut Session,
+    stream: &mut AggregatedMessageStream,
+    pool: &PgPool,
+    config: &Config,
+) -> Option<Uuid> {
+    let message = match tokio::time::timeout(AUTH_TIMEOUT, stream.recv()).await {
+        Ok(Some(Ok(AggregatedMessage::Text(text)))) => serde_json::from_str::<ClientMessage>(&text).ok(),
+        _ => None,
+    };
+    let Some(ClientMessage::Auth { token }) = message else {
+        let _ = send_error(session, "Authentication required").await;
+        return None;
+    };
+
+    let Some(claims) = validate_token(&token, &config.jwt.keys).ok() else {
+        let _ = send_error(session, "Invalid or expired token").await;
+        return None;
+    };
+    if let Some(error) = check_token_account(pool, &claims, false).await {
+        let _ = send_error(session, &error.to_string()).await;
+        return None;
+    }
+
+    Uuid::parse_str(&claims.sub).ok()
+}
+
+async fn handle_message(
+    session: &mut Session,
+    pool: &PgPool,
+    user_id: Uuid,
+    posts: &mut HashSet<Uuid>,
+    text: &str,
+) -> Result<(), actix_ws::Closed> {
+    match serde_json::from_str::<ClientMessage>(text) {
+        Ok(ClientMessage::Subscribe { post_id }) => {
+            if posts.len() >= MAX_POST_SUBSCRIPTIONS && !posts.contains(&post_id) {
+                let message = format!("At most {} posts can be watched at once", MAX_POST_SUBSCRIPTIONS);
+                return send_error(session, &message).await;
+            }
+            match can_watch_post(pool, post_id, user_id).await {
+                Ok(true) => {
+                    posts.insert(post_id);
+                    send(session, "subscribed", json!({ "post_id": post_id })).await
+                }
+                Ok(false) => send_error(session, "Post not found").await,
+                Err(e) => {
+                    log::error!("Failed to check post {} for a realtime subscription: {}", post_id, e);
+                    send_error(session, "Failed to subscribe").await
+                }
+            }
+        }
+        Ok(ClientMessage::Unsubscribe { post_id }) => {
+            posts.remove(&post_id);
+            send(session, "unsubscribed", json!({ "post_id": post_id })).await
+        }
+        Ok(ClientMessage::Auth { .. }) => send_error(session, "Already authenticated").await,
+        Err(_) => send_error(session, "Unrecognized message").await,
+    }
+}
+
+// Published posts of active authors, and posts the caller wrote or co-authors
+async fn can_watch_post(pool: &PgPool, post_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM posts
+            WHERE id = $1 AND deleted_at IS NULL
+              AND (author_id = $2
+                   OR id IN (SELECT post_id FROM post_authors WHERE user_id = $2 AND accepted_at IS NOT NULL)
+                   OR (is_published = true AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)))
+        ) AS "exists!"
+        "#,
+        post_id,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+async fn send(session: &mut Session, event_type: &str, data: Value) -> Result<(), actix_ws::Closed> {
+    session.text(json!({ "type": event_type, "data": data }).to_string()).await
+}
+
+async fn send_error(session: &mut Session, message: &str) -> Result<(), actix_ws::Closed> {
+    send(session, "error", json!({ "message": message })).await
+}
+
+// Server-Sent Events with the posts followed authors publish, for clients
+// that can't use the WebSocket
+pub async fn stream_feed(
+    pool: web::Data<PgPool>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let following = followed_authors(&pool, user_id).await?;
+    let state = FeedStream {
+        pool,
+        user_id,
+        following,
+        refreshed_at: Instant::now(),
+        events: broker.subscribe(),
+        keepalive: tokio::time::interval(FEED_KEEPALIVE_INTERVAL),
+    };
+
+    let body = futures_util::stream::unfold(state, |mut state| async move {
+        let chunk = state.next_chunk().await?;
+        Some((Ok::<_, actix_web::Error>(chunk), state))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // Stops nginx from buffering the stream
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(body))
+}
+
+struct FeedStream {
+    pool: web::Data<PgPool>,
+    user_id: Uuid,
+    following: HashSet<Uuid>,
+    refreshed_at: Instant,
+    events: Receiver<Arc<RealtimeMessage>>,
+    keepalive: Interval,
+}
+
+impl FeedStream {
+    // The next bytes to send, or None once the broker has shut down
+    async fn next_chunk(&mut self) -> Option<Bytes> {
+        loop {
+            tokio::select! {
+                event = self.events.recv() => match event {
+                    Ok(message) => {
+                        if let Topic::Author(author_id) = message.topic {
+                            if self.follows(author_id).await {
+                                return Some(sse_event(&message.event));
+                            }
+                        }
+                    }
+                    Err(RecvError::Lagged(missed)) => {
+                        return Some(sse_event(&json!({ "type": "lagged", "data": { "missed": missed } })));
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = self.keepalive.tick() => return Some(Bytes::from_static(b": keepalive\n\n")),
+            }
+        }
+    }
+
+    // Follows made or dropped after the stream opened are picked up within
+    // FOLLOWING_REFRESH
+    async fn follows(&mut self, author_id: Uuid) -> bool {
+        if self.refreshed_at.elapsed() > FOLLOWING_REFRESH {
+            match followed_authors(&self.pool, self.user_id).await {
+                Ok(following) => self.following = following,
+                Err(e) => log::warn!("Failed to refresh followed authors for {}: {:?}", self.user_id, e),
+            }
+            self.refreshed_at = Instant::now();
+        }
+        self.following.contains(&author_id)
+    }
+}
+
+async fn followed_authors(pool: &PgPool, user_id: Uuid) -> Result<HashSet<Uuid>, sqlx::Error> {
+    let following = sqlx::query_scalar!("SELECT following_id FROM follows WHERE follower_id = $1", user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(following.into_iter().collect())
+}
+
+// An event's `type` becomes the SSE event name and its `data` the payload
+fn sse_event(event: &Value) -> Bytes {
+    let name = event["type"].as_str().unwrap_or("message");
+    Bytes::from(format!("event: {}\ndata: {}\n\n", name, event["data"]))
+}
@@ -0,0 +1,367 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::audit::{AuditAction, AuditEvent, AuditLogger};
+use crate::error::AppError;
+use crate::handlers::admin::{ban_account, take_down_comment, take_down_post};
+use crate::middleware::auth::{require_role, AuthenticatedUser};
+use crate::models::{
+    ApiResponse, CreateReportRequest, PaginatedResponse, Report, ReportAction, ReportListParams, ReportStatus,
+    ResolveReportRequest, Role, UpdateReportRequest,
+};
+use crate::realtime::Broker;
+use crate::utils::pagination::Page;
+
+pub async fn report_post(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<CreateReportRequest>,
+) -> Result<HttpResponse, AppError> {
+    let post_id = path.into_inner();
+
+    req.validate()?;
+
+    let author_id = sqlx::query_scalar!(
+        r#"
+        SELECT author_id FROM posts
+        WHERE id = $1 AND is_published = true AND deleted_at IS NULL
+          AND author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+        post_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Post not found".to_string()))?;
+
+    if author_id == user_id {
+        return Err(AppError::BadRequest("You can't report your own post".to_string()));
+    }
+
+    create_report(&pool, user_id, "post", post_id, &req).await
+}
+
+pub async fn report_comment(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<CreateReportRequest>,
+) -> Result<HttpResponse, AppError> {
+    let comment_id = path.into_inner();
+
+    req.validate()?;
+
+    let author_id = sqlx::query_scalar!(
+        r#"
+        SELECT c.author_id FROM comments c
+        INNER JOIN posts p ON c.post_id = p.id
+        WHERE c.id = $1 AND c.deleted_at IS NULL
+          AND c.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+          AND p.is_published = true AND p.deleted_at IS NULL
+        "#,
+        comment_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Comment not found".to_string()))?;
+
+    if author_id == user_id {
+        return Err(AppError::BadRequest("You can't report your own comment".to_string()));
+    }
+
+    create_report(&pool, user_id, "comment", comment_id, &req).await
+}
+
+async fn create_report(
+    pool: &PgPool,
+    reporter_id: Uuid,
+    target_type: &str,
+    target_id: Uuid,
+    req: &CreateReportRequest,
+) -> Result<HttpResponse, AppError> {
+    let report_id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO reports (reporter_id, target_type, target_id, reason, details)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (reporter_id, target_type, target_id) DO NOTHING
+        RETURNING id
+        "#,
+        reporter_id,
+        target_type,
+        target_id,
+        req.reason.as_str(),
+        req.details.as_deref().map(str::trim).filter(|details| !details.is_empty())
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    if report_id.is_none() {
+        return Err(AppError::Conflict(format!("You have already reported this {}", target_type)));
+    }
+
+    Ok(HttpResponse::Created().json(ApiResponse::<()>::ok_message("Report submitted")))
+}
+
+// Open reports by default, oldest first so none wait longest
+pub async fn get_reports(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<ReportListParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let params = query.into_inner();
+    let status = params.status.unwrap_or_else(|| "open".to_string());
+    if !matches!(status.as_str(), "open" | "reviewing" | "resolved" | "dismissed" | "all") {
+        return Err(AppError::BadRequest(format!(
+            "Invalid status '{}', expected one of: open, reviewing, resolved, dismissed, all",
+            status
+        )));
+    }
+    if let Some(target_type) = params.target_type.as_deref() {
+        if target_type != "post" && target_type != "comment" {
+            return Err(AppError::BadRequest(format!(
+                "Invalid target_type '{}', expected one of: post, comment",
+                target_type
+            )));
+        }
+    }
+
+    let Page { page, limit, offset } = Page::new(params.page, params.limit);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM reports
+        WHERE ($1 = 'all' OR status = $1) AND ($2::text IS NULL OR target_type = $2)
+        "#,
+        status,
+        params.target_type
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let reports = sqlx::query_as!(
+        Report,
+        r#"
+        SELECT r.id, r.reporter_id, r.target_type, r.target_id,
+               CASE r.target_type
+                   WHEN 'post' THEN (SELECT author_id FROM posts WHERE id = r.target_id)
+                   ELSE (SELECT author_id FROM comments WHERE id = r.target_id)
+               END as target_author_id,
+               r.reason, r.details, r.status, r.resolution, r.resolution_note, r.resolved_by, r.resolved_at, r.created_at
+        FROM reports r
+        WHERE ($1 = 'all' OR r.status = $1) AND ($2::text IS NULL OR r.target_type = $2)
+        ORDER BY r.created_at ASC, r.id ASC
+        LIMIT $3 OFFSET $4
+        "#,
+        status,
+        params.target_type,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: reports,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+pub async fn get_report(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+
+    let report = fetch_report(&pool, path.into_inner())
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+// Acting on a report closes every open report on the same post or comment.
+// Banning the author takes admin rights, like banning anywhere else.
+pub async fn resolve_report(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    broker: web::Data<Broker>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<ResolveReportRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let action = req.action;
+    let note = resolution_note(req.note.as_deref());
+    let required_role = if action == ReportAction::BanAuthor { Role::Admin } else { Role::Moderator };
+    require_role(&pool, user_id, required_role).await?;
+
+    let report_id = path.into_inner();
+    let report = fetch_report(&pool, report_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    if !is_pending(&report.status) {
+        return Err(AppError::Conflict("Report has already been resolved".to_string()));
+    }
+
+    if action == ReportAction::BanAuthor {
+        let author_id = report
+            .target_author_id
+            .ok_or_else(|| AppError::NotFound("The reported content no longer exists".to_string()))?;
+        ban_account(&pool, user_id, author_id).await?;
+
+        let event = AuditEvent::new(AuditAction::UserBanned)
+            .actor(user_id)
+            .target("user", author_id)
+            .metadata(json!({ "report_id": report_id }));
+        audit.record(&http_req, event).await;
+    }
+
+    if action != ReportAction::Dismiss {
+        // Already taken down is fine; the reports are closed all the same
+        let (taken_down, deleted, target_type) = if report.target_type == "post" {
+            let taken_down = take_down_post(&pool, user_id, report.target_id).await?;
+            (taken_down, AuditAction::PostDeleted, "post")
+        } else {
+            let taken_down = take_down_comment(&pool, &broker, report.target_id).await?;
+            (taken_down, AuditAction::CommentDeleted, "comment")
+        };
+
+        if taken_down {
+            let event = AuditEvent::new(deleted)
+                .actor(user_id)
+                .target(target_type, report.target_id)
+                .metadata(json!({ "moderation": true, "report_id": report_id }));
+            audit.record(&http_req, event).await;
+        }
+    }
+
+    let status = if action == ReportAction::Dismiss { "dismissed" } else { "resolved" };
+    let closed = sqlx::query!(
+        r#"
+        UPDATE reports SET status = $3, resolution = $4, resolution_note = COALESCE($7, resolution_note), resolved_by = $5, resolved_at = $6
+        WHERE target_type = $1 AND target_id = $2 AND status IN ('open', 'reviewing')
+        "#,
+        report.target_type,
+        report.target_id,
+        status,
+        action.as_str(),
+        user_id,
+        Utc::now(),
+        note
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    log::warn!("Report {} resolved with {} by moderator {}", report_id, action.as_str(), user_id);
+    let event = AuditEvent::new(AuditAction::ReportResolved)
+        .actor(user_id)
+        .target("report", report_id)
+        .metadata(json!({ "action": action, "note": note, "reports_closed": closed.rows_affected() }));
+    audit.record(&http_req, event).await;
+
+    let report = fetch_report(&pool, report_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+// Moves a single report through the queue: claiming it for review, putting
+// it back, or closing it without acting on the content. Taking content down
+// goes through resolve_report.
+pub async fn update_report(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateReportRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Moderator).await?;
+    req.validate()?;
+
+    let report_id = path.into_inner();
+    let note = resolution_note(req.note.as_deref());
+
+    let report = fetch_report(&pool, report_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    let closing = !is_pending(req.status.as_str());
+    sqlx::query!(
+        r#"
+        UPDATE reports SET
+            status = $2,
+            resolution_note = COALESCE($3, resolution_note),
+            resolution = CASE WHEN $4 THEN resolution END,
+            resolved_by = CASE WHEN $4 THEN $5::uuid END,
+            resolved_at = CASE WHEN $4 THEN $6::timestamptz END
+        WHERE id = $1
+        "#,
+        report_id,
+        req.status.as_str(),
+        note,
+        closing,
+        user_id,
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    let event = AuditEvent::new(AuditAction::ReportUpdated)
+        .actor(user_id)
+        .target("report", report_id)
+        .metadata(json!({ "from": report.status, "to": req.status, "note": note }));
+    audit.record(&http_req, event).await;
+
+    let report = fetch_report(&pool, report_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Report not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(report)))
+}
+
+// Open and reviewing reports are still waiting on a decision
+fn is_pending(status: &str) -> bool {
+    status == ReportStatus::Open.as_str() || status == ReportStatus::Reviewing.as_str()
+}
+
+fn resolution_note(note: Option<&str>) -> Option<&str> {
+    note.map(str::trim).filter(|note| !note.is_empty())
+}
+
+async fn fetch_report(pool: &PgPool, report_id: Uuid) -> Result<Option<Report>, sqlx::Error> {
+    sqlx::query_as!(
+        Report,
+        r#"
+        SELECT r.id, r.reporter_id, r.target_type, r.target_id,
+               CASE r.target_type
+                   WHEN 'post' THEN (SELECT author_id FROM posts WHERE id = r.target_id)
+                   ELSE (SELECT author_id FROM comments WHERE id = r.target_id)
+               END as target_author_id,
+               r.reason, r.details, r.status, r.resolution, r.resolution_note, r.resolved_by, r.resolved_at, r.created_at
+        FROM reports r
+        WHERE r.id = $1
+        "#,
+        report_id
+    )
+    .fetch_optional(pool)
+    .await
+}
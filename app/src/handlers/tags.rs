@@ -1,220 +1,614 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::http::header::{CacheControl, CacheDirective, LOCATION};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use serde_json::json;
 use sqlx::PgPool;
-use uuid::Uuid;
+use url::Url;
+use validator::Validate;
 
-use crate::models::{ApiResponse, PaginatedResponse, PaginationParams, Post, PostResponse, Tag, UserResponse};
-use crate::middleware::auth::extract_optional_user_id;
+use crate::audit::{AuditAction, AuditEvent, AuditLogger};
+use crate::error::AppError;
+use crate::handlers::posts::build_post_summaries;
+use crate::models::{
+    ApiResponse, CreateTagSynonymRequest, FieldsParams, MergeTagRequest, PaginatedResponse, PaginationParams, Post, PostSort,
+    RelatedTagsParams, SortParams, Tag, TagSynonym, Role, TagCloudParams, TagWeight, TrendingParams,
+    TrendingPeriod, TrendingTag, UpdateTagRequest,
+};
+use crate::utils::fields::{shape, FieldSelection};
+use crate::utils::pagination::Page;
+use crate::utils::tags::normalize_tag_name;
+
+// Whose precomputed trending scores rank `sort=trending` post lists
+pub const TRENDING_SORT_PERIOD: TrendingPeriod = TrendingPeriod::Week;
+
+// How long clients and proxies may cache the tag cloud, related and
+// trending tags
+const TAG_STATS_MAX_AGE_SECS: u32 = 300;
+
+const DEFAULT_RELATED_TAGS: u32 = 10;
+const MAX_RELATED_TAGS: u32 = 50;
+use crate::middleware::auth::{require_role, AuthenticatedUser, MaybeUser};
 
 pub async fn get_tags(
     pool: web::Data<PgPool>,
     query: web::Query<PaginationParams>,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, AppError> {
     let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
     let tags = sqlx::query_as!(
         Tag,
-        "SELECT id, name, created_at FROM tags ORDER BY name ASC LIMIT $1 OFFSET $2",
+        r#"
+        SELECT t.id, t.name, t.slug, t.description, t.color, t.cover_image,
+               (SELECT COUNT(*) FROM post_tags pt INNER JOIN posts p ON pt.post_id = p.id
+                WHERE pt.tag_id = t.id AND p.is_published = true AND p.deleted_at IS NULL
+                  AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)) as "post_count!",
+               t.created_at, t.updated_at
+        FROM tags t
+        ORDER BY t.name ASC
+        LIMIT $1 OFFSET $2
+        "#,
         limit as i64,
-        offset as i64
+        offset
     )
     .fetch_all(pool.get_ref())
-    .await;
-
-    match tags {
-        Ok(tags) => {
-            let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tags")
-                .fetch_one(pool.get_ref())
-                .await
-                .unwrap_or((0,));
-
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
-
-            let paginated_response = PaginatedResponse {
-                data: tags,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Failed to get tags: {:?}", e);
-            Ok(HttpResponse::InternalServerError()
-                .json(ApiResponse::<()>::error("Failed to get tags".to_string())))
+    .await?;
+
+    let total: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM tags")
+        .fetch_one(pool.get_ref())
+        .await
+        .unwrap_or((0,));
+
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: tags,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+pub async fn get_tag(
+    pool: web::Data<PgPool>,
+    path: web::Path<String>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let tag_name = path.into_inner();
+    if let Some(tag) = fetch_tag(&pool, &tag_name).await? {
+        return Ok(HttpResponse::Ok().json(ApiResponse::success(tag)));
+    }
+
+    match resolve_tag_alias(&pool, &tag_name).await? {
+        Some(canonical) => Ok(redirect_to_tag(&http_req, &canonical, "")),
+        None => Err(AppError::NotFound("Tag not found".to_string())),
+    }
+}
+
+// Admins curate how a tag is presented; its name stays as posts use it
+pub async fn update_tag(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<String>,
+    req: web::Json<UpdateTagRequest>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+    req.validate()?;
+
+    let tag_name = path.into_inner();
+
+    if let Some(slug) = req.slug.as_deref() {
+        let taken = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM tags WHERE slug = $1 AND name <> $2) as "taken!""#,
+            slug,
+            tag_name
+        )
+        .fetch_one(pool.get_ref())
+        .await?;
+
+        if taken {
+            return Err(AppError::Conflict(format!("Slug '{}' is already used by another tag", slug)));
         }
     }
+
+    let result = sqlx::query!(
+        r#"
+        UPDATE tags SET
+            slug = COALESCE($2, slug),
+            description = COALESCE($3, description),
+            color = COALESCE($4, color),
+            cover_image = COALESCE($5, cover_image),
+            updated_at = $6
+        WHERE name = $1
+        "#,
+        tag_name,
+        req.slug.as_deref(),
+        req.description.as_deref(),
+        req.color.as_deref().map(str::to_lowercase),
+        req.cover_image.as_deref(),
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Tag not found".to_string()));
+    }
+
+    let tag = fetch_tag(&pool, &tag_name)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(tag)))
 }
 
-pub async fn get_posts_by_tag(
+async fn fetch_tag(pool: &PgPool, tag_name: &str) -> Result<Option<Tag>, sqlx::Error> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT t.id, t.name, t.slug, t.description, t.color, t.cover_image,
+               (SELECT COUNT(*) FROM post_tags pt INNER JOIN posts p ON pt.post_id = p.id
+                WHERE pt.tag_id = t.id AND p.is_published = true AND p.deleted_at IS NULL
+                  AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)) as "post_count!",
+               t.created_at, t.updated_at
+        FROM tags t
+        WHERE t.name = $1
+        "#,
+        tag_name
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// The tag a name stands for when it isn't a tag name itself: the tag it
+// normalizes to, or the one it is a synonym of
+pub async fn resolve_tag_alias(pool: &PgPool, name: &str) -> Result<Option<String>, sqlx::Error> {
+    let canonical = sqlx::query_scalar!(
+        r#"
+        SELECT name as "name!" FROM tags WHERE name = $1
+        UNION ALL
+        SELECT t.name FROM tag_synonyms s
+        INNER JOIN tags t ON s.tag_id = t.id
+        WHERE s.name = $1
+        LIMIT 1
+        "#,
+        normalize_tag_name(name)
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(canonical.filter(|canonical| canonical != name))
+}
+
+// Old tag URLs keep working after a merge, query string included
+fn redirect_to_tag(http_req: &HttpRequest, tag_name: &str, suffix: &str) -> HttpResponse {
+    let mut url = Url::parse("http://localhost/api/v1/tags").expect("static URL");
+    url.path_segments_mut().expect("base URL").push(tag_name);
+
+    let mut location = format!("{}{}", url.path(), suffix);
+    if !http_req.query_string().is_empty() {
+        location.push('?');
+        location.push_str(http_req.query_string());
+    }
+
+    HttpResponse::MovedPermanently()
+        .insert_header((LOCATION, location))
+        .finish()
+}
+
+// Moves every post of a tag onto another and deletes it, keeping its name
+// as a synonym of the tag it was merged into
+pub async fn merge_tag(
     pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     path: web::Path<String>,
-    query: web::Query<PaginationParams>,
+    req: web::Json<MergeTagRequest>,
     http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let tag_name = path.into_inner();
-    let user_id = extract_optional_user_id(&http_req);
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+    req.validate()?;
+
+    let source_name = path.into_inner();
+    let target_name = normalize_tag_name(&req.into);
+    if source_name == target_name {
+        return Err(AppError::BadRequest("A tag cannot be merged into itself".to_string()));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let source_id = sqlx::query_scalar!("SELECT id FROM tags WHERE name = $1 FOR UPDATE", source_name)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))?;
+
+    let target_id = sqlx::query_scalar!("SELECT id FROM tags WHERE name = $1 FOR UPDATE", target_name)
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Tag '{}' not found", target_name)))?;
+
+    let posts_moved = sqlx::query!(
+        r#"
+        INSERT INTO post_tags (post_id, tag_id)
+        SELECT post_id, $2 FROM post_tags WHERE tag_id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        source_id,
+        target_id
+    )
+    .execute(&mut *tx)
+    .await?
+    .rows_affected();
+
+    sqlx::query!("UPDATE tag_synonyms SET tag_id = $2 WHERE tag_id = $1", source_id, target_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // Cascades to the tag's post links and trending scores
+    sqlx::query!("DELETE FROM tags WHERE id = $1", source_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO tag_synonyms (name, tag_id, created_by, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (LOWER(name)) DO UPDATE SET tag_id = EXCLUDED.tag_id
+        "#,
+        source_name,
+        target_id,
+        user_id,
+        Utc::now()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    log::warn!("Tag '{}' merged into '{}' by admin {}", source_name, target_name, user_id);
+    let event = AuditEvent::new(AuditAction::TagMerged)
+        .actor(user_id)
+        .target("tag", target_id)
+        .metadata(json!({ "from": source_name, "from_id": source_id, "into": target_name, "posts_moved": posts_moved }));
+    audit.record(&http_req, event).await;
+
+    let tag = fetch_tag(&pool, &target_name)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Tag not found".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(tag)))
+}
+
+pub async fn get_tag_synonyms(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
     let pagination = query.into_inner();
-    let page = pagination.page.unwrap_or(1);
-    let limit = pagination.limit.unwrap_or(20);
-    let offset = (page - 1) * limit;
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
 
-    let posts = sqlx::query_as!(
-        Post,
+    let synonyms = sqlx::query_as!(
+        TagSynonym,
         r#"
-        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.created_at, p.updated_at FROM posts p
-        INNER JOIN post_tags pt ON p.id = pt.post_id
-        INNER JOIN tags t ON pt.tag_id = t.id
-        WHERE t.name = $1 AND p.is_published = true
-        ORDER BY p.published_at DESC
-        LIMIT $2 OFFSET $3
+        SELECT s.name, t.name as tag, s.created_by, s.created_at
+        FROM tag_synonyms s
+        INNER JOIN tags t ON s.tag_id = t.id
+        ORDER BY s.name ASC
+        LIMIT $1 OFFSET $2
         "#,
-        tag_name,
         limit as i64,
-        offset as i64
+        offset
     )
     .fetch_all(pool.get_ref())
-    .await;
-
-    match posts {
-        Ok(posts) => {
-            let mut post_responses = Vec::new();
-            for post in posts {
-                let post_response = build_post_response(&pool, post, user_id).await?;
-                post_responses.push(post_response);
-            }
-
-            let total: (i64,) = sqlx::query_as(
-                r#"
-                SELECT COUNT(*) FROM posts p
-                INNER JOIN post_tags pt ON p.id = pt.post_id
-                INNER JOIN tags t ON pt.tag_id = t.id
-                WHERE t.name = $1 AND p.is_published = true
-                "#,
-            )
-            .bind(&tag_name)
-            .fetch_one(pool.get_ref())
-            .await
-            .unwrap_or((0,));
-
-            let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
-
-            let paginated_response = PaginatedResponse {
-                data: post_responses,
-                total: total.0,
-                page,
-                limit,
-                total_pages,
-            };
-
-            Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
-        }
-        Err(e) => {
-            log::error!("Failed to get posts by tag: {:?}", e);
-            Ok(HttpResponse::InternalServerError()
-                .json(ApiResponse::<()>::error("Failed to get posts by tag".to_string())))
-        }
+    .await?;
+
+    let total = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM tag_synonyms"#)
+        .fetch_one(pool.get_ref())
+        .await?;
+
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: synonyms,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
+
+// Posts tagged with the synonym get the tag it points at instead
+pub async fn create_tag_synonym(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<CreateTagSynonymRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+    req.validate()?;
+
+    let name = normalize_tag_name(&req.name);
+    let tag_name = normalize_tag_name(&req.tag);
+
+    let shadowed = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM tags WHERE name = $1) as "exists!""#,
+        name
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if shadowed {
+        return Err(AppError::Conflict(format!(
+            "A tag named '{}' already exists; merge it instead",
+            name
+        )));
     }
+
+    let tag_id = sqlx::query_scalar!("SELECT id FROM tags WHERE name = $1", tag_name)
+        .fetch_optional(pool.get_ref())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Tag '{}' not found", tag_name)))?;
+
+    let synonym = sqlx::query_as!(
+        TagSynonym,
+        r#"
+        INSERT INTO tag_synonyms (name, tag_id, created_by, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT DO NOTHING
+        RETURNING name, $5::text as "tag!", created_by, created_at
+        "#,
+        name,
+        tag_id,
+        user_id,
+        Utc::now(),
+        tag_name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::Conflict(format!("'{}' is already a tag synonym", name)))?;
+
+    let event = AuditEvent::new(AuditAction::TagSynonymCreated)
+        .actor(user_id)
+        .target("tag", tag_id)
+        .metadata(json!({ "synonym": name, "tag": tag_name }));
+    audit.record(&http_req, event).await;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(synonym)))
 }
 
-async fn build_post_response(
-    pool: &PgPool,
-    post: Post,
-    current_user_id: Option<Uuid>,
-) -> Result<PostResponse> {
-    // Get author info
-    let author = sqlx::query!(
+pub async fn delete_tag_synonym(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<String>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    require_role(&pool, user_id, Role::Admin).await?;
+
+    let name = normalize_tag_name(&path.into_inner());
+    let deleted = sqlx::query!(
+        "DELETE FROM tag_synonyms WHERE name = $1 RETURNING name, tag_id",
+        name
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| AppError::NotFound("Tag synonym not found".to_string()))?;
+
+    let event = AuditEvent::new(AuditAction::TagSynonymDeleted)
+        .actor(user_id)
+        .target("tag", deleted.tag_id)
+        .metadata(json!({ "synonym": deleted.name }));
+    audit.record(&http_req, event).await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Tag synonym deleted")))
+}
+
+// Every tag weighted by its number of published posts, heaviest first,
+// optionally capped to the top `limit` tags
+pub async fn get_tag_cloud(
+    pool: web::Data<PgPool>,
+    query: web::Query<TagCloudParams>,
+) -> Result<HttpResponse, AppError> {
+    let limit = query.into_inner().limit;
+    if limit == Some(0) {
+        return Err(AppError::BadRequest("limit must be greater than 0".to_string()));
+    }
+
+    let tags = sqlx::query_as!(
+        TagWeight,
         r#"
-        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at,
-               COUNT(DISTINCT f1.follower_id) as "follower_count!",
-               COUNT(DISTINCT f2.following_id) as "following_count!"
-        FROM users u
-        LEFT JOIN follows f1 ON u.id = f1.following_id
-        LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE u.id = $1
-        GROUP BY u.id
+        SELECT t.name, COUNT(p.id) as "weight!"
+        FROM tags t
+        LEFT JOIN post_tags pt ON t.id = pt.tag_id
+        LEFT JOIN posts p ON pt.post_id = p.id AND p.is_published = true AND p.deleted_at IS NULL
+            AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        GROUP BY t.id, t.name
+        ORDER BY COUNT(p.id) DESC, t.name ASC
+        LIMIT $1
         "#,
-        post.author_id
+        limit.map(i64::from)
     )
-    .fetch_one(pool)
-    .await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(TAG_STATS_MAX_AGE_SECS),
+        ]))
+        .json(ApiResponse::success(tags)))
+}
 
-    // Get tags
-    let tags = sqlx::query!(
+// Tags that most often appear on the same published posts as the given tag,
+// weighted by the number of posts they share
+pub async fn get_related_tags(
+    pool: web::Data<PgPool>,
+    path: web::Path<String>,
+    query: web::Query<RelatedTagsParams>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let tag_name = path.into_inner();
+    let limit = query.into_inner().limit.unwrap_or(DEFAULT_RELATED_TAGS);
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be greater than 0".to_string()));
+    }
+
+    let tag_exists = sqlx::query!("SELECT id FROM tags WHERE name = $1", tag_name)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    if tag_exists.is_none() {
+        return match resolve_tag_alias(&pool, &tag_name).await? {
+            Some(canonical) => Ok(redirect_to_tag(&http_req, &canonical, "/related")),
+            None => Err(AppError::NotFound("Tag not found".to_string())),
+        };
+    }
+
+    let tags = sqlx::query_as!(
+        TagWeight,
         r#"
-        SELECT t.name FROM tags t
+        SELECT related.name, COUNT(*) as "weight!"
+        FROM tags t
         INNER JOIN post_tags pt ON t.id = pt.tag_id
-        WHERE pt.post_id = $1
+        INNER JOIN posts p ON pt.post_id = p.id
+        INNER JOIN post_tags related_pt ON p.id = related_pt.post_id AND related_pt.tag_id <> t.id
+        INNER JOIN tags related ON related_pt.tag_id = related.id
+        WHERE t.name = $1 AND p.is_published = true AND p.deleted_at IS NULL
+            AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        GROUP BY related.id, related.name
+        ORDER BY COUNT(*) DESC, related.name ASC
+        LIMIT $2
         "#,
-        post.id
+        tag_name,
+        limit.min(MAX_RELATED_TAGS) as i64
     )
-    .fetch_all(pool)
-    .await
-    .map_err(|e| actix_web::error::ErrorInternalServerError(e))?;
+    .fetch_all(pool.get_ref())
+    .await?;
 
-    // Get like count
-    let like_count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM likes WHERE post_id = $1"
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(TAG_STATS_MAX_AGE_SECS),
+        ]))
+        .json(ApiResponse::success(tags)))
+}
+
+// Tags ranked by the summed trending scores of their posts, as of the last
+// jobs::trending refresh
+pub async fn get_trending_tags(
+    pool: web::Data<PgPool>,
+    trending_query: web::Query<TrendingParams>,
+    query: web::Query<TagCloudParams>,
+) -> Result<HttpResponse, AppError> {
+    let period = TrendingPeriod::parse(trending_query.period.as_deref()).map_err(AppError::BadRequest)?;
+    let limit = query.into_inner().limit.unwrap_or(DEFAULT_RELATED_TAGS);
+    if limit == 0 {
+        return Err(AppError::BadRequest("limit must be greater than 0".to_string()));
+    }
+
+    let tags = sqlx::query_as!(
+        TrendingTag,
+        r#"
+        SELECT t.name, tt.score, tt.post_count
+        FROM trending_tags tt
+        INNER JOIN tags t ON tt.tag_id = t.id
+        WHERE tt.period = $1
+        ORDER BY tt.score DESC, t.name ASC
+        LIMIT $2
+        "#,
+        period.as_str(),
+        limit.min(MAX_RELATED_TAGS) as i64
     )
-    .bind(post.id)
-    .fetch_one(pool)
-    .await
-    .unwrap_or((0,));
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(TAG_STATS_MAX_AGE_SECS),
+        ]))
+        .json(ApiResponse::success(tags)))
+}
+
+pub async fn get_posts_by_tag(
+    pool: web::Data<PgPool>,
+    MaybeUser(user_id): MaybeUser,
+    path: web::Path<String>,
+    query: web::Query<PaginationParams>,
+    sort_query: web::Query<SortParams>,
+    http_req: HttpRequest,
+    fields_query: web::Query<FieldsParams>,
+) -> Result<HttpResponse, AppError> {
+    let fields = FieldSelection::parse(fields_query.fields.as_deref()).map_err(AppError::BadRequest)?;
+    let tag_name = path.into_inner();
+    if let Some(canonical) = resolve_tag_alias(&pool, &tag_name).await? {
+        return Ok(redirect_to_tag(&http_req, &canonical, "/posts"));
+    }
+
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let sort = match PostSort::parse(sort_query.sort.as_deref()) {
+        Ok(sort) => sort,
+        Err(message) => {
+            return Err(AppError::BadRequest(message));
+        }
+    };
 
-    // Get comment count
-    let comment_count: (i64,) = sqlx::query_as(
-        "SELECT COUNT(*) FROM comments WHERE post_id = $1"
+    // Same ordering as the post list, one query for every sort
+    let posts = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT p.id, p.title, p.slug, p.content, p.excerpt, p.cover_image, p.author_id, p.is_published, p.published_at, p.publish_at, p.language, p.like_count, p.comment_count, p.word_count, p.reading_time_minutes, p.created_at, p.updated_at FROM posts p
+        INNER JOIN post_tags pt ON p.id = pt.post_id
+        INNER JOIN tags t ON pt.tag_id = t.id
+        LEFT JOIN trending_posts tp ON tp.post_id = p.id AND tp.period = $5
+        WHERE t.name = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        ORDER BY CASE WHEN $4 = 'most_liked' THEN p.like_count END DESC,
+                 CASE WHEN $4 = 'most_commented' THEN p.comment_count END DESC,
+                 CASE WHEN $4 = 'trending' THEN COALESCE(tp.score, 0) END DESC,
+                 p.published_at DESC, p.id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        tag_name,
+        limit as i64,
+        offset,
+        sort.as_str(),
+        TRENDING_SORT_PERIOD.as_str()
     )
-    .bind(post.id)
-    .fetch_one(pool)
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let post_responses = shape(build_post_summaries(&pool, posts, user_id).await?, fields.as_ref())?;
+
+    let total: (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM posts p
+        INNER JOIN post_tags pt ON p.id = pt.post_id
+        INNER JOIN tags t ON pt.tag_id = t.id
+        WHERE t.name = $1 AND p.is_published = true AND p.deleted_at IS NULL AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        "#,
+    )
+    .bind(&tag_name)
+    .fetch_one(pool.get_ref())
     .await
     .unwrap_or((0,));
 
-    // Check if current user liked the post
-    let is_liked = if let Some(user_id) = current_user_id {
-        sqlx::query!(
-            "SELECT id FROM likes WHERE post_id = $1 AND user_id = $2",
-            post.id,
-            user_id
-        )
-        .fetch_optional(pool)
-        .await
-        .unwrap_or(None)
-        .is_some()
-    } else {
-        false
+    let total_pages = (total.0 as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: post_responses,
+        total: total.0,
+        page,
+        limit,
+        total_pages,
     };
 
-    Ok(PostResponse {
-        id: post.id,
-        title: post.title,
-        slug: post.slug,
-        content: post.content,
-        excerpt: post.excerpt,
-        cover_image: post.cover_image,
-        author: UserResponse {
-            id: author.id,
-            username: author.username,
-            email: author.email,
-            full_name: author.full_name,
-            bio: author.bio,
-            avatar_url: author.avatar_url,
-            is_verified: author.is_verified.unwrap_or(false),
-            follower_count: author.follower_count,
-            following_count: author.following_count,
-            created_at: author.created_at.unwrap(),
-        },
-        tags: tags.into_iter().map(|t| t.name).collect(),
-        like_count: like_count.0,
-        comment_count: comment_count.0,
-        is_liked,
-        is_published: post.is_published.unwrap_or(false),
-        published_at: post.published_at,
-        created_at: post.created_at.unwrap(),
-        updated_at: post.updated_at.unwrap(),
-    })
-}
\ No newline at end of file
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
@@ -1,36 +1,60 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::middleware::auth::get_user_id_from_request;
-use crate::models::{ApiResponse, UpdateUserRequest, UserResponse};
+use crate::error::AppError;
+use crate::handlers::follows::attach_relationships;
+use crate::middleware::auth::{has_role, AuthenticatedUser, MaybeUser};
+use crate::models::{ApiResponse, Role, UpdateUserRequest, UserResponse};
+use crate::utils::conditional::CacheValidators;
+
+// Deleted accounts stay deactivated for this long before they are purged
+const ACCOUNT_DELETION_GRACE_DAYS: i64 = 14;
+
+#[derive(serde::Serialize)]
+struct AccountStatusResponse {
+    is_active: bool,
+    deactivated_at: Option<DateTime<Utc>>,
+    deletion_scheduled_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct UserSummaryResponse {
+    scheduled_post_count: i64,
+    // Open reports awaiting review; only for moderators and admins
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pending_report_count: Option<i64>,
+}
 
 pub async fn get_user(
     pool: web::Data<PgPool>,
+    MaybeUser(viewer_id): MaybeUser,
     path: web::Path<Uuid>,
-) -> Result<HttpResponse> {
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
     let user_id = path.into_inner();
 
     let user = sqlx::query!(
         r#"
-        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at,
+        SELECT u.id, u.username, u.email, u.full_name, u.bio, u.avatar_url, u.is_verified, u.created_at, u.updated_at,
                COUNT(DISTINCT f1.follower_id) as "follower_count!",
                COUNT(DISTINCT f2.following_id) as "following_count!"
         FROM users u
         LEFT JOIN follows f1 ON u.id = f1.following_id
         LEFT JOIN follows f2 ON u.id = f2.follower_id
-        WHERE u.id = $1
+        WHERE u.id = $1 AND u.deactivated_at IS NULL
         GROUP BY u.id
         "#,
         user_id
     )
     .fetch_optional(pool.get_ref())
-    .await;
+    .await?;
 
     match user {
-        Ok(Some(user)) => {
-            let user_response = UserResponse {
+        Some(user) => {
+            let mut user_response = [UserResponse {
                 id: user.id,
                 username: user.username,
                 email: user.email,
@@ -41,58 +65,40 @@ pub async fn get_user(
                 follower_count: user.follower_count,
                 following_count: user.following_count,
                 created_at: user.created_at.unwrap(),
-            };
-            Ok(HttpResponse::Ok().json(ApiResponse::success(user_response)))
-        }
-        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
-            "User not found".to_string(),
-        ))),
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
+                relationship: None,
+            }];
+            attach_relationships(&pool, viewer_id, &mut user_response).await?;
+            let [user_response] = user_response;
+
+            // Follower counts and the relationship change without touching
+            // updated_at, so there's no Last-Modified
+            let validators = CacheValidators::for_body(&user_response, None)?.private();
+            if let Some(response) = validators.not_modified(&http_req) {
+                return Ok(response);
+            }
+
+            Ok(validators.apply(&mut HttpResponse::Ok()).json(ApiResponse::success(user_response)))
         }
+        None => Err(AppError::NotFound("User not found".to_string())),
     }
 }
 
 pub async fn get_profile(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
-
-    get_user(pool, web::Path::from(user_id)).await
+) -> Result<HttpResponse, AppError> {
+    get_user(pool, MaybeUser(Some(user_id)), web::Path::from(user_id), http_req).await
 }
 
 pub async fn update_profile(
     pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
     req: web::Json<UpdateUserRequest>,
-    http_req: HttpRequest,
-) -> Result<HttpResponse> {
-    let user_id = match get_user_id_from_request(&http_req) {
-        Some(id) => id,
-        None => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Authentication required".to_string(),
-            )));
-        }
-    };
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
 
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
-    }
-
-    let updated_user = sqlx::query!(
+    let user = sqlx::query!(
         r#"
         UPDATE users SET
             full_name = COALESCE($2, full_name),
@@ -109,60 +115,265 @@ pub async fn update_profile(
         chrono::Utc::now()
     )
     .fetch_one(pool.get_ref())
+    .await?;
+
+    // Get follower counts
+    let counts = sqlx::query!(
+        r#"
+        SELECT COUNT(DISTINCT f1.follower_id) as "follower_count!",
+               COUNT(DISTINCT f2.following_id) as "following_count!"
+        FROM users u
+        LEFT JOIN follows f1 ON u.id = f1.following_id
+        LEFT JOIN follows f2 ON u.id = f2.follower_id
+        WHERE u.id = $1
+        GROUP BY u.id
+        "#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
     .await;
 
-    match updated_user {
-        Ok(user) => {
-            // Get follower counts
-            let counts = sqlx::query!(
-                r#"
-                SELECT COUNT(DISTINCT f1.follower_id) as "follower_count!",
-                       COUNT(DISTINCT f2.following_id) as "following_count!"
-                FROM users u
-                LEFT JOIN follows f1 ON u.id = f1.following_id
-                LEFT JOIN follows f2 ON u.id = f2.follower_id
-                WHERE u.id = $1
-                GROUP BY u.id
-                "#,
-                user_id
-            )
+    let user_response = match counts {
+        Ok(counts) => UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            follower_count: counts.follower_count,
+            following_count: counts.following_count,
+            created_at: user.created_at.unwrap(),
+            relationship: None,
+        },
+        Err(_) => UserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            follower_count: 0,
+            following_count: 0,
+            created_at: user.created_at.unwrap(),
+            relationship: None,
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(user_response)))
+}
+
+// Hides the account and its content from everyone else. Existing tokens stop
+// working; the user has to log in again and call reactivate to undo it.
+pub async fn deactivate_account(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    set_deactivated_at(&pool, user_id, Some(Utc::now())).await
+}
+
+pub async fn reactivate_account(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    set_deactivated_at(&pool, user_id, None).await
+}
+
+// Reactivating also cancels a pending account deletion
+async fn set_deactivated_at(
+    pool: &PgPool,
+    user_id: Uuid,
+    deactivated_at: Option<DateTime<Utc>>,
+) -> Result<HttpResponse, AppError> {
+    let account = sqlx::query!(
+        r#"
+        UPDATE users SET
+            deactivated_at = $2,
+            tokens_revoked_at = COALESCE($2, tokens_revoked_at),
+            deletion_scheduled_at = CASE WHEN $2::timestamptz IS NULL THEN NULL ELSE deletion_scheduled_at END,
+            updated_at = $3
+        WHERE id = $1
+        RETURNING deletion_scheduled_at
+        "#,
+        user_id,
+        deactivated_at,
+        Utc::now()
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match account {
+        None => Err(AppError::NotFound("User not found".to_string())),
+        Some(account) => Ok(HttpResponse::Ok().json(ApiResponse::success(AccountStatusResponse {
+            is_active: deactivated_at.is_none(),
+            deactivated_at,
+            deletion_scheduled_at: account.deletion_scheduled_at,
+        }))),
+    }
+}
+
+// Deactivates the account right away, which hides its content and revokes
+// its tokens, and leaves the purge to the account deletion job
+pub async fn delete_account(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let now = Utc::now();
+    let account = sqlx::query!(
+        r#"
+        UPDATE users SET
+            deactivated_at = COALESCE(deactivated_at, $2),
+            tokens_revoked_at = $2,
+            deletion_scheduled_at = COALESCE(deletion_scheduled_at, $3),
+            updated_at = $2
+        WHERE id = $1
+        RETURNING deactivated_at, deletion_scheduled_at
+        "#,
+        user_id,
+        now,
+        now + Duration::days(ACCOUNT_DELETION_GRACE_DAYS)
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
+
+    match account {
+        Some(account) => Ok(HttpResponse::Accepted().json(ApiResponse::success(AccountStatusResponse {
+            is_active: false,
+            deactivated_at: account.deactivated_at,
+            deletion_scheduled_at: account.deletion_scheduled_at,
+        }))),
+        None => Err(AppError::NotFound("User not found".to_string())),
+    }
+}
+
+pub async fn get_my_summary(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let scheduled_post_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!" FROM posts
+        WHERE author_id = $1 AND is_published = false AND deleted_at IS NULL AND publish_at IS NOT NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let pending_report_count = if has_role(&pool, user_id, Role::Moderator).await? {
+        let count = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM reports WHERE status = 'open'"#)
             .fetch_one(pool.get_ref())
-            .await;
-
-            let user_response = match counts {
-                Ok(counts) => UserResponse {
-                    id: user.id,
-                    username: user.username,
-                    email: user.email,
-                    full_name: user.full_name,
-                    bio: user.bio,
-                    avatar_url: user.avatar_url,
-                    is_verified: user.is_verified.unwrap_or(false),
-                    follower_count: counts.follower_count,
-                    following_count: counts.following_count,
-                    created_at: user.created_at.unwrap(),
-                },
-                Err(_) => UserResponse {
-                    id: user.id,
-                    username: user.username,
-                    email: user.email,
-                    full_name: user.full_name,
-                    bio: user.bio,
-                    avatar_url: user.avatar_url,
-                    is_verified: user.is_verified.unwrap_or(false),
-                    follower_count: 0,
-                    following_count: 0,
-                    created_at: user.created_at.unwrap(),
-                },
-            };
-
-            Ok(HttpResponse::Ok().json(ApiResponse::success(user_response)))
-        }
-        Err(e) => {
-            log::error!("Failed to update user: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to update profile".to_string(),
-            )))
+            .await?;
+        Some(count)
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(UserSummaryResponse {
+        scheduled_post_count,
+        pending_report_count,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::{header, StatusCode};
+    use actix_web::{test, App};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn summary_counts_open_reports_for_moderators_only(pool: PgPool) {
+        let config = test_support::config();
+        let user_id = test_support::insert_user(&pool, "reader", "").await;
+        let moderator_id = test_support::insert_user(&pool, "moderator", "").await;
+        sqlx::query!("UPDATE users SET role = 'moderator' WHERE id = $1", moderator_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let post_id = test_support::insert_published_post(&pool, moderator_id, "Reported", Utc::now()).await;
+        for (reporter_id, status) in [(user_id, "open"), (moderator_id, "dismissed")] {
+            sqlx::query!(
+                "INSERT INTO reports (reporter_id, target_type, target_id, reason, status) VALUES ($1, 'post', $2, 'spam', $3)",
+                reporter_id,
+                post_id,
+                status
+            )
+            .execute(&pool)
+            .await
+            .unwrap();
         }
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(config.clone())
+                .route("/users/me/summary", web::get().to(get_my_summary)),
+        )
+        .await;
+        let summary = |user_id| {
+            test::TestRequest::get()
+                .uri("/users/me/summary")
+                .insert_header(("Authorization", test_support::bearer(&config, user_id)))
+                .to_request()
+        };
+
+        let body: Value = test::call_and_read_body_json(&app, summary(user_id)).await;
+        assert!(body["data"].get("pending_report_count").is_none());
+
+        let body: Value = test::call_and_read_body_json(&app, summary(moderator_id)).await;
+        assert_eq!(body["data"]["pending_report_count"], 1);
     }
-}
\ No newline at end of file
+
+    #[sqlx::test]
+    async fn profile_validators_change_with_follower_counts(pool: PgPool) {
+        let config = test_support::config();
+        let user_id = test_support::insert_user(&pool, "writer", "").await;
+        let follower_id = test_support::insert_user(&pool, "fan", "").await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(config)
+                .route("/users/{user_id}", web::get().to(get_user)),
+        )
+        .await;
+        let uri = format!("/users/{}", user_id);
+
+        let resp = test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let headers = resp.headers();
+        assert_eq!(headers.get(header::VARY).unwrap(), "Authorization");
+        assert_eq!(headers.get(header::CACHE_CONTROL).unwrap(), "private");
+        assert!(headers.get(header::LAST_MODIFIED).is_none());
+        let etag = headers.get(header::ETAG).unwrap().clone();
+
+        let req = test::TestRequest::get().uri(&uri).insert_header((header::IF_NONE_MATCH, etag.clone())).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(header::VARY).unwrap(), "Authorization");
+
+        sqlx::query!("INSERT INTO follows (follower_id, following_id) VALUES ($1, $2)", follower_id, user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get().uri(&uri).insert_header((header::IF_NONE_MATCH, etag)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // Without a Last-Modified to compare against, the date can't vouch for the counts
+        let req = test::TestRequest::get()
+            .uri(&uri)
+            .insert_header((header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"]["follower_count"], 1);
+    }
+}
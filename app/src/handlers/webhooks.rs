@@ -0,0 +1,167 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::error::AppError;
+use crate::middleware::auth::{require_role, AuthenticatedUser};
+use crate::models::{
+    ApiResponse, CreateWebhookRequest, CreatedWebhookResponse, PaginatedResponse, PaginationParams, Role, Webhook,
+    WebhookDelivery,
+};
+use crate::utils::pagination::Page;
+use crate::utils::tokens::generate_token;
+
+const SECRET_PREFIX: &str = "whsec_";
+const MAX_WEBHOOKS_PER_USER: i64 = 10;
+
+pub async fn create_webhook(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<CreateWebhookRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+    if req.site_wide {
+        require_role(&pool, user_id, Role::Admin).await?;
+    }
+
+    let webhook_count = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM webhooks WHERE user_id = $1"#,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if webhook_count >= MAX_WEBHOOKS_PER_USER {
+        return Err(AppError::BadRequest(format!(
+            "You can have at most {} webhooks; delete one first",
+            MAX_WEBHOOKS_PER_USER
+        )));
+    }
+
+    let mut event_types = req.event_types.clone();
+    event_types.sort();
+    event_types.dedup();
+
+    let secret = format!("{}{}", SECRET_PREFIX, generate_token());
+    let webhook = sqlx::query_as!(
+        Webhook,
+        r#"
+        INSERT INTO webhooks (user_id, url, secret, event_types, site_wide)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, url, event_types, site_wide, created_at
+        "#,
+        user_id,
+        req.url.trim(),
+        secret,
+        &event_types,
+        req.site_wide
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(CreatedWebhookResponse { webhook, secret })))
+}
+
+pub async fn get_webhooks(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let webhooks = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT id, url, event_types, site_wide, created_at
+        FROM webhooks
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(webhooks)))
+}
+
+// Pending deliveries are dropped along with the webhook
+pub async fn delete_webhook(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse, AppError> {
+    let webhook_id = path.into_inner();
+
+    let result = sqlx::query!(
+        "DELETE FROM webhooks WHERE id = $1 AND user_id = $2",
+        webhook_id,
+        user_id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Webhook deleted")))
+}
+
+// Newest first, including deliveries still being retried
+pub async fn get_webhook_deliveries(
+    pool: web::Data<PgPool>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    path: web::Path<Uuid>,
+    query: web::Query<PaginationParams>,
+) -> Result<HttpResponse, AppError> {
+    let webhook_id = path.into_inner();
+    let pagination = query.into_inner();
+    let Page { page, limit, offset } = Page::new(pagination.page, pagination.limit);
+
+    let owned = sqlx::query_scalar!(
+        r#"SELECT EXISTS (SELECT 1 FROM webhooks WHERE id = $1 AND user_id = $2) AS "exists!""#,
+        webhook_id,
+        user_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    if !owned {
+        return Err(AppError::NotFound("Webhook not found".to_string()));
+    }
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM webhook_deliveries WHERE webhook_id = $1"#,
+        webhook_id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let deliveries = sqlx::query_as!(
+        WebhookDelivery,
+        r#"
+        SELECT id, event_id, event_type, payload, status, attempts, response_status, last_error,
+               next_attempt_at, delivered_at, created_at
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+        ORDER BY created_at DESC, id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+        webhook_id,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let total_pages = (total as f64 / limit as f64).ceil() as u32;
+
+    let paginated_response = PaginatedResponse {
+        data: deliveries,
+        total,
+        page,
+        limit,
+        total_pages,
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(paginated_response)))
+}
@@ -0,0 +1,175 @@
+use actix_web::web::Bytes;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageReader, Limits};
+use sqlx::PgPool;
+use std::io::Cursor;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::storage::Storage;
+
+// Images larger than this in either dimension are not resized
+const MAX_SOURCE_DIMENSION: u32 = 12_000;
+const JPEG_QUALITY: u8 = 82;
+
+/// How an image is fitted into a preset's bounds.
+#[derive(Debug, Clone, Copy)]
+pub enum Fit {
+    /// Scaled down to fit within the bounds, keeping its aspect ratio.
+    /// Smaller images are left at their size.
+    Contain,
+    /// Scaled and center-cropped to fill the bounds exactly.
+    Cover,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Preset {
+    pub name: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub fit: Fit,
+}
+
+/// Variants generated for every uploaded image.
+pub const MEDIA_PRESETS: &[Preset] = &[
+    Preset { name: "thumbnail", width: 320, height: 320, fit: Fit::Cover },
+    Preset { name: "feed", width: 800, height: 800, fit: Fit::Contain },
+    Preset { name: "full", width: 1920, height: 1920, fit: Fit::Contain },
+];
+
+/// Sizes avatars are cropped to. The first is stored as the avatar itself,
+/// the rest as its variants.
+pub const AVATAR_PRESETS: &[Preset] = &[
+    Preset { name: "large", width: 512, height: 512, fit: Fit::Cover },
+    Preset { name: "medium", width: 128, height: 128, fit: Fit::Cover },
+    Preset { name: "small", width: 48, height: 48, fit: Fit::Cover },
+];
+
+/// A resized, re-encoded image. Images with transparency are written as PNG,
+/// everything else as JPEG.
+pub struct Rendered {
+    pub data: Bytes,
+    pub content_type: &'static str,
+    pub extension: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Decodes an uploaded image, applying any EXIF orientation. Animated GIFs
+/// decode to their first frame.
+pub fn decode(data: &[u8]) -> Result<DynamicImage, String> {
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| e.to_string())?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(|e| e.to_string())?;
+    let orientation = decoder.orientation().map_err(|e| e.to_string())?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(|e| e.to_string())?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Resizes `image` to `preset` and encodes the result. CPU-bound, so call it
+/// from a blocking task.
+pub fn render(image: &DynamicImage, preset: &Preset) -> Result<Rendered, String> {
+    let resized = match preset.fit {
+        Fit::Contain if image.width() <= preset.width && image.height() <= preset.height => image.clone(),
+        Fit::Contain => image.resize(preset.width, preset.height, FilterType::Lanczos3),
+        Fit::Cover => image.resize_to_fill(preset.width, preset.height, FilterType::Lanczos3),
+    };
+
+    let mut buffer = Vec::new();
+    let (content_type, extension) = if resized.color().has_alpha() {
+        resized
+            .write_with_encoder(PngEncoder::new(&mut buffer))
+            .map_err(|e| e.to_string())?;
+        ("image/png", "png")
+    } else {
+        DynamicImage::ImageRgb8(resized.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, JPEG_QUALITY))
+            .map_err(|e| e.to_string())?;
+        ("image/jpeg", "jpg")
+    };
+
+    Ok(Rendered {
+        data: Bytes::from(buffer),
+        content_type,
+        extension,
+        width: resized.width(),
+        height: resized.height(),
+    })
+}
+
+/// Renders `presets` from `data` off the async runtime.
+pub async fn render_presets(data: Bytes, presets: &'static [Preset]) -> Result<Vec<(&'static Preset, Rendered)>, String> {
+    tokio::task::spawn_blocking(move || {
+        let image = decode(&data)?;
+        presets
+            .iter()
+            .map(|preset| render(&image, preset).map(|rendered| (preset, rendered)))
+            .collect()
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Generates the `MEDIA_PRESETS` variants of an upload and records them in
+/// `media_variants`, storing each next to the upload's `key`. Run in the
+/// background after the upload is stored; failures are logged and leave the
+/// upload without variants.
+pub fn spawn_variant_generation(pool: PgPool, storage: Arc<dyn Storage>, media_id: Uuid, key: String, data: Bytes) {
+    tokio::spawn(async move {
+        if let Err(e) = generate_variants(&pool, storage.as_ref(), media_id, &key, data).await {
+            log::error!("Failed to generate variants for media {}: {}", media_id, e);
+        }
+    });
+}
+
+async fn generate_variants(
+    pool: &PgPool,
+    storage: &dyn Storage,
+    media_id: Uuid,
+    source_key: &str,
+    data: Bytes,
+) -> Result<(), String> {
+    let stem = source_key.rsplit_once('.').map_or(source_key, |(stem, _)| stem);
+
+    for (preset, rendered) in render_presets(data, MEDIA_PRESETS).await? {
+        let key = format!("{}_{}.{}", stem, preset.name, rendered.extension);
+        let size_bytes = rendered.data.len() as i64;
+        storage.put(&key, rendered.content_type, rendered.data).await?;
+
+        // The upload may have been deleted while its variants were rendering
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO media_variants (media_id, name, storage_key, content_type, width, height, size_bytes)
+            SELECT $1, $2, $3, $4, $5, $6, $7
+            WHERE EXISTS (SELECT 1 FROM media WHERE id = $1)
+            "#,
+            media_id,
+            preset.name,
+            key,
+            rendered.content_type,
+            rendered.width as i32,
+            rendered.height as i32,
+            size_bytes
+        )
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        if inserted.rows_affected() == 0 {
+            storage.delete(&key).await?;
+            return Ok(());
+        }
+    }
+
+    Ok(())
+}
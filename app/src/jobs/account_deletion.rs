@@ -0,0 +1,84 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const PURGE_INTERVAL_SECS: u64 = 3600;
+
+// Always on: users were promised their data goes once the grace period ends
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(PURGE_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match purge_deleted_accounts(&pool).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Purged {} deleted accounts", count),
+                Err(e) => log::error!("Account deletion failed: {:?}", e),
+            }
+        }
+    });
+}
+
+async fn purge_deleted_accounts(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let due = sqlx::query_scalar!(
+        "SELECT id FROM users WHERE deletion_scheduled_at <= $1",
+        Utc::now()
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut purged = 0;
+    for user_id in due {
+        if purge_account(pool, user_id).await? {
+            purged += 1;
+        }
+    }
+
+    Ok(purged)
+}
+
+// Each account is purged in its own transaction. The row lock skips accounts
+// another replica is already purging, and the deadline is re-checked in case
+// the user reactivated in the meantime.
+async fn purge_account(pool: &PgPool, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let locked = sqlx::query_scalar!(
+        "SELECT id FROM users WHERE id = $1 AND deletion_scheduled_at <= $2 FOR UPDATE SKIP LOCKED",
+        user_id,
+        Utc::now()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if locked.is_none() {
+        return Ok(false);
+    }
+
+    sqlx::query!("DELETE FROM likes WHERE user_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!(
+        "DELETE FROM follows WHERE follower_id = $1 OR following_id = $1",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM comments WHERE author_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query!("DELETE FROM posts WHERE author_id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+    // Tokens, keys and everything else owned by the user cascade
+    sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    log::info!("Purged account {}", user_id);
+
+    Ok(true)
+}
@@ -0,0 +1,35 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+// How long a deleted post can still be restored before it is purged
+pub const DELETED_POST_RETENTION_DAYS: i64 = 30;
+
+const PURGE_INTERVAL_SECS: u64 = 3600;
+
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(PURGE_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match purge_deleted_posts(&pool).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Purged {} posts deleted over {} days ago", count, DELETED_POST_RETENTION_DAYS),
+                Err(e) => log::error!("Deleted post purge failed: {:?}", e),
+            }
+        }
+    });
+}
+
+// Comments, likes, tags and views go with the post through ON DELETE CASCADE
+async fn purge_deleted_posts(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM posts WHERE deleted_at < $1",
+        Utc::now() - Duration::days(DELETED_POST_RETENTION_DAYS)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
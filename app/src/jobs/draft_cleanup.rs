@@ -0,0 +1,223 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::env;
+use uuid::Uuid;
+
+use crate::events::{emit, DomainEvent};
+
+pub struct DraftCleanupConfig {
+    pub enabled: bool,
+    pub max_age_days: i64,
+    // How long before the cleanup the author is warned
+    pub warning_days: i64,
+    pub interval_hours: u64,
+}
+
+impl DraftCleanupConfig {
+    pub fn from_env() -> Self {
+        let enabled = env::var("DRAFT_CLEANUP_ENABLED")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+        let max_age_days = env::var("DRAFT_CLEANUP_AFTER_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|days| *days > 0)
+            .unwrap_or(180);
+        let warning_days = env::var("DRAFT_CLEANUP_WARNING_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|days| *days > 0)
+            .unwrap_or(14)
+            .min(max_age_days);
+        let interval_hours = env::var("DRAFT_CLEANUP_INTERVAL_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|hours| *hours > 0)
+            .unwrap_or(24);
+
+        Self {
+            enabled,
+            max_age_days,
+            warning_days,
+            interval_hours,
+        }
+    }
+}
+
+// Runs the cleanup on startup and then every `interval_hours` until the
+// server shuts down
+pub fn spawn(pool: PgPool, config: DraftCleanupConfig) {
+    tokio::spawn(async move {
+        let mut interval =
+            tokio::time::interval(std::time::Duration::from_secs(config.interval_hours * 3600));
+
+        loop {
+            interval.tick().await;
+
+            match warn_expiring_drafts(&pool, config.max_age_days, config.warning_days).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Warned the authors of {} drafts about to be cleaned up", count),
+                Err(e) => log::error!("Draft cleanup warnings failed: {:?}", e),
+            }
+
+            match delete_stale_drafts(&pool, config.max_age_days, config.warning_days).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Soft-deleted {} drafts untouched for {} days", count, config.max_age_days),
+                Err(e) => log::error!("Draft cleanup failed: {:?}", e),
+            }
+        }
+    });
+}
+
+// Queues a warning for each draft `warning_days` away from the cleanup whose
+// author hasn't been warned since its last edit. The author's notification
+// settings decide how, or whether, it reaches them. Scheduled drafts are
+// kept since they are still meant to go live.
+async fn warn_expiring_drafts(pool: &PgPool, max_age_days: i64, warning_days: i64) -> Result<usize, sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    let drafts = sqlx::query!(
+        r#"
+        SELECT id, author_id, updated_at as "updated_at!"
+        FROM posts
+        WHERE is_published = false AND deleted_at IS NULL AND publish_at IS NULL
+          AND updated_at < $1
+          AND (cleanup_warned_at IS NULL OR cleanup_warned_at < updated_at)
+        FOR UPDATE SKIP LOCKED
+        "#,
+        now - Duration::days(max_age_days - warning_days)
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for draft in &drafts {
+        let deletes_at = (draft.updated_at + Duration::days(max_age_days)).max(now + Duration::days(warning_days));
+        let event = DomainEvent::DraftExpiring {
+            post_id: draft.id,
+            author_id: draft.author_id,
+            deletes_at,
+        };
+        emit(&mut tx, &event).await?;
+    }
+
+    let post_ids: Vec<Uuid> = drafts.iter().map(|draft| draft.id).collect();
+    sqlx::query!(
+        "UPDATE posts SET cleanup_warned_at = $2 WHERE id = ANY($1)",
+        &post_ids,
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(drafts.len())
+}
+
+// Only drafts whose author was warned at least `warning_days` ago, and
+// hasn't edited them since, are removed
+async fn delete_stale_drafts(pool: &PgPool, max_age_days: i64, warning_days: i64) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = sqlx::query!(
+        r#"
+        UPDATE posts SET deleted_at = $1
+        WHERE is_published = false AND deleted_at IS NULL AND publish_at IS NULL
+          AND updated_at < $2
+          AND cleanup_warned_at >= updated_at AND cleanup_warned_at < $3
+        "#,
+        now,
+        now - Duration::days(max_age_days),
+        now - Duration::days(warning_days)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    async fn insert_draft(pool: &PgPool, author_id: Uuid, days_untouched: i64) -> Uuid {
+        let updated_at = Utc::now() - Duration::days(days_untouched);
+        sqlx::query_scalar!(
+            r#"
+            INSERT INTO posts (id, title, slug, content, author_id, is_published, created_at, updated_at)
+            VALUES ($1, 'Draft', $2, 'Unfinished', $3, false, $4, $4)
+            RETURNING id
+            "#,
+            Uuid::new_v4(),
+            format!("draft-{}", Uuid::new_v4().simple()),
+            author_id,
+            updated_at
+        )
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn is_deleted(pool: &PgPool, post_id: Uuid) -> bool {
+        sqlx::query_scalar!(r#"SELECT deleted_at IS NOT NULL as "deleted!" FROM posts WHERE id = $1"#, post_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn drafts_are_only_deleted_well_after_warning_their_author(pool: PgPool) {
+        let author_id = test_support::insert_user(&pool, "drafter", "").await;
+        let expiring = insert_draft(&pool, author_id, 170).await;
+        let abandoned = insert_draft(&pool, author_id, 400).await;
+        let recent = insert_draft(&pool, author_id, 10).await;
+
+        // Nobody has been warned yet, however old the draft
+        assert_eq!(delete_stale_drafts(&pool, 180, 14).await.unwrap(), 0);
+
+        assert_eq!(warn_expiring_drafts(&pool, 180, 14).await.unwrap(), 2);
+        assert_eq!(warn_expiring_drafts(&pool, 180, 14).await.unwrap(), 0);
+        let warnings = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) as "count!" FROM outbox_events WHERE event_type = 'post.draft_expiring'"#
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(warnings, 2);
+
+        // Warned, but not long enough ago
+        assert_eq!(delete_stale_drafts(&pool, 180, 14).await.unwrap(), 0);
+
+        sqlx::query!(
+            "UPDATE posts SET cleanup_warned_at = $2 WHERE id = ANY($1)",
+            &[expiring, abandoned][..],
+            Utc::now() - Duration::days(15)
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        assert_eq!(delete_stale_drafts(&pool, 180, 14).await.unwrap(), 1);
+        assert!(is_deleted(&pool, abandoned).await);
+        assert!(!is_deleted(&pool, expiring).await);
+        assert!(!is_deleted(&pool, recent).await);
+    }
+
+    #[sqlx::test]
+    async fn editing_a_draft_after_its_warning_needs_a_new_one(pool: PgPool) {
+        let author_id = test_support::insert_user(&pool, "drafter", "").await;
+        let draft = insert_draft(&pool, author_id, 300).await;
+        // Warned long ago, then edited, then left alone again
+        sqlx::query!(
+            "UPDATE posts SET cleanup_warned_at = $2 WHERE id = $1",
+            draft,
+            Utc::now() - Duration::days(400)
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        assert_eq!(delete_stale_drafts(&pool, 180, 14).await.unwrap(), 0);
+        assert_eq!(warn_expiring_drafts(&pool, 180, 14).await.unwrap(), 1);
+        assert!(!is_deleted(&pool, draft).await);
+    }
+}
@@ -0,0 +1,151 @@
+use actix_web::web;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::mailer::{Email, Mailer};
+use crate::models::DigestFrequency;
+use crate::notifications::settings_footer;
+
+const CHECK_INTERVAL_SECS: u64 = 3600;
+const BATCH_SIZE: i64 = 100;
+// Posts listed in one digest, newest first
+const MAX_DIGEST_POSTS: i64 = 20;
+
+// Emails daily and weekly digests of followed authors' new posts as they
+// fall due, checking hourly
+pub fn spawn(pool: PgPool, mailer: web::Data<dyn Mailer>, app_base_url: String) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(CHECK_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match send_due_digests(&pool, mailer.get_ref(), &app_base_url).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Sent {} email digests", count),
+                Err(e) => log::error!("Email digests failed: {:?}", e),
+            }
+        }
+    });
+}
+
+async fn send_due_digests(pool: &PgPool, mailer: &dyn Mailer, app_base_url: &str) -> Result<usize, sqlx::Error> {
+    let mut sent = 0;
+
+    loop {
+        let due = sqlx::query!(
+            r#"
+            SELECT s.user_id, s.digest_frequency, s.last_digest_at, u.email
+            FROM notification_settings s
+            INNER JOIN users u ON s.user_id = u.id
+            WHERE s.digest_frequency <> 'off'
+              AND u.is_verified = true AND u.deactivated_at IS NULL AND u.banned_at IS NULL
+              AND (s.last_digest_at IS NULL
+                   OR s.last_digest_at <= NOW() - CASE s.digest_frequency WHEN 'weekly' THEN INTERVAL '7 days' ELSE INTERVAL '1 day' END)
+            ORDER BY s.last_digest_at NULLS FIRST
+            LIMIT $1
+            "#,
+            BATCH_SIZE
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let claimed = due.len();
+        for user in due {
+            // Claimed by moving last_digest_at on, so no other pod sends it
+            // too. Rounded to the hour so the send time doesn't drift later
+            // each period.
+            let result = sqlx::query!(
+                r#"
+                UPDATE notification_settings SET last_digest_at = date_trunc('hour', NOW())
+                WHERE user_id = $1 AND last_digest_at IS NOT DISTINCT FROM $2
+                "#,
+                user.user_id,
+                user.last_digest_at
+            )
+            .execute(pool)
+            .await?;
+            if result.rows_affected() == 0 {
+                continue;
+            }
+
+            let frequency = DigestFrequency::from_db(&user.digest_frequency);
+            let period = match frequency {
+                DigestFrequency::Weekly => Duration::days(7),
+                _ => Duration::days(1),
+            };
+            let since = user.last_digest_at.unwrap_or_else(|| Utc::now() - period);
+
+            let Some(email) = digest_email(pool, user.user_id, user.email, frequency, since, app_base_url).await? else {
+                continue;
+            };
+
+            if let Err(e) = mailer.send(&email).await {
+                // Most likely the mail server is down; put the user back and
+                // leave the rest for the next check
+                log::warn!("Failed to send email digest to {}: {}", user.user_id, e);
+                sqlx::query!(
+                    "UPDATE notification_settings SET last_digest_at = $2 WHERE user_id = $1",
+                    user.user_id,
+                    user.last_digest_at
+                )
+                .execute(pool)
+                .await?;
+                return Ok(sent);
+            }
+            sent += 1;
+        }
+
+        if (claimed as i64) < BATCH_SIZE {
+            return Ok(sent);
+        }
+    }
+}
+
+// None when nothing was published since the last digest
+async fn digest_email(
+    pool: &PgPool,
+    user_id: Uuid,
+    to: String,
+    frequency: DigestFrequency,
+    since: DateTime<Utc>,
+    app_base_url: &str,
+) -> Result<Option<Email>, sqlx::Error> {
+    let posts = sqlx::query!(
+        r#"
+        SELECT p.title, p.slug, u.username
+        FROM posts p
+        INNER JOIN follows f ON f.following_id = p.author_id
+        INNER JOIN users u ON p.author_id = u.id
+        WHERE f.follower_id = $1 AND p.is_published = true AND p.deleted_at IS NULL
+          AND u.deactivated_at IS NULL AND p.published_at > $2
+        ORDER BY p.published_at DESC
+        LIMIT $3
+        "#,
+        user_id,
+        since,
+        MAX_DIGEST_POSTS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    if posts.is_empty() {
+        return Ok(None);
+    }
+
+    let listing: Vec<String> = posts
+        .iter()
+        .map(|post| format!("- {} by {}\n  {}/posts/{}", post.title, post.username, app_base_url, post.slug))
+        .collect();
+
+    Ok(Some(Email {
+        to,
+        subject: format!("Your {} digest", frequency.as_str()),
+        body: format!(
+            "New posts from authors you follow:\n\n{}{}",
+            listing.join("\n\n"),
+            settings_footer(app_base_url)
+        ),
+    }))
+}
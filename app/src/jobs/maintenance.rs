@@ -0,0 +1,23 @@
+use actix_web::web;
+use sqlx::PgPool;
+
+use crate::middleware::maintenance::MaintenanceMode;
+
+// How long another replica can keep serving writes after maintenance mode
+// is switched on
+const REFRESH_INTERVAL_SECS: u64 = 5;
+
+pub fn spawn(pool: PgPool, maintenance: web::Data<MaintenanceMode>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(REFRESH_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            // Keeps the last known state while the database is unreachable
+            if let Err(e) = maintenance.refresh(&pool).await {
+                log::error!("Failed to refresh maintenance mode: {:?}", e);
+            }
+        }
+    });
+}
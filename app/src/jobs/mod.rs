@@ -0,0 +1,11 @@
+pub mod draft_cleanup;
+pub mod account_deletion;
+pub mod post_counters;
+pub mod post_views;
+pub mod scheduled_publishing;
+pub mod deleted_posts;
+pub mod outbox;
+pub mod webhook_deliveries;
+pub mod email_digests;
+pub mod trending;
+pub mod maintenance;
@@ -0,0 +1,56 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::events::{Dispatcher, BATCH_SIZE};
+
+const POLL_INTERVAL_MS: u64 = 1000;
+const PRUNE_INTERVAL_SECS: u64 = 3600;
+// Delivered events are kept this long for troubleshooting
+const DISPATCHED_RETENTION_DAYS: i64 = 7;
+
+// Delivers outbox events as they become due, draining full batches without
+// waiting for the next tick
+pub fn spawn(pool: PgPool, dispatcher: Arc<Dispatcher>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(POLL_INTERVAL_MS));
+        let mut pruned_at = Instant::now();
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match dispatcher.dispatch_batch(&pool).await {
+                    Ok(count) if count as i64 == BATCH_SIZE => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        log::error!("Outbox dispatch failed: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            if pruned_at.elapsed().as_secs() >= PRUNE_INTERVAL_SECS {
+                pruned_at = Instant::now();
+                match prune_dispatched(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Pruned {} dispatched outbox events", count),
+                    Err(e) => log::error!("Outbox prune failed: {:?}", e),
+                }
+            }
+        }
+    });
+}
+
+// Events that were given up on are kept for inspection
+async fn prune_dispatched(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM outbox_events WHERE dispatched_at < $1",
+        Utc::now() - Duration::days(DISPATCHED_RETENTION_DAYS)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,66 @@
+use sqlx::PgPool;
+
+const RECONCILE_INTERVAL_SECS: u64 = 6 * 3600;
+
+// The triggers keep the counters exact; this only repairs drift from rows
+// changed with the triggers disabled, e.g. during a manual restore
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(RECONCILE_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match reconcile_post_counters(&pool).await {
+                Ok(0) => {}
+                Ok(count) => log::warn!("Corrected like/comment counts on {} posts", count),
+                Err(e) => log::error!("Post counter reconciliation failed: {:?}", e),
+            }
+
+            match reconcile_comment_like_counts(&pool).await {
+                Ok(0) => {}
+                Ok(count) => log::warn!("Corrected like counts on {} comments", count),
+                Err(e) => log::error!("Comment counter reconciliation failed: {:?}", e),
+            }
+        }
+    });
+}
+
+async fn reconcile_post_counters(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        WITH actual AS (
+            SELECT p.id,
+                   (SELECT COUNT(*) FROM likes l WHERE l.post_id = p.id) as like_count,
+                   (SELECT COUNT(*) FROM comments c WHERE c.post_id = p.id AND c.deleted_at IS NULL) as comment_count
+            FROM posts p
+        )
+        UPDATE posts p SET like_count = actual.like_count, comment_count = actual.comment_count
+        FROM actual
+        WHERE p.id = actual.id
+          AND (p.like_count <> actual.like_count OR p.comment_count <> actual.comment_count)
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn reconcile_comment_like_counts(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        WITH actual AS (
+            SELECT c.id, (SELECT COUNT(*) FROM comment_likes l WHERE l.comment_id = c.id) as like_count
+            FROM comments c
+        )
+        UPDATE comments c SET like_count = actual.like_count
+        FROM actual
+        WHERE c.id = actual.id AND c.like_count <> actual.like_count
+        "#
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
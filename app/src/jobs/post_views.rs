@@ -0,0 +1,20 @@
+use actix_web::web;
+use sqlx::PgPool;
+
+use crate::views::ViewRecorder;
+
+const FLUSH_INTERVAL_SECS: u64 = 10;
+
+pub fn spawn(pool: PgPool, views: web::Data<ViewRecorder>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(FLUSH_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = views.flush(&pool).await {
+                log::error!("Failed to write post views: {:?}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,57 @@
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::events::{emit, DomainEvent};
+
+const POLL_INTERVAL_SECS: u64 = 30;
+
+// Publishes drafts whose `publish_at` has passed. The single UPDATE claims
+// each post atomically, so running this on every pod is safe.
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(POLL_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            match publish_due_posts(&pool).await {
+                Ok(0) => {}
+                Ok(count) => log::info!("Published {} scheduled posts", count),
+                Err(e) => log::error!("Scheduled publishing failed: {:?}", e),
+            }
+        }
+    });
+}
+
+async fn publish_due_posts(pool: &PgPool) -> Result<usize, sqlx::Error> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+
+    // published_at is the scheduled time rather than when the job ran, so a
+    // late run doesn't reorder the feed
+    let published = sqlx::query!(
+        r#"
+        UPDATE posts SET
+            is_published = true,
+            published_at = publish_at,
+            publish_at = NULL,
+            updated_at = $1
+        WHERE is_published = false AND publish_at <= $1 AND deleted_at IS NULL
+        RETURNING id, author_id
+        "#,
+        now
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for post in &published {
+        let event = DomainEvent::PostPublished {
+            post_id: post.id,
+            author_id: post.author_id,
+        };
+        emit(&mut tx, &event).await?;
+    }
+
+    tx.commit().await?;
+    Ok(published.len())
+}
@@ -0,0 +1,94 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::models::TrendingPeriod;
+
+const REFRESH_INTERVAL_SECS: u64 = 15 * 60;
+
+// Comments weigh twice a like, as in post_engagement_score; views are cheap
+// and plentiful, so they count for much less. These scores also rank
+// `sort=trending` post lists.
+const LIKE_WEIGHT: f64 = 1.0;
+const COMMENT_WEIGHT: f64 = 2.0;
+const VIEW_WEIGHT: f64 = 0.2;
+
+// Rebuilds trending_posts and trending_tags, once at startup and then every
+// REFRESH_INTERVAL_SECS
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(REFRESH_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+
+            for period in TrendingPeriod::ALL {
+                if let Err(e) = refresh(&pool, period, Utc::now()).await {
+                    log::error!("Failed to refresh {} trending scores: {:?}", period.as_str(), e);
+                }
+            }
+        }
+    });
+}
+
+// Each like, comment and view in the period adds its weight, halved for
+// every half-life that has passed since. Tags score the sum of their
+// trending posts.
+async fn refresh(pool: &PgPool, period: TrendingPeriod, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let since = now - Duration::hours(period.hours());
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!("DELETE FROM trending_posts WHERE period = $1", period.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO trending_posts (period, post_id, score, computed_at)
+        SELECT $1::text, activity.post_id,
+               SUM(activity.weight * power(0.5, EXTRACT(EPOCH FROM ($3 - activity.at))::float8 / 3600 / $4::float8)),
+               $3
+        FROM (
+            SELECT post_id, created_at as at, $5::float8 as weight FROM likes WHERE created_at >= $2
+            UNION ALL
+            SELECT post_id, created_at, $6::float8 FROM comments
+            WHERE created_at >= $2 AND deleted_at IS NULL AND hidden_at IS NULL
+            UNION ALL
+            SELECT post_id, viewed_at, $7::float8 FROM post_views WHERE viewed_at >= $2
+        ) activity
+        INNER JOIN posts p ON activity.post_id = p.id
+        WHERE p.is_published = true AND p.deleted_at IS NULL
+          AND p.author_id IN (SELECT id FROM users WHERE deactivated_at IS NULL)
+        GROUP BY activity.post_id
+        "#,
+        period.as_str(),
+        since,
+        now,
+        period.half_life_hours(),
+        LIKE_WEIGHT,
+        COMMENT_WEIGHT,
+        VIEW_WEIGHT
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!("DELETE FROM trending_tags WHERE period = $1", period.as_str())
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO trending_tags (period, tag_id, score, post_count, computed_at)
+        SELECT $1::text, pt.tag_id, SUM(tp.score), COUNT(*), $2
+        FROM trending_posts tp
+        INNER JOIN post_tags pt ON tp.post_id = pt.post_id
+        WHERE tp.period = $1
+        GROUP BY pt.tag_id
+        "#,
+        period.as_str(),
+        now
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
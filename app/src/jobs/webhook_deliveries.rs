@@ -0,0 +1,55 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use std::time::Instant;
+
+use crate::webhooks::{deliver_batch, http_client, BATCH_SIZE};
+
+const POLL_INTERVAL_MS: u64 = 2000;
+const PRUNE_INTERVAL_SECS: u64 = 3600;
+// How far back the delivery log goes
+const DELIVERY_RETENTION_DAYS: i64 = 30;
+
+// Sends webhook deliveries as they become due, draining full batches without
+// waiting for the next tick
+pub fn spawn(pool: PgPool) {
+    tokio::spawn(async move {
+        let client = http_client();
+        let mut interval = tokio::time::interval(std::time::Duration::from_millis(POLL_INTERVAL_MS));
+        let mut pruned_at = Instant::now();
+
+        loop {
+            interval.tick().await;
+
+            loop {
+                match deliver_batch(&pool, &client).await {
+                    Ok(count) if count as i64 == BATCH_SIZE => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        log::error!("Webhook delivery failed: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            if pruned_at.elapsed().as_secs() >= PRUNE_INTERVAL_SECS {
+                pruned_at = Instant::now();
+                match prune_finished(&pool).await {
+                    Ok(0) => {}
+                    Ok(count) => log::info!("Pruned {} old webhook deliveries", count),
+                    Err(e) => log::error!("Webhook delivery prune failed: {:?}", e),
+                }
+            }
+        }
+    });
+}
+
+async fn prune_finished(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM webhook_deliveries WHERE status <> 'pending' AND created_at < $1",
+        Utc::now() - Duration::days(DELIVERY_RETENTION_DAYS)
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::sync::Arc;
+
+/// An outgoing email.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// Delivers transactional email. Registered as `web::Data<dyn Mailer>` so the
+/// transport can be swapped without touching the handlers.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), String>;
+}
+
+/// Picks the transport: SMTP when `SMTP_HOST` is set, otherwise `LogMailer`.
+pub fn from_env() -> Result<Arc<dyn Mailer>, String> {
+    match env::var("SMTP_HOST").ok().filter(|host| !host.trim().is_empty()) {
+        Some(host) => Ok(Arc::new(SmtpMailer::new(host.trim())?)),
+        None => Ok(Arc::new(LogMailer)),
+    }
+}
+
+/// Writes emails to the log instead of sending them; for development and
+/// deployments without an email transport.
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        log::info!(
+            "Email to {}\nSubject: {}\n\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+        Ok(())
+    }
+}
+
+/// Sends plain-text email through an SMTP relay.
+///
+/// `SMTP_TLS` is `starttls` (the default, port 587), `tls` (port 465) or
+/// `none` (port 25, for a relay on a trusted network); `SMTP_PORT` overrides
+/// the port. `SMTP_USERNAME` and `SMTP_PASSWORD` are optional and `MAIL_FROM`
+/// is required, e.g. `Blog <no-reply@example.com>`.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    fn new(host: &str) -> Result<Self, String> {
+        let from = env::var("MAIL_FROM")
+            .map_err(|_| "MAIL_FROM is required when SMTP_HOST is set".to_string())?
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid MAIL_FROM: {}", e))?;
+
+        let tls = env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+        let mut builder = match tls.to_lowercase().as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+            other => return Err(format!("invalid SMTP_TLS '{}': expected starttls, tls or none", other)),
+        }
+        .map_err(|e| format!("invalid SMTP_HOST: {}", e))?;
+
+        if let Ok(port) = env::var("SMTP_PORT") {
+            let port = port.parse().map_err(|_| format!("invalid SMTP_PORT '{}'", port))?;
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), String> {
+        let to = email
+            .to
+            .parse::<Mailbox>()
+            .map_err(|e| format!("invalid recipient {}: {}", email.to, e))?;
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&email.subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(email.body.clone())
+            .map_err(|e| e.to_string())?;
+
+        self.transport.send(message).await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use sqlx::PgPool;
+use std::time::Duration;
+
+/// Prometheus metrics for the server, rendered in the text exposition format
+/// by `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_connections: IntGauge,
+    db_connections_idle: IntGauge,
+    posts_created: IntCounter,
+    logins: IntCounterVec,
+}
+
+impl Metrics {
+    pub fn new(max_db_connections: u32) -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route and status"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time spent handling HTTP requests, by route"),
+            &["method", "route"],
+        )
+        .unwrap();
+        let db_connections = IntGauge::new("db_pool_connections", "Open database connections").unwrap();
+        let db_connections_idle =
+            IntGauge::new("db_pool_connections_idle", "Open database connections not in use").unwrap();
+        let db_connections_max =
+            IntGauge::new("db_pool_connections_max", "Maximum database connections the pool may open").unwrap();
+        let posts_created = IntCounter::new("posts_created_total", "Posts created").unwrap();
+        let logins = IntCounterVec::new(
+            Opts::new("logins_total", "Login attempts, by method and outcome"),
+            &["method", "outcome"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(db_connections.clone())).unwrap();
+        registry.register(Box::new(db_connections_idle.clone())).unwrap();
+        registry.register(Box::new(db_connections_max.clone())).unwrap();
+        registry.register(Box::new(posts_created.clone())).unwrap();
+        registry.register(Box::new(logins.clone())).unwrap();
+
+        db_connections_max.set(max_db_connections as i64);
+
+        Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_connections,
+            db_connections_idle,
+            posts_created,
+            logins,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn post_created(&self) {
+        self.posts_created.inc();
+    }
+
+    /// `method` is `password` or the OAuth provider's name.
+    pub fn login(&self, method: &str, succeeded: bool) {
+        let outcome = if succeeded { "success" } else { "failure" };
+        self.logins.with_label_values(&[method, outcome]).inc();
+    }
+
+    // Pool gauges are sampled when scraped rather than tracked on every checkout
+    pub fn render(&self, pool: &PgPool) -> String {
+        self.db_connections.set(pool.size() as i64);
+        self.db_connections_idle.set(pool.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            log::error!("Failed to encode metrics: {:?}", e);
+        }
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -0,0 +1,84 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::error::AppError;
+use crate::middleware::auth::{account_banned, account_suspended};
+use crate::models::Claims;
+use crate::utils::jwt::validate_token;
+
+// The only route a deactivated account may call
+const REACTIVATE_PATH: &str = "/api/v1/users/me/reactivate";
+
+// Tokens are stateless, so revocation, bans, suspensions and deactivation are enforced by
+// checking the account on every authenticated request
+pub async fn reject_deactivated_accounts(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let claims = req.app_data::<web::Data<Config>>().and_then(|config| {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .and_then(|token| validate_token(token, &config.jwt.keys).ok())
+    });
+    let pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+    if let (Some(claims), Some(pool)) = (claims, pool) {
+        let allow_deactivated = req.path() == REACTIVATE_PATH;
+        if let Some(error) = check_token_account(pool.get_ref(), &claims, allow_deactivated).await {
+            return Ok(req.into_response(error.error_response()));
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
+
+/// Why a validly signed token may not be used: revoked, or its account is
+/// banned, suspended or (unless `allow_deactivated`) deactivated. For callers that
+/// authenticate outside `reject_deactivated_accounts`.
+pub async fn check_token_account(pool: &PgPool, claims: &Claims, allow_deactivated: bool) -> Option<AppError> {
+    let user_id = Uuid::parse_str(&claims.sub).ok()?;
+    let account = sqlx::query!(
+        "SELECT deactivated_at, tokens_revoked_at, banned_at, suspended_until FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_optional(pool)
+    .await;
+
+    match account {
+        Ok(Some(account)) => {
+            let revoked = account
+                .tokens_revoked_at
+                .is_some_and(|revoked_at| (claims.iat as i64) < revoked_at.timestamp());
+
+            if revoked {
+                Some(AppError::coded(
+                    StatusCode::UNAUTHORIZED,
+                    "TOKEN_REVOKED",
+                    "This token has been revoked; log in again",
+                ))
+            } else if account.banned_at.is_some() {
+                Some(account_banned())
+            } else if let Some(error) = account_suspended(account.suspended_until) {
+                Some(error)
+            } else if account.deactivated_at.is_some() && !allow_deactivated {
+                Some(AppError::coded(
+                    StatusCode::UNAUTHORIZED,
+                    "ACCOUNT_DEACTIVATED",
+                    "This account is deactivated; reactivate it to continue",
+                ))
+            } else {
+                None
+            }
+        }
+        Ok(None) => None,
+        Err(e) => Some(AppError::from(e)),
+    }
+}
@@ -0,0 +1,71 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage, ResponseError};
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error::AppError;
+use crate::middleware::auth::{account_banned, account_suspended};
+use crate::utils::tokens::hash_token;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+/// The owner of the API key a request was sent with, stored in the request
+/// extensions for the auth extractors to pick up.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiKeyUser(pub Uuid);
+
+// Looking a key up needs the database, which the extractors can't reach
+// synchronously, so keys are resolved here before the handler runs
+pub async fn authenticate_api_key(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let key = req
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_string());
+    let pool = req.app_data::<web::Data<PgPool>>().cloned();
+
+    if let (Some(key), Some(pool)) = (key, pool) {
+        let owner = sqlx::query!(
+            r#"
+            UPDATE api_keys k SET last_used_at = $2
+            FROM users u
+            WHERE k.key_hash = $1 AND u.id = k.user_id
+            RETURNING k.user_id, u.deactivated_at, u.banned_at, u.suspended_until
+            "#,
+            hash_token(&key),
+            Utc::now()
+        )
+        .fetch_optional(pool.get_ref())
+        .await;
+
+        let rejection = match owner {
+            Ok(Some(owner)) if owner.banned_at.is_some() => Some(account_banned()),
+            Ok(Some(owner)) if owner.deactivated_at.is_some() => {
+                Some(AppError::coded(
+                    StatusCode::UNAUTHORIZED,
+                    "ACCOUNT_DEACTIVATED",
+                    "This account is deactivated; reactivate it to continue",
+                ))
+            }
+            Ok(Some(owner)) => account_suspended(owner.suspended_until).or_else(|| {
+                req.extensions_mut().insert(ApiKeyUser(owner.user_id));
+                None
+            }),
+            Ok(None) => Some(AppError::coded(StatusCode::UNAUTHORIZED, "INVALID_API_KEY", "Invalid API key")),
+            Err(e) => Some(AppError::from(e)),
+        };
+
+        if let Some(error) = rejection {
+            return Ok(req.into_response(error.error_response()));
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
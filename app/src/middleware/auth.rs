@@ -1,13 +1,34 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
-use bcrypt::{hash, verify, DEFAULT_COST};
-use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, Header, EncodingKey};
-use sqlx::PgPool;
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{web, FromRequest, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use std::env;
+use std::future::{ready, Ready};
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::models::{ApiResponse, Claims, CreateUserRequest, LoginRequest, User};
-use crate::utils::jwt::{extract_user_id_from_token, JWT_SECRET};
+use crate::audit::{AuditAction, AuditEvent, AuditLogger};
+use crate::config::{Config, JwtConfig};
+use crate::error::AppError;
+use crate::mailer::{Email, Mailer};
+use crate::metrics::Metrics;
+use crate::middleware::api_key::ApiKeyUser;
+use crate::middleware::client_ip::client_ip;
+use crate::middleware::rate_limit::RateLimiter;
+use crate::models::{
+    ApiResponse, AvailabilityParams, AvailabilityResponse, ChangePasswordRequest, Claims, CreateUserRequest,
+    ForgotPasswordRequest, LoginRequest, RefreshTokenRequest, ResetPasswordRequest, Role, User, VerifyEmailParams,
+};
+use crate::telemetry::record_user_id;
+use crate::utils::email_verification::{create_verification_token, VerificationToken};
+use crate::utils::jwt::extract_user_id_from_token;
+use crate::utils::media::serialize_media_url;
+use crate::utils::password::{hash_password, verify_password, PasswordMatch};
+use crate::utils::tokens::{generate_token, hash_token};
+
+pub const DEVICE_ID_HEADER: &str = "X-Device-Id";
 
 #[derive(serde::Serialize)]
 pub struct AuthResponse {
@@ -23,16 +44,38 @@ pub struct AuthUserResponse {
     pub email: String,
     pub full_name: Option<String>,
     pub bio: Option<String>,
+    #[serde(serialize_with = "serialize_media_url")]
     pub avatar_url: Option<String>,
     pub is_verified: bool,
+    pub is_active: bool,
+}
+
+impl From<User> for AuthUserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            bio: user.bio,
+            avatar_url: user.avatar_url,
+            is_verified: user.is_verified.unwrap_or(false),
+            is_active: user.deactivated_at.is_none(),
+        }
+    }
 }
 
 pub fn get_user_id_from_request(req: &HttpRequest) -> Option<Uuid> {
+    if let Some(ApiKeyUser(user_id)) = req.extensions().get::<ApiKeyUser>() {
+        return Some(*user_id);
+    }
+
+    let config = req.app_data::<web::Data<Config>>()?;
     if let Some(auth_header) = req.headers().get("Authorization") {
         if let Ok(auth_str) = auth_header.to_str() {
             if auth_str.starts_with("Bearer ") {
                 let token = &auth_str["Bearer ".len()..];
-                if let Ok(user_id) = extract_user_id_from_token(token) {
+                if let Ok(user_id) = extract_user_id_from_token(token, &config.jwt.keys) {
                     return Some(user_id);
                 }
             }
@@ -41,40 +84,122 @@ pub fn get_user_id_from_request(req: &HttpRequest) -> Option<Uuid> {
     None
 }
 
-pub fn extract_optional_user_id(req: &HttpRequest) -> Option<Uuid> {
-    if let Some(auth_header) = req.headers().get("Authorization") {
-        if let Ok(auth_str) = auth_header.to_str() {
-            if auth_str.starts_with("Bearer ") {
-                let token = &auth_str["Bearer ".len()..];
-                if let Ok(user_id) = extract_user_id_from_token(token) {
-                    return Some(user_id);
-                }
-            }
+/// The id of the authenticated caller. Declaring it as a handler argument
+/// rejects requests without a valid bearer token or API key with 401.
+#[derive(Debug, Clone, Copy)]
+pub struct AuthenticatedUser(pub Uuid);
+
+impl FromRequest for AuthenticatedUser {
+    type Error = AppError;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user_id = get_user_id_from_request(req);
+        if let Some(user_id) = user_id {
+            record_user_id(req, user_id);
         }
+        ready(
+            user_id
+                .map(AuthenticatedUser)
+                .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string())),
+        )
     }
-    None
+}
+
+/// The id of the caller when a valid bearer token or API key was sent. Never
+/// rejects, for endpoints that are public but personalise their response.
+#[derive(Debug, Clone, Copy)]
+pub struct MaybeUser(pub Option<Uuid>);
+
+impl FromRequest for MaybeUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let user_id = extract_optional_user_id(req);
+        if let Some(user_id) = user_id {
+            record_user_id(req, user_id);
+        }
+        ready(Ok(MaybeUser(user_id)))
+    }
+}
+
+pub fn extract_optional_user_id(req: &HttpRequest) -> Option<Uuid> {
+    get_user_id_from_request(req)
+}
+
+// Ids in ADMIN_USER_IDS (comma-separated) are admins regardless of their
+// stored role, so a fresh deployment can bootstrap its first admin
+fn is_configured_admin(user_id: Uuid) -> bool {
+    env::var("ADMIN_USER_IDS")
+        .map(|ids| {
+            ids.split(',')
+                .any(|id| Uuid::parse_str(id.trim()).is_ok_and(|id| id == user_id))
+        })
+        .unwrap_or(false)
+}
+
+pub async fn user_role(pool: &PgPool, user_id: Uuid) -> Result<Role, sqlx::Error> {
+    if is_configured_admin(user_id) {
+        return Ok(Role::Admin);
+    }
+
+    let role = sqlx::query_scalar!("SELECT role FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(role.map(|role| Role::from_db(&role)).unwrap_or(Role::User))
+}
+
+pub async fn has_role(pool: &PgPool, user_id: Uuid, role: Role) -> Result<bool, sqlx::Error> {
+    Ok(user_role(pool, user_id).await? >= role)
+}
+
+/// Fails with 403 when the caller's role is below `role`.
+pub async fn require_role(pool: &PgPool, user_id: Uuid, role: Role) -> Result<(), AppError> {
+    if has_role(pool, user_id, role).await? {
+        return Ok(());
+    }
+
+    let message = match role {
+        Role::Admin => "Admin access required",
+        _ => "Moderator access required",
+    };
+    Err(AppError::Forbidden(message.to_string()))
+}
+
+pub fn account_banned() -> AppError {
+    AppError::coded(StatusCode::FORBIDDEN, "ACCOUNT_BANNED", "This account has been banned")
+}
+
+// A suspension lapses on its own once `suspended_until` has passed
+pub fn account_suspended(suspended_until: Option<DateTime<Utc>>) -> Option<AppError> {
+    suspended_until.filter(|until| *until > Utc::now()).map(|until| {
+        AppError::coded(
+            StatusCode::FORBIDDEN,
+            "ACCOUNT_SUSPENDED",
+            format!("This account is suspended until {}", until.to_rfc3339()),
+        )
+    })
 }
 
 pub async fn register(
     pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn Mailer>,
     req: web::Json<CreateUserRequest>,
-) -> Result<HttpResponse> {
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
     // Validate request
-    if let Err(errors) = req.validate() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            format!("Validation error: {:?}", errors),
-        )));
-    }
+    req.validate()?;
 
     let user_id = Uuid::new_v4();
-    let password_hash = match hash(&req.password, DEFAULT_COST) {
-        Ok(hash) => hash,
-        Err(_) => {
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to hash password".to_string(),
-            )));
-        }
-    };
+    let password_hash = hash_password(&req.password)
+        .map_err(|_| AppError::Internal("Failed to hash password".to_string()))?;
+
+    // Everything a new account needs is created in one transaction so a
+    // failure can't leave a half-registered user behind
+    let mut tx = pool.begin().await?;
 
     // Check if user already exists
     let existing_user = sqlx::query!(
@@ -82,31 +207,48 @@ pub async fn register(
         req.email,
         req.username
     )
-    .fetch_optional(pool.get_ref())
-    .await;
+    .fetch_optional(&mut *tx)
+    .await?;
 
-    match existing_user {
-        Ok(Some(_)) => {
-            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
-                "User with this email or username already exists".to_string(),
-            )));
-        }
-        Err(e) => {
-            log::error!("Database error: {:?}", e);
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )));
-        }
-        Ok(None) => {}
+    if existing_user.is_some() {
+        return Err(AppError::Conflict("User with this email or username already exists".to_string()));
     }
 
-    // Insert new user
+    // Insert new user; dropping the transaction on error rolls it back
+    let user = create_user(&mut tx, user_id, &req, &password_hash, &config.default_follow_user_ids).await?;
+
+    let fingerprint = client_fingerprint(&http_req);
+    let (access_token, refresh_token) = issue_tokens(&mut *tx, &config.jwt, &user, Uuid::new_v4(), &fingerprint).await?;
+
+    tx.commit().await?;
+
+    // The account works without verification, so a failed send only gets
+    // logged; the user can ask for another email
+    if let Err(e) = send_verification_email(&config, mailer.get_ref(), user.id, &user.email).await {
+        log::error!("Failed to send verification email: {}", e);
+    }
+
+    let auth_response = AuthResponse {
+        user: user.into(),
+        access_token,
+        refresh_token,
+    };
+    Ok(HttpResponse::Created().json(ApiResponse::success(auth_response)))
+}
+
+async fn create_user(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    req: &CreateUserRequest,
+    password_hash: &str,
+    default_follow_ids: &[Uuid],
+) -> Result<User, sqlx::Error> {
     let user = sqlx::query_as!(
         User,
         r#"
         INSERT INTO users (id, username, email, password_hash, full_name, bio, is_verified, created_at, updated_at)
         VALUES ($1, $2, $3, $4, $5, $6, false, $7, $7)
-        RETURNING id, username, email, password_hash, full_name, bio, avatar_url, is_verified, created_at, updated_at
+        RETURNING id, username, email, password_hash, full_name, bio, avatar_url, is_verified, deactivated_at, role, banned_at, suspended_until, created_at, updated_at
         "#,
         user_id,
         req.username,
@@ -116,137 +258,778 @@ pub async fn register(
         req.bio,
         Utc::now()
     )
-    .fetch_one(pool.get_ref())
-    .await;
+    .fetch_one(&mut **tx)
+    .await?;
+
+    create_default_user_data(tx, &user, default_follow_ids).await?;
+
+    Ok(user)
+}
+
+// Default rows every new account starts with, following the curated
+// `default_follow_ids`. Runs inside the registration transaction, so any
+// error here also undoes the user insert.
+pub async fn create_default_user_data(
+    tx: &mut Transaction<'_, Postgres>,
+    user: &User,
+    default_follow_ids: &[Uuid],
+) -> Result<(), sqlx::Error> {
+    if !default_follow_ids.is_empty() {
+        // Unknown or deactivated accounts are skipped, as is the new user itself
+        sqlx::query!(
+            r#"
+            INSERT INTO follows (id, follower_id, following_id, created_at)
+            SELECT uuid_generate_v4(), $1, u.id, $2
+            FROM users u
+            WHERE u.id = ANY($3) AND u.id <> $1 AND u.deactivated_at IS NULL
+            ON CONFLICT DO NOTHING
+            "#,
+            user.id,
+            Utc::now(),
+            default_follow_ids
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+pub async fn login(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    audit: web::Data<AuditLogger>,
+    metrics: web::Data<Metrics>,
+    req: web::Json<LoginRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user = sqlx::query_as!(
+        User,
+        "SELECT id, username, email, password_hash, full_name, bio, avatar_url, is_verified, deactivated_at, role, banned_at, suspended_until, created_at, updated_at FROM users WHERE email = $1",
+        req.email
+    )
+    .fetch_optional(pool.get_ref())
+    .await?;
 
     match user {
-        Ok(user) => {
-            let tokens = generate_tokens(&user);
-            match tokens {
-                Ok((access_token, refresh_token)) => {
+        Some(user) => {
+            match verify_password(&req.password, &user.password_hash) {
+                Ok(password_match) if password_match != PasswordMatch::Invalid => {
+                    if password_match == PasswordMatch::ValidNeedsRehash {
+                        rehash_password(&pool, user.id, &req.password).await;
+                    }
+
+                    if user.banned_at.is_some() {
+                        return Err(account_banned());
+                    }
+                    if let Some(error) = account_suspended(user.suspended_until) {
+                        return Err(error);
+                    }
+
+                    // Each login starts a new token family
+                    let fingerprint = client_fingerprint(&http_req);
+                    let (access_token, refresh_token) =
+                        issue_tokens(pool.get_ref(), &config.jwt, &user, Uuid::new_v4(), &fingerprint).await?;
+
+                    audit.record(&http_req, AuditEvent::new(AuditAction::Login).actor(user.id)).await;
+                    metrics.login("password", true);
                     let auth_response = AuthResponse {
-                        user: AuthUserResponse {
-                            id: user.id,
-                            username: user.username,
-                            email: user.email,
-                            full_name: user.full_name,
-                            bio: user.bio,
-                            avatar_url: user.avatar_url,
-                            is_verified: user.is_verified.unwrap_or(false),
-                        },
+                        user: user.into(),
                         access_token,
                         refresh_token,
                     };
-                    Ok(HttpResponse::Created().json(ApiResponse::success(auth_response)))
+                    Ok(HttpResponse::Ok().json(ApiResponse::success(auth_response)))
+                }
+                _ => {
+                    audit.record(&http_req, AuditEvent::new(AuditAction::LoginFailed).actor(user.id)).await;
+                    metrics.login("password", false);
+                    Err(AppError::Unauthorized("Invalid credentials".to_string()))
                 }
-                Err(_) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                    "Failed to generate tokens".to_string(),
-                ))),
             }
         }
-        Err(e) => {
-            log::error!("Failed to create user: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Failed to create user".to_string(),
-            )))
+        None => {
+            let event = AuditEvent::new(AuditAction::LoginFailed).metadata(json!({ "email": req.email }));
+            audit.record(&http_req, event).await;
+            metrics.login("password", false);
+            Err(AppError::Unauthorized("Invalid credentials".to_string()))
         }
     }
 }
 
-pub async fn login(
+// Lets the signup form check a username and/or email before submitting.
+// Rate limited per client since it reveals which accounts exist.
+pub async fn check_availability(
     pool: web::Data<PgPool>,
-    req: web::Json<LoginRequest>,
-) -> Result<HttpResponse> {
+    limiter: web::Data<RateLimiter>,
+    query: web::Query<AvailabilityParams>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    if let Some(ip) = client_ip(&http_req) {
+        if let Err(retry_after) = limiter.check(ip) {
+            return Err(AppError::RetryLater {
+                status: StatusCode::TOO_MANY_REQUESTS,
+                code: "RATE_LIMITED",
+                message: "Too many availability checks; try again later".to_string(),
+                retry_after,
+            });
+        }
+    }
+
+    let params = query.into_inner();
+    let username = params.username.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    let email = params.email.as_deref().map(str::trim).filter(|v| !v.is_empty());
+    if username.is_none() && email.is_none() {
+        return Err(AppError::BadRequest("Provide a username or an email to check".to_string()));
+    }
+
+    let taken = sqlx::query!(
+        r#"
+        SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(username) = LOWER($1)) as "username_taken!",
+               EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($2)) as "email_taken!"
+        "#,
+        username,
+        email
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(AvailabilityResponse {
+        username_available: username.map(|_| !taken.username_taken),
+        email_available: email.map(|_| !taken.email_taken),
+    })))
+}
+
+// Rotates a refresh token: the presented token is spent and a new pair is
+// issued in the same family. Presenting a spent token again means a copy
+// leaked, so the whole family is revoked and the user has to log in again.
+pub async fn refresh_token(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: web::Json<RefreshTokenRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let token_hash = hash_token(req.refresh_token.trim());
+
+    let mut tx = pool.begin().await?;
+
+    // Locking the row makes concurrent refreshes with the same token queue up,
+    // so only one of them can rotate it
+    let stored = sqlx::query!(
+        r#"
+        SELECT rt.id, rt.user_id, rt.family_id, rt.expires_at, rt.used_at, rt.revoked_at, rt.created_at,
+               rt.client_fingerprint, u.tokens_revoked_at
+        FROM refresh_tokens rt
+        JOIN users u ON u.id = rt.user_id
+        WHERE rt.token_hash = $1
+        FOR UPDATE OF rt
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let stored = match stored {
+        Some(stored) => stored,
+        None => return Err(invalid_refresh_token()),
+    };
+
+    if stored.revoked_at.is_some() {
+        return Err(invalid_refresh_token());
+    }
+
+    if stored.used_at.is_some() {
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked_at = $2 WHERE family_id = $1 AND revoked_at IS NULL",
+            stored.family_id,
+            Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        log::warn!(
+            "Refresh token reuse detected for user {}; revoked token family {}",
+            stored.user_id,
+            stored.family_id
+        );
+        return Err(AppError::coded(
+            StatusCode::UNAUTHORIZED,
+            "REFRESH_TOKEN_REUSED",
+            "This refresh token has already been used; log in again",
+        ));
+    }
+
+    // A token presented by a different client than it was issued to is
+    // treated as stolen, like a reused one
+    let fingerprint = client_fingerprint(&http_req);
+    if stored.client_fingerprint.as_ref().is_some_and(|bound| *bound != fingerprint) {
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked_at = $2 WHERE family_id = $1 AND revoked_at IS NULL",
+            stored.family_id,
+            Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        log::warn!(
+            "Refresh token for user {} presented by a different client; revoked token family {}",
+            stored.user_id,
+            stored.family_id
+        );
+        return Err(AppError::coded(
+            StatusCode::UNAUTHORIZED,
+            "REFRESH_TOKEN_CLIENT_MISMATCH",
+            "This refresh token was issued to a different client; log in again",
+        ));
+    }
+
+    let revoked_by_account = stored
+        .tokens_revoked_at
+        .is_some_and(|revoked_at| stored.created_at < revoked_at);
+    if stored.expires_at <= Utc::now() || revoked_by_account {
+        return Err(invalid_refresh_token());
+    }
+
+    sqlx::query!(
+        "UPDATE refresh_tokens SET used_at = $2 WHERE id = $1",
+        stored.id,
+        Utc::now()
+    )
+    .execute(&mut *tx)
+    .await?;
+
     let user = sqlx::query_as!(
         User,
-        "SELECT id, username, email, password_hash, full_name, bio, avatar_url, is_verified, created_at, updated_at FROM users WHERE email = $1",
-        req.email
+        "SELECT id, username, email, password_hash, full_name, bio, avatar_url, is_verified, deactivated_at, role, banned_at, suspended_until, created_at, updated_at FROM users WHERE id = $1",
+        stored.user_id
     )
-    .fetch_optional(pool.get_ref())
-    .await;
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if user.banned_at.is_some() {
+        return Err(account_banned());
+    }
+    if let Some(error) = account_suspended(user.suspended_until) {
+        return Err(error);
+    }
+
+    let (access_token, refresh_token) = issue_tokens(&mut *tx, &config.jwt, &user, stored.family_id, &fingerprint).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(AuthResponse {
+        user: user.into(),
+        access_token,
+        refresh_token,
+    })))
+}
+
+fn invalid_refresh_token() -> AppError {
+    AppError::coded(StatusCode::UNAUTHORIZED, "INVALID_REFRESH_TOKEN", "Invalid or expired refresh token")
+}
+
+pub async fn verify_email(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    query: web::Query<VerifyEmailParams>,
+) -> Result<HttpResponse, AppError> {
+    let invalid = || {
+        AppError::coded(
+            StatusCode::BAD_REQUEST,
+            "INVALID_VERIFICATION_TOKEN",
+            "Invalid or expired verification link",
+        )
+    };
+
+    let token = match VerificationToken::parse(&query.token) {
+        Some(token) => token,
+        None => return Err(invalid()),
+    };
+
+    let user = sqlx::query!("SELECT email FROM users WHERE id = $1", token.user_id)
+        .fetch_optional(pool.get_ref())
+        .await?;
 
     match user {
-        Ok(Some(user)) => {
-            match verify(&req.password, &user.password_hash) {
-                Ok(is_valid) if is_valid => {
-                    let tokens = generate_tokens(&user);
-                    match tokens {
-                        Ok((access_token, refresh_token)) => {
-                            let auth_response = AuthResponse {
-                                user: AuthUserResponse {
-                                    id: user.id,
-                                    username: user.username,
-                                    email: user.email,
-                                    full_name: user.full_name,
-                                    bio: user.bio,
-                                    avatar_url: user.avatar_url,
-                                    is_verified: user.is_verified.unwrap_or(false),
-                                },
-                                access_token,
-                                refresh_token,
-                            };
-                            Ok(HttpResponse::Ok().json(ApiResponse::success(auth_response)))
-                        }
-                        Err(_) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                            "Failed to generate tokens".to_string(),
-                        ))),
-                    }
-                }
-                _ => Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                    "Invalid credentials".to_string(),
-                ))),
-            }
+        Some(user) if token.is_valid_for(&config.jwt.secret, &user.email) => {}
+        _ => return Err(invalid()),
+    }
+
+    sqlx::query!(
+        "UPDATE users SET is_verified = true, updated_at = $2 WHERE id = $1",
+        token.user_id,
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Email verified")))
+}
+
+pub async fn resend_verification_email(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn Mailer>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+) -> Result<HttpResponse, AppError> {
+    let user = sqlx::query!("SELECT email, is_verified FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => {
+            return Err(AppError::NotFound("User not found".to_string()));
         }
-        Ok(None) => Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-            "Invalid credentials".to_string(),
-        ))),
+    };
+
+    if user.is_verified.unwrap_or(false) {
+        return Err(AppError::coded(
+            StatusCode::BAD_REQUEST,
+            "ALREADY_VERIFIED",
+            "This email address is already verified",
+        ));
+    }
+
+    match send_verification_email(&config, mailer.get_ref(), user_id, &user.email).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Verification email sent"))),
         Err(e) => {
-            log::error!("Database error: {:?}", e);
-            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Database error".to_string(),
-            )))
+            log::error!("Failed to send verification email: {}", e);
+            Err(AppError::Internal("Failed to send verification email".to_string()))
         }
     }
 }
 
-pub async fn refresh_token(
-    _pool: web::Data<PgPool>,
-    _req: web::Json<serde_json::Value>,
-) -> Result<HttpResponse> {
-    // For now, return a simple response
-    // In a production app, you'd validate the refresh token and generate new tokens
-    Ok(HttpResponse::Ok().json(ApiResponse::<()>::error(
-        "Refresh token functionality not implemented yet".to_string(),
+async fn send_verification_email(config: &Config, mailer: &dyn Mailer, user_id: Uuid, email: &str) -> Result<(), String> {
+    let token = create_verification_token(
+        &config.jwt.secret,
+        user_id,
+        email,
+        Utc::now() + config.email_verification_ttl,
+    );
+
+    mailer.send(&Email {
+        to: email.to_string(),
+        subject: "Verify your email address".to_string(),
+        body: format!(
+            "Confirm this address for your account by opening the link below within {} hours:\n\n{}/verify-email?token={}",
+            config.email_verification_ttl.num_hours(),
+            config.app_base_url,
+            token
+        ),
+    })
+    .await
+}
+
+// Whether the user may publish, given REQUIRE_VERIFIED_EMAIL
+pub async fn can_publish(pool: &PgPool, config: &Config, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    if !config.require_verified_email {
+        return Ok(true);
+    }
+
+    let verified = sqlx::query_scalar!("SELECT is_verified FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(verified.flatten().unwrap_or(false))
+}
+
+// Emails a single-use reset link. The response is the same whether or not
+// the account exists so the endpoint can't be used to probe for emails.
+pub async fn forgot_password(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<dyn Mailer>,
+    req: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let accepted = HttpResponse::Ok().json(ApiResponse::<()>::ok_message(
+        "If an account exists for that email, a reset link has been sent",
+    ));
+
+    let user = sqlx::query!("SELECT id, email FROM users WHERE email = $1", req.email)
+        .fetch_optional(pool.get_ref())
+        .await?;
+
+    let user = match user {
+        Some(user) => user,
+        None => return Ok(accepted),
+    };
+
+    // Requesting a new link invalidates any earlier one
+    let token = generate_token();
+    sqlx::query!(
+        r#"
+        WITH superseded AS (
+            DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL
+        )
+        INSERT INTO password_reset_tokens (user_id, token_hash, expires_at, created_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user.id,
+        hash_token(&token),
+        Utc::now() + config.password_reset_ttl,
+        Utc::now()
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    let email = Email {
+        to: user.email,
+        subject: "Reset your password".to_string(),
+        body: format!(
+            "Someone requested a password reset for your account. Use the link below within {} minutes to choose a new password:\n\n{}/reset-password?token={}\n\nIf you didn't request this, you can ignore this email.",
+            config.password_reset_ttl.num_minutes(),
+            config.app_base_url,
+            token
+        ),
+    };
+    // Reported the same way as success so delivery failures don't reveal the account
+    if let Err(e) = mailer.send(&email).await {
+        log::error!("Failed to send password reset email: {}", e);
+    }
+
+    Ok(accepted)
+}
+
+// Sets a new password with a reset token. Every token issued before the
+// reset is revoked, so other sessions have to log in again.
+pub async fn reset_password(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    req: web::Json<ResetPasswordRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
+
+    let password_hash = hash_password(&req.new_password)
+        .map_err(|_| AppError::Internal("Failed to hash password".to_string()))?;
+
+    let mut tx = pool.begin().await?;
+
+    let reset = sqlx::query!(
+        r#"
+        SELECT id, user_id FROM password_reset_tokens
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > $2
+        FOR UPDATE
+        "#,
+        hash_token(req.token.trim()),
+        Utc::now()
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let reset = match reset {
+        Some(reset) => reset,
+        None => {
+            return Err(AppError::coded(
+                StatusCode::BAD_REQUEST,
+                "INVALID_RESET_TOKEN",
+                "Invalid or expired reset token",
+            ));
+        }
+    };
+
+    apply_password_reset(&mut tx, reset.id, reset.user_id, &password_hash).await?;
+
+    tx.commit().await?;
+
+    audit.record(&http_req, AuditEvent::new(AuditAction::PasswordReset).actor(reset.user_id)).await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message(
+        "Password has been reset; log in with the new password",
     )))
 }
 
-fn generate_tokens(user: &User) -> Result<(String, String), jsonwebtoken::errors::Error> {
-    let access_expiration = Utc::now() + Duration::hours(1);
-    let refresh_expiration = Utc::now() + Duration::days(30);
+pub async fn change_password(
+    pool: web::Data<PgPool>,
+    audit: web::Data<AuditLogger>,
+    AuthenticatedUser(user_id): AuthenticatedUser,
+    req: web::Json<ChangePasswordRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    req.validate()?;
 
-    let access_claims = Claims {
-        sub: user.id.to_string(),
-        username: user.username.clone(),
-        exp: access_expiration.timestamp() as usize,
+    let mut tx = pool.begin().await?;
+
+    let current_hash = sqlx::query_scalar!(
+        "SELECT password_hash FROM users WHERE id = $1 FOR UPDATE",
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let current_hash = match current_hash {
+        Some(hash) => hash,
+        None => {
+            return Err(AppError::NotFound("User not found".to_string()));
+        }
+    };
+
+    match verify_password(&req.current_password, &current_hash) {
+        Ok(password_match) if password_match != PasswordMatch::Invalid => {}
+        _ => {
+            return Err(AppError::coded(
+                StatusCode::BAD_REQUEST,
+                "INVALID_CURRENT_PASSWORD",
+                "Current password is incorrect",
+            ));
+        }
+    }
+
+    let password_hash = hash_password(&req.new_password)
+        .map_err(|_| AppError::Internal("Failed to hash password".to_string()))?;
+
+    apply_password_change(&mut tx, user_id, &password_hash, req.revoke_refresh_tokens).await?;
+
+    tx.commit().await?;
+
+    let event = AuditEvent::new(AuditAction::PasswordChanged)
+        .actor(user_id)
+        .metadata(json!({ "revoked_refresh_tokens": req.revoke_refresh_tokens }));
+    audit.record(&http_req, event).await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::ok_message("Password changed")))
+}
+
+async fn apply_password_change(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+    password_hash: &str,
+    revoke_refresh_tokens: bool,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+
+    sqlx::query!(
+        "UPDATE users SET password_hash = $2, updated_at = $3 WHERE id = $1",
+        user_id,
+        password_hash,
+        now
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    if revoke_refresh_tokens {
+        sqlx::query!(
+            "UPDATE refresh_tokens SET revoked_at = $2 WHERE user_id = $1 AND revoked_at IS NULL",
+            user_id,
+            now
+        )
+        .execute(&mut **tx)
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn apply_password_reset(
+    tx: &mut Transaction<'_, Postgres>,
+    reset_id: Uuid,
+    user_id: Uuid,
+    password_hash: &str,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+
+    sqlx::query!(
+        "UPDATE users SET password_hash = $2, tokens_revoked_at = $3, updated_at = $3 WHERE id = $1",
+        user_id,
+        password_hash,
+        now
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET used_at = $2 WHERE id = $1",
+        reset_id,
+        now
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Upgrades a legacy bcrypt hash, or one created before PASSWORD_PEPPER was
+// set; failures only get logged since the login itself already succeeded
+async fn rehash_password(pool: &PgPool, user_id: Uuid, password: &str) {
+    let password_hash = match hash_password(password) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("Failed to rehash password: {:?}", e);
+            return;
+        }
     };
 
-    let refresh_claims = Claims {
+    let result = sqlx::query!(
+        "UPDATE users SET password_hash = $2, updated_at = $3 WHERE id = $1",
+        user_id,
+        password_hash,
+        Utc::now()
+    )
+    .execute(pool)
+    .await;
+
+    if let Err(e) = result {
+        log::error!("Failed to store rehashed password: {:?}", e);
+    }
+}
+
+/// Identifies the client a refresh token is bound to: its user agent plus the
+/// optional `X-Device-Id` header, hashed so neither is stored.
+pub fn client_fingerprint(req: &HttpRequest) -> String {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    hash_token(&format!("{}\n{}", header("User-Agent"), header(DEVICE_ID_HEADER)))
+}
+
+// A short-lived access token plus an opaque refresh token stored in `family_id`
+// and bound to the requesting client
+pub async fn issue_tokens<'e>(
+    executor: impl PgExecutor<'e>,
+    jwt: &JwtConfig,
+    user: &User,
+    family_id: Uuid,
+    client_fingerprint: &str,
+) -> Result<(String, String), AppError> {
+    let now = Utc::now();
+    let access_claims = Claims {
         sub: user.id.to_string(),
         username: user.username.clone(),
-        exp: refresh_expiration.timestamp() as usize,
+        exp: (now + jwt.access_token_ttl).timestamp() as usize,
+        iat: now.timestamp() as usize,
     };
 
-    let access_token = encode(
-        &Header::default(),
-        &access_claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-    )?;
+    let access_token = jwt.keys.sign(&access_claims).map_err(|e| {
+        log::error!("Failed to generate tokens: {:?}", e);
+        AppError::Internal("Failed to generate tokens".to_string())
+    })?;
 
-    let refresh_token = encode(
-        &Header::default(),
-        &refresh_claims,
-        &EncodingKey::from_secret(JWT_SECRET.as_ref()),
-    )?;
+    let refresh_token = generate_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at, client_fingerprint, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        user.id,
+        family_id,
+        hash_token(&refresh_token),
+        now + jwt.refresh_token_ttl,
+        client_fingerprint,
+        now
+    )
+    .execute(executor)
+    .await?;
 
     Ok((access_token, refresh_token))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test, App};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::mailer::LogMailer;
+    use crate::test_support;
+
+    #[sqlx::test]
+    async fn register_leaves_no_user_behind_when_default_data_fails(pool: PgPool) {
+        let curated_id = test_support::insert_user(&pool, "curated", "").await;
+
+        // Following the default accounts comes after the user insert; make it fail
+        sqlx::query(
+            "CREATE FUNCTION reject_follows() RETURNS trigger AS $$ BEGIN RAISE EXCEPTION 'follows are unavailable'; END $$ LANGUAGE plpgsql",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("CREATE TRIGGER reject_follows BEFORE INSERT ON follows FOR EACH ROW EXECUTE FUNCTION reject_follows()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut config = test_support::settings();
+        config.default_follow_user_ids = vec![curated_id];
+
+        let mailer: Arc<dyn Mailer> = Arc::new(LogMailer);
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(web::Data::new(config))
+                .app_data(web::Data::from(mailer))
+                .route("/register", web::post().to(register)),
+        )
+        .await;
+
+        let register_request = || {
+            test::TestRequest::post()
+                .uri("/register")
+                .set_json(json!({ "username": "newcomer", "email": "newcomer@example.com", "password": "secret12" }))
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, register_request()).await;
+        assert!(resp.status().is_server_error());
+
+        let users = sqlx::query_scalar!(r#"SELECT COUNT(*) as "count!" FROM users WHERE username = 'newcomer'"#)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(users, 0);
+
+        // Nothing is left over to block registering again once it works
+        sqlx::query("DROP TRIGGER reject_follows ON follows").execute(&pool).await.unwrap();
+        let resp = test::call_service(&app, register_request()).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let follows_curated = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM follows f INNER JOIN users u ON f.follower_id = u.id
+                WHERE u.username = 'newcomer' AND f.following_id = $1
+            ) as "exists!"
+            "#,
+            curated_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert!(follows_curated);
+    }
+
+    #[sqlx::test]
+    async fn login_rehashes_a_legacy_password_hash(pool: PgPool) {
+        let legacy_hash = bcrypt::hash("correct horse", 4).unwrap();
+        let user_id = test_support::insert_user(&pool, "legacy", &legacy_hash).await;
+
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(pool.clone()))
+                .app_data(test_support::config())
+                .app_data(web::Data::new(AuditLogger::new(pool.clone())))
+                .app_data(web::Data::new(Metrics::new(1)))
+                .route("/login", web::post().to(login)),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/login")
+            .set_json(json!({ "email": "legacy@example.com", "password": "correct horse" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let stored = sqlx::query_scalar!("SELECT password_hash FROM users WHERE id = $1", user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(stored.starts_with("$argon2id$"));
+        assert_eq!(verify_password("correct horse", &stored).unwrap(), PasswordMatch::Valid);
+    }
+}
@@ -0,0 +1,295 @@
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, HttpRequest};
+use std::env;
+use std::net::IpAddr;
+
+#[derive(Debug, Clone)]
+pub struct ClientIpConfig {
+    pub trusted_proxies: Vec<IpNetwork>,
+    pub use_forwarded_headers: bool,
+}
+
+/// An address range in CIDR notation; a bare address is a single-host range.
+#[derive(Debug, Clone, Copy)]
+pub struct IpNetwork {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpNetwork {
+    fn parse(value: &str) -> Result<Self, String> {
+        let (addr, prefix_len) = match value.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (value, None),
+        };
+        let addr: IpAddr = addr
+            .parse()
+            .map_err(|_| format!("invalid trusted proxy '{}'", value))?;
+        let max_prefix_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len
+                .parse()
+                .ok()
+                .filter(|len| *len <= max_prefix_len)
+                .ok_or_else(|| format!("invalid prefix length in trusted proxy '{}'", value))?,
+            None => max_prefix_len,
+        };
+
+        // Peers are compared in canonical form, so an IPv4-mapped range has
+        // to be stored as the IPv4 range it stands for
+        match addr.to_canonical() {
+            IpAddr::V4(v4) if addr.is_ipv6() && prefix_len >= 96 => Ok(Self {
+                addr: IpAddr::V4(v4),
+                prefix_len: prefix_len - 96,
+            }),
+            _ => Ok(Self { addr, prefix_len }),
+        }
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl ClientIpConfig {
+    /// Reads `TRUSTED_PROXIES` (comma-separated addresses or CIDR ranges) and
+    /// `USE_X_FORWARDED_FOR`, rejecting malformed entries at startup.
+    pub fn from_env() -> Result<Self, String> {
+        let trusted_proxies = env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .map(|item| item.trim())
+            .filter(|item| !item.is_empty())
+            .map(IpNetwork::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        let use_forwarded_headers = env::var("USE_X_FORWARDED_FOR")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+
+        Ok(Self {
+            trusted_proxies,
+            use_forwarded_headers,
+        })
+    }
+
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted_proxies.iter().any(|network| network.contains(ip))
+    }
+
+    /// Resolves the address of the client behind any trusted proxies.
+    ///
+    /// Forwarding headers are only honoured when the socket peer is a trusted
+    /// proxy. The chain is then walked from the nearest hop outwards and the
+    /// first address that isn't a trusted proxy is the client, so entries a
+    /// client prepends to the header itself are ignored.
+    pub fn resolve(&self, peer: Option<IpAddr>, headers: &HeaderMap) -> Option<IpAddr> {
+        let peer = peer?;
+        if !self.use_forwarded_headers || !self.is_trusted(peer) {
+            return Some(peer);
+        }
+
+        let mut client = peer;
+        for hop in forwarded_chain(headers).into_iter().rev() {
+            match hop {
+                Some(ip) => {
+                    client = ip;
+                    if !self.is_trusted(ip) {
+                        break;
+                    }
+                }
+                // Obfuscated or malformed hops can't be followed any further
+                None => break,
+            }
+        }
+
+        Some(client)
+    }
+}
+
+/// The client address for a request, resolved with the app's `ClientIpConfig`
+/// when one is registered.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    match req.app_data::<web::Data<ClientIpConfig>>() {
+        Some(config) => config.resolve(peer, req.headers()),
+        None => peer,
+    }
+}
+
+// Client-to-proxy chain from `Forwarded` (RFC 7239) if present, otherwise
+// from `X-Forwarded-For`, leftmost hop first
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let forwarded: Vec<&str> = headers
+        .get_all("forwarded")
+        .filter_map(|value| value.to_str().ok())
+        .collect();
+
+    if !forwarded.is_empty() {
+        return forwarded
+            .iter()
+            .flat_map(|value| value.split(','))
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+
+    headers
+        .get_all("x-forwarded-for")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(parse_node)
+        .collect()
+}
+
+// Accepts `1.2.3.4`, `1.2.3.4:port`, `2001:db8::1` and `[2001:db8::1]:port`,
+// optionally quoted
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']').and_then(|(ip, _)| ip.parse().ok());
+    }
+
+    node.parse().ok().or_else(|| {
+        node.rsplit_once(':')
+            .and_then(|(ip, _)| ip.parse().ok())
+            .filter(IpAddr::is_ipv4)
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn network(value: &str) -> IpNetwork {
+        IpNetwork::parse(value).unwrap()
+    }
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(HeaderName::from_static(name), HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn config(trusted_proxies: &[&str]) -> ClientIpConfig {
+        ClientIpConfig {
+            trusted_proxies: trusted_proxies.iter().map(|value| network(value)).collect(),
+            use_forwarded_headers: true,
+        }
+    }
+
+    #[test]
+    fn cidr_ranges_match_addresses_inside_them() {
+        assert!(network("10.0.0.0/8").contains(ip("10.255.1.2")));
+        assert!(!network("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(network("192.168.1.7").contains(ip("192.168.1.7")));
+        assert!(!network("192.168.1.7").contains(ip("192.168.1.8")));
+        assert!(network("0.0.0.0/0").contains(ip("203.0.113.9")));
+        assert!(network("2001:db8::/32").contains(ip("2001:db8:ffff::1")));
+        assert!(!network("2001:db8::/32").contains(ip("2001:db9::1")));
+        assert!(!network("10.0.0.0/8").contains(ip("::a00:1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_ipv4_ranges() {
+        assert!(network("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+        assert!(network("::ffff:10.0.0.0/104").contains(ip("10.1.2.3")));
+        assert!(network("::ffff:10.0.0.0/104").contains(ip("::ffff:10.1.2.3")));
+        assert!(!network("::ffff:10.0.0.0/104").contains(ip("11.1.2.3")));
+    }
+
+    #[test]
+    fn malformed_ranges_are_rejected() {
+        assert!(IpNetwork::parse("10.0.0.0/33").is_err());
+        assert!(IpNetwork::parse("2001:db8::/129").is_err());
+        assert!(IpNetwork::parse("10.0.0.0/x").is_err());
+        assert!(IpNetwork::parse("proxy.internal").is_err());
+    }
+
+    #[test]
+    fn untrusted_peers_cannot_spoof_forwarded_for() {
+        let config = config(&["10.0.0.0/8"]);
+        let spoofed = headers(&[("x-forwarded-for", "1.2.3.4")]);
+
+        assert_eq!(config.resolve(Some(ip("203.0.113.9")), &spoofed), Some(ip("203.0.113.9")));
+        assert_eq!(config.resolve(None, &spoofed), None);
+
+        let disabled = ClientIpConfig {
+            use_forwarded_headers: false,
+            ..config
+        };
+        assert_eq!(disabled.resolve(Some(ip("10.0.0.1")), &spoofed), Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn trusted_proxies_are_skipped_from_the_nearest_hop() {
+        let config = config(&["10.0.0.0/8"]);
+
+        // The client prepended 1.2.3.4 itself; the proxy appended its real address
+        let chain = headers(&[("x-forwarded-for", "1.2.3.4, 198.51.100.7, 10.0.0.2")]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &chain), Some(ip("198.51.100.7")));
+
+        let split = headers(&[("x-forwarded-for", "1.2.3.4"), ("x-forwarded-for", "198.51.100.7")]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &split), Some(ip("198.51.100.7")));
+
+        let garbage = headers(&[("x-forwarded-for", "198.51.100.7, not-an-ip")]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &garbage), Some(ip("10.0.0.1")));
+
+        let all_trusted = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &all_trusted), Some(ip("10.0.0.3")));
+    }
+
+    #[test]
+    fn forwarded_takes_precedence_over_x_forwarded_for() {
+        let config = config(&["10.0.0.0/8"]);
+        let both = headers(&[
+            ("x-forwarded-for", "1.2.3.4"),
+            ("forwarded", "for=198.51.100.7;proto=https, For=\"[2001:db8::5]:4711\";by=10.0.0.1"),
+        ]);
+
+        assert_eq!(forwarded_chain(&both), vec![Some(ip("198.51.100.7")), Some(ip("2001:db8::5"))]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &both), Some(ip("2001:db8::5")));
+
+        let obfuscated = headers(&[("forwarded", "for=198.51.100.7, for=_hidden")]);
+        assert_eq!(forwarded_chain(&obfuscated), vec![Some(ip("198.51.100.7")), None]);
+        assert_eq!(config.resolve(Some(ip("10.0.0.1")), &obfuscated), Some(ip("10.0.0.1")));
+    }
+
+    #[test]
+    fn nodes_may_carry_ports_brackets_and_quotes() {
+        assert_eq!(parse_node(" 192.0.2.1 "), Some(ip("192.0.2.1")));
+        assert_eq!(parse_node("192.0.2.1:8080"), Some(ip("192.0.2.1")));
+        assert_eq!(parse_node("\"192.0.2.1:8080\""), Some(ip("192.0.2.1")));
+        assert_eq!(parse_node("2001:db8::1"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("[2001:db8::1]"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("[2001:db8::1]:443"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("\"[2001:db8::1]:443\""), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("[2001:db8::1"), None);
+        assert_eq!(parse_node("unknown"), None);
+        assert_eq!(parse_node("_hidden"), None);
+    }
+}
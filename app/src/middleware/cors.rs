@@ -0,0 +1,104 @@
+use actix_cors::Cors;
+use actix_web::http::header::HeaderName;
+use actix_web::http::Method;
+use std::env;
+use url::Url;
+
+const DEFAULT_ORIGINS: &str = "http://localhost:3000";
+const DEFAULT_METHODS: &str = "GET,POST,PUT,DELETE,PATCH";
+const DEFAULT_HEADERS: &str = "Authorization,Content-Type";
+const DEFAULT_MAX_AGE: usize = 3600;
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allow_any_origin: bool,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    pub max_age: usize,
+}
+
+impl CorsConfig {
+    /// Reads the CORS settings from the environment, rejecting anything
+    /// malformed so a bad deployment fails at startup instead of per request.
+    pub fn from_env() -> Result<Self, String> {
+        let origins = env::var("CORS_ALLOWED_ORIGINS").unwrap_or_else(|_| DEFAULT_ORIGINS.to_string());
+        let methods = env::var("CORS_ALLOWED_METHODS").unwrap_or_else(|_| DEFAULT_METHODS.to_string());
+        let headers = env::var("CORS_ALLOWED_HEADERS").unwrap_or_else(|_| DEFAULT_HEADERS.to_string());
+
+        let mut allowed_origins = Vec::new();
+        let mut allow_any_origin = false;
+        for origin in split_list(&origins) {
+            if origin == "*" {
+                allow_any_origin = true;
+                continue;
+            }
+
+            let url = Url::parse(origin).map_err(|e| format!("invalid origin '{}': {}", origin, e))?;
+            if !matches!(url.scheme(), "http" | "https") || url.path() != "/" || url.query().is_some() {
+                return Err(format!(
+                    "invalid origin '{}': expected scheme://host[:port]",
+                    origin
+                ));
+            }
+            allowed_origins.push(origin.trim_end_matches('/').to_string());
+        }
+
+        if allowed_origins.is_empty() && !allow_any_origin {
+            return Err("CORS_ALLOWED_ORIGINS must list at least one origin".to_string());
+        }
+
+        let allowed_methods = split_list(&methods)
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| format!("invalid method '{}'", method))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let allowed_headers = split_list(&headers)
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|_| format!("invalid header '{}'", header))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let max_age = match env::var("CORS_MAX_AGE") {
+            Ok(value) => value
+                .parse()
+                .map_err(|_| format!("invalid CORS_MAX_AGE '{}'", value))?,
+            Err(_) => DEFAULT_MAX_AGE,
+        };
+
+        Ok(Self {
+            allowed_origins,
+            allow_any_origin,
+            allowed_methods,
+            allowed_headers,
+            max_age,
+        })
+    }
+
+    /// Builds the middleware. Matching origins are echoed back in
+    /// `Access-Control-Allow-Origin` rather than sent as a fixed value.
+    pub fn build(&self) -> Cors {
+        let mut cors = Cors::default()
+            .allowed_methods(self.allowed_methods.clone())
+            .allowed_headers(self.allowed_headers.clone())
+            .max_age(self.max_age)
+            .supports_credentials();
+
+        if self.allow_any_origin {
+            cors = cors.allow_any_origin();
+        } else {
+            for origin in &self.allowed_origins {
+                cors = cors.allowed_origin(origin);
+            }
+        }
+
+        cors
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|item| item.trim()).filter(|item| !item.is_empty())
+}
@@ -0,0 +1,159 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use serde_json::Value;
+use std::env;
+
+use crate::error::{AppError, PROBLEM_JSON};
+
+/// Naming convention for the keys of JSON response bodies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldCase {
+    Snake,
+    Camel,
+}
+
+impl FieldCase {
+    /// Reads `API_FIELD_CASE` (`snake` or `camel`, default `snake`).
+    pub fn from_env() -> Result<Self, String> {
+        match env::var("API_FIELD_CASE") {
+            Err(_) => Ok(Self::Snake),
+            Ok(value) => match value.trim().to_lowercase().as_str() {
+                "" | "snake" => Ok(Self::Snake),
+                "camel" => Ok(Self::Camel),
+                other => Err(format!(
+                    "invalid API_FIELD_CASE '{}', expected one of: snake, camel",
+                    other
+                )),
+            },
+        }
+    }
+}
+
+// Response models are declared in snake_case, so camelCase output is
+// produced by rewriting the keys of JSON bodies on the way out
+pub async fn apply_field_case(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let camel = req
+        .app_data::<web::Data<FieldCase>>()
+        .is_some_and(|case| *case.get_ref() == FieldCase::Camel);
+
+    let res = next.call(req).await?;
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json") || value.starts_with(PROBLEM_JSON));
+
+    if !camel || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        AppError::Internal(e.to_string())
+    })?;
+
+    let body = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(mut value) => {
+            camelize_keys(&mut value);
+            BoxBody::new(value.to_string())
+        }
+        Err(_) => BoxBody::new(bytes),
+    };
+
+    Ok(ServiceResponse::new(req, res.set_body(body)))
+}
+
+// Fields holding stored data rather than more of the response schema: audit
+// metadata, event payloads, image variants keyed by size and notification
+// settings keyed by channel and kind. Their own keys are left as they are.
+const OPAQUE_FIELDS: &[&str] = &["metadata", "payload", "variants", "channels"];
+
+fn camelize_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            *map = std::mem::take(map)
+                .into_iter()
+                .map(|(key, mut value)| {
+                    if !OPAQUE_FIELDS.contains(&key.as_str()) {
+                        camelize_keys(&mut value);
+                    }
+                    (snake_to_camel(&key), value)
+                })
+                .collect();
+        }
+        Value::Array(items) => items.iter_mut().for_each(camelize_keys),
+        _ => {}
+    }
+}
+
+fn snake_to_camel(key: &str) -> String {
+    let mut camel = String::with_capacity(key.len());
+    let mut upper_next = false;
+    for c in key.chars() {
+        if c == '_' && !camel.is_empty() {
+            upper_next = true;
+        } else if upper_next {
+            camel.extend(c.to_uppercase());
+            upper_next = false;
+        } else {
+            camel.push(c);
+        }
+    }
+    camel
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn camelized(mut value: Value) -> Value {
+        camelize_keys(&mut value);
+        value
+    }
+
+    #[test]
+    fn renames_keys_at_every_depth() {
+        assert_eq!(
+            camelized(json!({ "author_id": 1, "author": { "avatar_url": "a" }, "items": [{ "like_count": 2 }] })),
+            json!({ "authorId": 1, "author": { "avatarUrl": "a" }, "items": [{ "likeCount": 2 }] })
+        );
+    }
+
+    #[test]
+    fn leaves_stored_data_alone() {
+        let event = json!({
+            "event_type": "comment.created",
+            "payload": { "type": "comment.created", "data": { "post_author_id": 1 } },
+            "metadata": { "previous_role": "user" },
+            "variants": { "thumb_small": "a.webp" },
+            "channels": { "in_app": { "draft_expiring": true } }
+        });
+        assert_eq!(
+            camelized(event),
+            json!({
+                "eventType": "comment.created",
+                "payload": { "type": "comment.created", "data": { "post_author_id": 1 } },
+                "metadata": { "previous_role": "user" },
+                "variants": { "thumb_small": "a.webp" },
+                "channels": { "in_app": { "draft_expiring": true } }
+            })
+        );
+    }
+
+    #[test]
+    fn converts_snake_case_names() {
+        assert_eq!(snake_to_camel("reading_time_minutes"), "readingTimeMinutes");
+        assert_eq!(snake_to_camel("id"), "id");
+        assert_eq!(snake_to_camel("_private"), "_private");
+    }
+}
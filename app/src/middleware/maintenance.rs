@@ -0,0 +1,158 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use sqlx::PgPool;
+use std::env;
+use std::sync::atomic::{AtomicBool, Ordering};
+use uuid::Uuid;
+
+use crate::error::AppError;
+
+// Write requests to these paths keep working so an admin can still log in,
+// stay logged in and switch maintenance mode back off.
+const EXEMPT_PATHS: &[&str] = &[
+    "/api/v1/auth/login",
+    "/api/v1/auth/refresh",
+    "/api/v1/admin/maintenance",
+];
+
+/// Whether write requests are rejected. The flag lives in the database so
+/// every replica follows it; each keeps a copy that `refresh` brings up to
+/// date.
+pub struct MaintenanceMode {
+    enabled: AtomicBool,
+    retry_after_secs: u64,
+}
+
+impl MaintenanceMode {
+    pub fn from_env() -> Self {
+        let enabled = env::var("MAINTENANCE_MODE")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+            .unwrap_or(false);
+        let retry_after_secs = env::var("MAINTENANCE_RETRY_AFTER")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(300);
+
+        Self {
+            enabled: AtomicBool::new(enabled),
+            retry_after_secs,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Loads the deployment's state. `MAINTENANCE_MODE` only seeds it when no
+    /// replica has recorded one yet; after that the database wins, so a
+    /// restart doesn't undo a switch made through the API.
+    pub async fn load(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO maintenance_mode (id, enabled) VALUES (true, $1) ON CONFLICT (id) DO NOTHING",
+            self.is_enabled()
+        )
+        .execute(pool)
+        .await?;
+
+        self.refresh(pool).await
+    }
+
+    /// Picks up the state another replica may have switched to.
+    pub async fn refresh(&self, pool: &PgPool) -> Result<(), sqlx::Error> {
+        let enabled = sqlx::query_scalar!("SELECT enabled FROM maintenance_mode")
+            .fetch_optional(pool)
+            .await?
+            .unwrap_or(false);
+
+        self.enabled.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Switches maintenance mode for every replica; the others follow on
+    /// their next refresh.
+    pub async fn set_enabled(&self, pool: &PgPool, enabled: bool, updated_by: Option<Uuid>) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO maintenance_mode (id, enabled, updated_by, updated_at)
+            VALUES (true, $1, $2, NOW())
+            ON CONFLICT (id) DO UPDATE SET enabled = EXCLUDED.enabled, updated_by = EXCLUDED.updated_by, updated_at = EXCLUDED.updated_at
+            "#,
+            enabled,
+            updated_by
+        )
+        .execute(pool)
+        .await?;
+
+        self.enabled.store(enabled, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+pub async fn maintenance_guard(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let retry_after = req
+        .app_data::<web::Data<MaintenanceMode>>()
+        .filter(|mode| mode.is_enabled())
+        .map(|mode| mode.retry_after_secs);
+
+    if let Some(retry_after) = retry_after {
+        let is_write = matches!(
+            *req.method(),
+            Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+        );
+        let path = req.path();
+
+        if is_write && path.starts_with("/api/v1") && !EXEMPT_PATHS.contains(&path) {
+            let error = AppError::RetryLater {
+                status: StatusCode::SERVICE_UNAVAILABLE,
+                code: "MAINTENANCE",
+                message: "The service is in maintenance mode; only read requests are accepted".to_string(),
+                retry_after,
+            };
+            return Ok(req.into_response(error.error_response()));
+        }
+    }
+
+    next.call(req).await.map(ServiceResponse::map_into_boxed_body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+
+    fn started_with(enabled: bool) -> MaintenanceMode {
+        MaintenanceMode {
+            enabled: AtomicBool::new(enabled),
+            retry_after_secs: 300,
+        }
+    }
+
+    #[sqlx::test]
+    async fn configured_state_seeds_a_new_deployment(pool: PgPool) {
+        let first = started_with(true);
+        first.load(&pool).await.unwrap();
+        assert!(first.is_enabled());
+
+        let second = started_with(false);
+        second.load(&pool).await.unwrap();
+        assert!(second.is_enabled());
+    }
+
+    #[sqlx::test]
+    async fn restarting_does_not_undo_a_switch_made_through_the_api(pool: PgPool) {
+        let admin_id = test_support::insert_user(&pool, "admin", "").await;
+        let replica = started_with(true);
+        replica.load(&pool).await.unwrap();
+        replica.set_enabled(&pool, false, Some(admin_id)).await.unwrap();
+
+        let restarted = started_with(true);
+        restarted.load(&pool).await.unwrap();
+        assert!(!restarted.is_enabled());
+    }
+}
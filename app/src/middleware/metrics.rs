@@ -0,0 +1,31 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error};
+use std::time::Instant;
+
+use crate::metrics::Metrics;
+
+// Requests are labelled with the route pattern, e.g. `/api/v1/posts/{post_id}`,
+// so ids in the path don't create a new series each
+pub async fn record_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let method = req.method().to_string();
+    let route = req.match_pattern().unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+
+    let res = next.call(req).await;
+
+    if let Some(metrics) = metrics {
+        let status = match &res {
+            Ok(res) => res.status(),
+            Err(e) => e.as_response_error().status_code(),
+        };
+        metrics.observe_request(&method, &route, status.as_u16(), started.elapsed());
+    }
+
+    res
+}
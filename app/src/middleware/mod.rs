@@ -1 +1,10 @@
-pub mod auth;
\ No newline at end of file
+pub mod account_status;
+pub mod api_key;
+pub mod auth;
+pub mod client_ip;
+pub mod cors;
+pub mod field_case;
+pub mod maintenance;
+pub mod metrics;
+pub mod rate_limit;
+pub mod render;
@@ -0,0 +1,343 @@
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, ResponseError};
+use redis::aio::ConnectionManager;
+use redis::Script;
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::error::AppError;
+use crate::middleware::client_ip::client_ip;
+
+// Expired windows are only swept once this many clients are tracked
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Outcome of counting a request against a limit.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitUsage {
+    pub limit: u32,
+    pub remaining: u32,
+    /// Seconds until the window resets
+    pub reset_after: u64,
+    pub allowed: bool,
+}
+
+/// Fixed-window request counter per client address, kept in memory.
+pub struct RateLimiter {
+    max_requests: u32,
+    window: Duration,
+    hits: Mutex<HashMap<IpAddr, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    pub fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            hits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Limiter for `GET /auth/available`, allowing `AVAILABILITY_RATE_LIMIT`
+    /// requests per minute (default 30).
+    pub fn availability_from_env() -> Self {
+        let max_requests = env::var("AVAILABILITY_RATE_LIMIT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|max| *max > 0)
+            .unwrap_or(30);
+
+        Self::new(max_requests, Duration::from_secs(60))
+    }
+
+    /// Records a request, returning the seconds until the client may retry
+    /// when it is over the limit.
+    pub fn check(&self, client: IpAddr) -> Result<(), u64> {
+        let usage = self.hit(client);
+        if usage.allowed {
+            Ok(())
+        } else {
+            Err(usage.reset_after)
+        }
+    }
+
+    /// Records a request and reports how much of the window's budget is left.
+    pub fn hit(&self, client: IpAddr) -> RateLimitUsage {
+        let now = Instant::now();
+        let mut hits = self.hits.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if hits.len() >= PRUNE_THRESHOLD {
+            hits.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        }
+
+        let (started, count) = hits.entry(client).or_insert((now, 0));
+        if now.duration_since(*started) >= self.window {
+            *started = now;
+            *count = 0;
+        }
+
+        let allowed = *count < self.max_requests;
+        if allowed {
+            *count += 1;
+        }
+
+        RateLimitUsage {
+            limit: self.max_requests,
+            remaining: self.max_requests - *count,
+            reset_after: self.window.saturating_sub(now.duration_since(*started)).as_secs().max(1),
+            allowed,
+        }
+    }
+}
+
+const POLICY_WINDOW: Duration = Duration::from_secs(60);
+
+// Counts the request and starts the window on the first one; returns the
+// count and the milliseconds left in the window
+const REDIS_HIT_SCRIPT: &str = r#"
+local count = redis.call('INCR', KEYS[1])
+if count == 1 then
+    redis.call('PEXPIRE', KEYS[1], ARGV[1])
+end
+return {count, redis.call('PTTL', KEYS[1])}
+"#;
+
+/// Per-minute request budgets for the API, applied per client address:
+/// `RATE_LIMIT_AUTH` for writes to `/auth` (login, registration, resets;
+/// default 20), `RATE_LIMIT_WRITE` for other writes (default 120) and
+/// `RATE_LIMIT_READ` for reads (default 600). `0` turns a budget off.
+///
+/// Counts are kept per pod unless `RATE_LIMIT_REDIS_URL` is set, in which
+/// case they are shared by the whole cluster.
+pub struct RateLimitPolicies {
+    auth: Option<RateLimiter>,
+    write: Option<RateLimiter>,
+    read: Option<RateLimiter>,
+    redis: Option<RedisCounter>,
+}
+
+struct RedisCounter {
+    connection: ConnectionManager,
+    script: Script,
+}
+
+impl RateLimitPolicies {
+    pub async fn from_env() -> Result<Self, String> {
+        let budget = |name: &str, default: u32| -> Result<Option<RateLimiter>, String> {
+            let max_requests = match env::var(name) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid {} '{}'", name, value))?,
+                Err(_) => default,
+            };
+            Ok((max_requests > 0).then(|| RateLimiter::new(max_requests, POLICY_WINDOW)))
+        };
+
+        let redis = match env::var("RATE_LIMIT_REDIS_URL").ok().filter(|url| !url.trim().is_empty()) {
+            Some(url) => {
+                let client = redis::Client::open(url.trim()).map_err(|e| format!("invalid RATE_LIMIT_REDIS_URL: {}", e))?;
+                let connection = ConnectionManager::new(client)
+                    .await
+                    .map_err(|e| format!("failed to connect to Redis: {}", e))?;
+                Some(RedisCounter {
+                    connection,
+                    script: Script::new(REDIS_HIT_SCRIPT),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            auth: budget("RATE_LIMIT_AUTH", 20)?,
+            write: budget("RATE_LIMIT_WRITE", 120)?,
+            read: budget("RATE_LIMIT_READ", 600)?,
+            redis,
+        })
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.redis.is_some()
+    }
+
+    fn policy(&self, method: &Method, path: &str) -> Option<(&'static str, &RateLimiter)> {
+        let is_read = matches!(*method, Method::GET | Method::HEAD);
+        if *method == Method::OPTIONS || !path.starts_with("/api/v1/") {
+            return None;
+        }
+
+        if !is_read && path.starts_with("/api/v1/auth/") {
+            self.auth.as_ref().map(|limiter| ("auth", limiter))
+        } else if is_read {
+            self.read.as_ref().map(|limiter| ("read", limiter))
+        } else {
+            self.write.as_ref().map(|limiter| ("write", limiter))
+        }
+    }
+
+    async fn hit(&self, method: &Method, path: &str, client: IpAddr) -> Option<RateLimitUsage> {
+        let (name, limiter) = self.policy(method, path)?;
+
+        let Some(redis) = &self.redis else {
+            return Some(limiter.hit(client));
+        };
+
+        let key = format!("ratelimit:{}:{}", name, client);
+        let result: redis::RedisResult<(u32, i64)> = redis
+            .script
+            .key(key)
+            .arg(limiter.window.as_millis() as u64)
+            .invoke_async(&mut redis.connection.clone())
+            .await;
+
+        match result {
+            Ok((count, ttl_ms)) => Some(RateLimitUsage {
+                limit: limiter.max_requests,
+                remaining: limiter.max_requests.saturating_sub(count),
+                reset_after: (ttl_ms.max(0) as u64).div_ceil(1000).max(1),
+                allowed: count <= limiter.max_requests,
+            }),
+            // Redis being down shouldn't take the API with it; fall back to
+            // this pod's own counts
+            Err(e) => {
+                log::warn!("Rate limit lookup in Redis failed: {}", e);
+                Some(limiter.hit(client))
+            }
+        }
+    }
+}
+
+pub async fn enforce_rate_limits(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let policies = req.app_data::<web::Data<RateLimitPolicies>>().cloned();
+    let usage = match (policies, client_ip(req.request())) {
+        (Some(policies), Some(client)) => policies.hit(req.method(), req.path(), client).await,
+        _ => None,
+    };
+
+    if let Some(usage) = usage.filter(|usage| !usage.allowed) {
+        let error = AppError::RetryLater {
+            status: StatusCode::TOO_MANY_REQUESTS,
+            code: "RATE_LIMITED",
+            message: "Too many requests; try again later".to_string(),
+            retry_after: usage.reset_after,
+        };
+        let mut response = error.error_response();
+        insert_rate_limit_headers(response.headers_mut(), &usage);
+        return Ok(req.into_response(response));
+    }
+
+    let mut res = next.call(req).await?.map_into_boxed_body();
+    if let Some(usage) = usage {
+        insert_rate_limit_headers(res.headers_mut(), &usage);
+    }
+    Ok(res)
+}
+
+fn insert_rate_limit_headers(headers: &mut HeaderMap, usage: &RateLimitUsage) {
+    let values = [
+        ("x-ratelimit-limit", usage.limit as u64),
+        ("x-ratelimit-remaining", usage.remaining as u64),
+        ("x-ratelimit-reset", usage.reset_after),
+    ];
+    for (name, value) in values {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use super::*;
+
+    fn client(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn policies(auth: u32, write: u32, read: u32) -> RateLimitPolicies {
+        let budget = |max_requests: u32| (max_requests > 0).then(|| RateLimiter::new(max_requests, POLICY_WINDOW));
+        RateLimitPolicies {
+            auth: budget(auth),
+            write: budget(write),
+            read: budget(read),
+            redis: None,
+        }
+    }
+
+    fn policy_name(policies: &RateLimitPolicies, method: Method, path: &str) -> Option<&'static str> {
+        policies.policy(&method, path).map(|(name, _)| name)
+    }
+
+    #[test]
+    fn requests_count_down_the_remaining_budget() {
+        let limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let a = client("198.51.100.1");
+
+        let first = limiter.hit(a);
+        assert!(first.allowed);
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert!(first.reset_after > 0 && first.reset_after <= 60);
+
+        assert_eq!(limiter.hit(a).remaining, 0);
+
+        let blocked = limiter.hit(a);
+        assert!(!blocked.allowed);
+        assert_eq!(blocked.remaining, 0);
+        assert!(limiter.check(a).is_err());
+
+        // Other clients have their own windows
+        assert!(limiter.check(client("198.51.100.2")).is_ok());
+    }
+
+    #[test]
+    fn budget_refills_once_the_window_ends() {
+        let limiter = RateLimiter::new(1, Duration::from_millis(50));
+        let a = client("2001:db8::1");
+
+        assert!(limiter.hit(a).allowed);
+        assert!(!limiter.hit(a).allowed);
+        assert_eq!(limiter.check(a), Err(1));
+
+        sleep(Duration::from_millis(60));
+
+        let usage = limiter.hit(a);
+        assert!(usage.allowed);
+        assert_eq!(usage.remaining, 0);
+    }
+
+    #[test]
+    fn routes_get_the_auth_write_or_read_budget() {
+        let policies = policies(20, 120, 600);
+
+        assert_eq!(policy_name(&policies, Method::POST, "/api/v1/auth/login"), Some("auth"));
+        assert_eq!(policy_name(&policies, Method::POST, "/api/v1/auth/register"), Some("auth"));
+        assert_eq!(policy_name(&policies, Method::GET, "/api/v1/auth/available"), Some("read"));
+        assert_eq!(policy_name(&policies, Method::POST, "/api/v1/posts"), Some("write"));
+        assert_eq!(policy_name(&policies, Method::DELETE, "/api/v1/posts/1"), Some("write"));
+        assert_eq!(policy_name(&policies, Method::PATCH, "/api/v1/users/me"), Some("write"));
+        assert_eq!(policy_name(&policies, Method::GET, "/api/v1/posts"), Some("read"));
+        assert_eq!(policy_name(&policies, Method::HEAD, "/api/v1/posts"), Some("read"));
+
+        assert_eq!(policy_name(&policies, Method::OPTIONS, "/api/v1/posts"), None);
+        assert_eq!(policy_name(&policies, Method::GET, "/health"), None);
+        assert_eq!(policy_name(&policies, Method::POST, "/api/v1auth/login"), None);
+    }
+
+    #[test]
+    fn disabled_budgets_are_not_enforced() {
+        let policies = policies(0, 120, 0);
+
+        assert_eq!(policy_name(&policies, Method::POST, "/api/v1/auth/login"), None);
+        assert_eq!(policy_name(&policies, Method::GET, "/api/v1/posts"), None);
+        assert_eq!(policy_name(&policies, Method::PUT, "/api/v1/posts/1"), Some("write"));
+    }
+}
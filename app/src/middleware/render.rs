@@ -0,0 +1,27 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::Error;
+
+tokio::task_local! {
+    static RENDER_HTML: bool;
+}
+
+// Set for the rest of the request when it asks for `render=html`, so post
+// responses built anywhere in the handler include `content_html`
+pub async fn detect_content_rendering(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let render_html = req
+        .query_string()
+        .split('&')
+        .any(|pair| pair.eq_ignore_ascii_case("render=html"));
+
+    RENDER_HTML.scope(render_html, next.call(req)).await
+}
+
+/// Whether the current request opted in to rendered HTML.
+pub fn html_requested() -> bool {
+    RENDER_HTML.try_with(|render| *render).unwrap_or(false)
+}
@@ -1,9 +1,18 @@
+use actix_multipart::form::bytes::Bytes as MultipartBytes;
+use actix_multipart::form::MultipartForm;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use std::collections::BTreeMap;
 use uuid::Uuid;
 use validator::Validate;
 
+use crate::utils::media::serialize_media_url;
+use crate::utils::validation::{
+    validate_hex_color, validate_image_host, validate_language, validate_tag_name, validate_tag_slug, validate_tags,
+    validate_webhook_event_types, validate_webhook_url,
+};
+
 // User Models
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -15,10 +24,53 @@ pub struct User {
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
     pub is_verified: Option<bool>,
+    pub deactivated_at: Option<DateTime<Utc>>,
+    pub role: String,
+    pub banned_at: Option<DateTime<Utc>>,
+    pub suspended_until: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+/// Permission level of an account. Roles are ordered, so a moderator check
+/// also admits admins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    User,
+    Moderator,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::User => "user",
+            Role::Moderator => "moderator",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "admin" => Role::Admin,
+            "moderator" => Role::Moderator,
+            _ => Role::User,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: Role,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UserRoleResponse {
+    pub user_id: Uuid,
+    pub role: Role,
+}
+
 #[derive(Debug, Serialize, Clone)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -26,11 +78,22 @@ pub struct UserResponse {
     pub email: String,
     pub full_name: Option<String>,
     pub bio: Option<String>,
+    #[serde(serialize_with = "serialize_media_url")]
     pub avatar_url: Option<String>,
     pub is_verified: bool,
     pub follower_count: i64,
     pub following_count: i64,
     pub created_at: DateTime<Utc>,
+    // How the requesting user relates to this one; only in follower and
+    // following listings and on profiles, and never for the user themselves
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub relationship: Option<Relationship>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, Default)]
+pub struct Relationship {
+    pub is_following: bool,
+    pub is_followed_by: bool,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -49,6 +112,7 @@ pub struct CreateUserRequest {
 pub struct UpdateUserRequest {
     pub full_name: Option<String>,
     pub bio: Option<String>,
+    #[validate(url, custom = "validate_image_host")]
     pub avatar_url: Option<String>,
 }
 
@@ -58,8 +122,63 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailParams {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthCallbackParams {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    #[validate(length(min = 6))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    #[validate(length(min = 6))]
+    pub new_password: String,
+    // Signs out every other session once their access tokens expire
+    #[serde(default)]
+    pub revoke_refresh_tokens: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AvailabilityParams {
+    pub username: Option<String>,
+    pub email: Option<String>,
+}
+
+// Only the fields that were asked about are included
+#[derive(Debug, Serialize)]
+pub struct AvailabilityResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username_available: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email_available: Option<bool>,
+}
+
 // Post Models
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Post {
     pub id: Uuid,
     pub title: String,
@@ -70,6 +189,13 @@ pub struct Post {
     pub author_id: Uuid,
     pub is_published: Option<bool>,
     pub published_at: Option<DateTime<Utc>>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub language: Option<String>,
+    // Kept up to date by triggers on likes and comments
+    pub like_count: i64,
+    pub comment_count: i64,
+    pub word_count: i32,
+    pub reading_time_minutes: i32,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -80,19 +206,103 @@ pub struct PostResponse {
     pub title: String,
     pub slug: String,
     pub content: String,
+    // Sanitized HTML rendering of `content`, included with `render=html`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_html: Option<String>,
+    pub excerpt: Option<String>,
+    #[serde(serialize_with = "serialize_media_url")]
+    pub cover_image: Option<String>,
+    pub author: UserResponse,
+    // The author followed by the co-authors who accepted, in order of joining
+    pub authors: Vec<UserResponse>,
+    pub tags: Vec<String>,
+    pub like_count: i64,
+    pub comment_count: i64,
+    pub word_count: i32,
+    pub reading_time_minutes: i32,
+    pub is_liked: bool,
+    pub is_bookmarked: bool,
+    pub is_published: bool,
+    pub published_at: Option<DateTime<Utc>>,
+    // When a scheduled draft will be published
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+    pub language: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Set when the post was looked up through one of its old slugs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonical_slug: Option<String>,
+}
+
+// Longest excerpt list endpoints send, in characters
+const SUMMARY_EXCERPT_LENGTH: usize = 300;
+
+// A post as list endpoints return it: everything but the content, with the
+// excerpt cut short
+#[derive(Debug, Serialize)]
+pub struct PostSummaryResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub slug: String,
     pub excerpt: Option<String>,
+    #[serde(serialize_with = "serialize_media_url")]
     pub cover_image: Option<String>,
     pub author: UserResponse,
+    pub authors: Vec<UserResponse>,
     pub tags: Vec<String>,
     pub like_count: i64,
     pub comment_count: i64,
+    pub word_count: i32,
+    pub reading_time_minutes: i32,
     pub is_liked: bool,
+    pub is_bookmarked: bool,
     pub is_published: bool,
     pub published_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub publish_at: Option<DateTime<Utc>>,
+    pub language: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+impl From<PostResponse> for PostSummaryResponse {
+    fn from(post: PostResponse) -> Self {
+        let excerpt = post.excerpt.map(|excerpt| {
+            if excerpt.chars().count() <= SUMMARY_EXCERPT_LENGTH {
+                return excerpt;
+            }
+            let mut short: String = excerpt.chars().take(SUMMARY_EXCERPT_LENGTH - 1).collect();
+            short.truncate(short.trim_end().len());
+            short.push('…');
+            short
+        });
+
+        PostSummaryResponse {
+            id: post.id,
+            title: post.title,
+            slug: post.slug,
+            excerpt,
+            cover_image: post.cover_image,
+            author: post.author,
+            authors: post.authors,
+            tags: post.tags,
+            like_count: post.like_count,
+            comment_count: post.comment_count,
+            word_count: post.word_count,
+            reading_time_minutes: post.reading_time_minutes,
+            is_liked: post.is_liked,
+            is_bookmarked: post.is_bookmarked,
+            is_published: post.is_published,
+            published_at: post.published_at,
+            publish_at: post.publish_at,
+            language: post.language,
+            created_at: post.created_at,
+            updated_at: post.updated_at,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreatePostRequest {
     #[validate(length(min = 1, max = 255))]
@@ -100,8 +310,14 @@ pub struct CreatePostRequest {
     #[validate(length(min = 1))]
     pub content: String,
     pub excerpt: Option<String>,
+    #[validate(url, custom = "validate_image_host")]
     pub cover_image: Option<String>,
+    #[validate(custom = "validate_tags")]
     pub tags: Option<Vec<String>>,
+    #[validate(custom = "validate_language")]
+    pub language: Option<String>,
+    // Keeps the post as a draft until this time, then publishes it
+    pub publish_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -109,8 +325,27 @@ pub struct UpdatePostRequest {
     pub title: Option<String>,
     pub content: Option<String>,
     pub excerpt: Option<String>,
+    #[validate(url, custom = "validate_image_host")]
     pub cover_image: Option<String>,
+    #[validate(custom = "validate_tags")]
     pub tags: Option<Vec<String>>,
+    #[validate(custom = "validate_language")]
+    pub language: Option<String>,
+    // Replaces the excerpt with one generated from the (new) content
+    #[serde(default)]
+    pub regenerate_excerpt: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PublishPostRequest {
+    // Publishes at this time instead of immediately
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddTagRequest {
+    #[validate(custom = "validate_tag_name")]
+    pub name: String,
 }
 
 // Comment Models
@@ -121,6 +356,10 @@ pub struct Comment {
     pub post_id: Uuid,
     pub author_id: Uuid,
     pub parent_id: Option<Uuid>,
+    pub pinned: bool,
+    pub like_count: i64,
+    pub edited: bool,
+    pub deleted_at: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
@@ -128,14 +367,69 @@ pub struct Comment {
 #[derive(Debug, Serialize, Clone)]
 pub struct CommentResponse {
     pub id: Uuid,
+    // "[deleted]" for deleted comments, which keep their place in the thread
     pub content: String,
-    pub author: UserResponse,
+    // Hidden on deleted comments
+    pub author: Option<UserResponse>,
+    pub is_deleted: bool,
     pub parent_id: Option<Uuid>,
+    pub pinned: bool,
+    // Whether the content changed since it was posted
+    pub edited: bool,
     pub replies: Vec<CommentResponse>,
+    // Direct replies, including those not in `replies`
+    pub reply_count: i64,
+    pub like_count: i64,
+    pub is_liked: bool,
+    pub has_more_replies: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
+// An earlier version of an edited comment
+#[derive(Debug, Serialize)]
+pub struct CommentRevision {
+    pub id: Uuid,
+    pub content: String,
+    pub edited_by: Option<Uuid>,
+    // When this version was replaced
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CommentRepliesResponse {
+    pub replies: Vec<CommentResponse>,
+    pub has_more: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CommentListParams {
+    pub flat: Option<bool>,
+    pub sort: Option<String>,
+}
+
+// A page of top-level comments with their first replies. `total` counts the
+// top-level comments being paged through, `total_comments` every comment.
+#[derive(Debug, Serialize)]
+pub struct CommentTreeResponse {
+    #[serde(flatten)]
+    pub page: PaginatedResponse<CommentResponse>,
+    pub total_comments: i64,
+}
+
+// How many levels of replies to render, up to COMMENT_MAX_RENDER_DEPTH
+#[derive(Debug, Deserialize)]
+pub struct CommentDepthParams {
+    pub depth: Option<u32>,
+}
+
+// `after` is the id of the last reply the client already has
+#[derive(Debug, Deserialize)]
+pub struct MoreRepliesParams {
+    pub after: Option<Uuid>,
+    pub limit: Option<u32>,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateCommentRequest {
     #[validate(length(min = 1))]
@@ -166,7 +460,76 @@ pub struct Follow {
 pub struct Tag {
     pub id: Uuid,
     pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+    pub color: Option<String>,
+    #[serde(serialize_with = "serialize_media_url")]
+    pub cover_image: Option<String>,
+    // Published posts carrying the tag
+    pub post_count: i64,
     pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// Fields left out are kept as they are
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateTagRequest {
+    #[validate(custom = "validate_tag_slug")]
+    pub slug: Option<String>,
+    #[validate(length(max = 500))]
+    pub description: Option<String>,
+    #[validate(custom = "validate_hex_color")]
+    pub color: Option<String>,
+    #[validate(url, custom = "validate_image_host")]
+    pub cover_image: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MergeTagRequest {
+    // Name of the tag that absorbs the merged one
+    #[validate(custom = "validate_tag_name")]
+    pub into: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TagSynonym {
+    pub name: String,
+    // Name of the tag the synonym resolves to
+    pub tag: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateTagSynonymRequest {
+    #[validate(custom = "validate_tag_name")]
+    pub name: String,
+    #[validate(custom = "validate_tag_name")]
+    pub tag: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TagWeight {
+    pub name: String,
+    pub weight: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrendingTag {
+    pub name: String,
+    pub score: f64,
+    // Trending posts carrying the tag
+    pub post_count: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagCloudParams {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedTagsParams {
+    pub limit: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -175,12 +538,396 @@ pub struct PostTag {
     pub tag_id: Uuid,
 }
 
+// Mention Models
+#[derive(Debug, Serialize)]
+pub struct MentionResponse {
+    pub id: Uuid,
+    pub source: String, // "post" or "comment"
+    pub post_id: Uuid,
+    pub post_title: String,
+    pub post_slug: String,
+    pub comment_id: Option<Uuid>,
+    pub snippet: String,
+    pub author_id: Uuid,
+    pub author_username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// API Key Models
+#[derive(Debug, Serialize, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+}
+
+// The only time the full key is ever returned
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKeyResponse {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub key: String,
+}
+
+// Co-author Models
+#[derive(Debug, Deserialize)]
+pub struct InviteCoauthorRequest {
+    pub user_id: Uuid,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoauthorResponse {
+    pub user: UserResponse,
+    // "invited" until the invitation is accepted, then "accepted"
+    pub status: &'static str,
+    pub invited_by: Option<Uuid>,
+    pub invited_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoauthorInvitationResponse {
+    pub post_id: Uuid,
+    pub post_title: String,
+    pub invited_by: Option<Uuid>,
+    pub invited_at: DateTime<Utc>,
+}
+
+// Reading List Models
+#[derive(Debug, Serialize)]
+pub struct ReadingListResponse {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_public: bool,
+    // Posts in the list that are currently published
+    pub post_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateReadingListRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 500))]
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateReadingListRequest {
+    #[validate(length(min = 1, max = 100))]
+    pub name: Option<String>,
+    #[validate(length(max = 500))]
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+// Webhook Models
+#[derive(Debug, Serialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub url: String,
+    pub event_types: Vec<String>,
+    pub site_wide: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWebhookRequest {
+    #[validate(length(max = 2048), custom = "validate_webhook_url")]
+    pub url: String,
+    #[validate(length(min = 1), custom = "validate_webhook_event_types")]
+    pub event_types: Vec<String>,
+    // Every event rather than only the caller's; admins only
+    #[serde(default)]
+    pub site_wide: bool,
+}
+
+// The only time the signing secret is ever returned
+#[derive(Debug, Serialize)]
+pub struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub event_id: Uuid,
+    pub event_type: String,
+    pub payload: serde_json::Value,
+    pub status: String, // "pending", "delivered" or "failed"
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub last_error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Audit Models
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditEventRecord {
+    pub id: Uuid,
+    pub action: String,
+    pub actor_id: Option<Uuid>,
+    pub target_type: Option<String>,
+    pub target_id: Option<Uuid>,
+    pub ip_address: Option<String>,
+    pub metadata: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditEventParams {
+    pub action: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+// Report Models
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportReason {
+    Spam,
+    Harassment,
+    HateSpeech,
+    Violence,
+    SexualContent,
+    Misinformation,
+    OffTopic,
+    Other,
+}
+
+impl ReportReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportReason::Spam => "spam",
+            ReportReason::Harassment => "harassment",
+            ReportReason::HateSpeech => "hate_speech",
+            ReportReason::Violence => "violence",
+            ReportReason::SexualContent => "sexual_content",
+            ReportReason::Misinformation => "misinformation",
+            ReportReason::OffTopic => "off_topic",
+            ReportReason::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateReportRequest {
+    pub reason: ReportReason,
+    #[validate(length(max = 1000))]
+    pub details: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportAction {
+    // Takes the post or comment down
+    HideContent,
+    // Bans the author and takes the post or comment down
+    BanAuthor,
+    Dismiss,
+}
+
+impl ReportAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportAction::HideContent => "hide_content",
+            ReportAction::BanAuthor => "ban_author",
+            ReportAction::Dismiss => "dismiss",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResolveReportRequest {
+    pub action: ReportAction,
+    #[validate(length(max = 1000))]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportStatus {
+    Open,
+    // A moderator has picked the report up
+    Reviewing,
+    Resolved,
+    Dismissed,
+}
+
+impl ReportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReportStatus::Open => "open",
+            ReportStatus::Reviewing => "reviewing",
+            ReportStatus::Resolved => "resolved",
+            ReportStatus::Dismissed => "dismissed",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateReportRequest {
+    pub status: ReportStatus,
+    #[validate(length(max = 1000))]
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub id: Uuid,
+    pub reporter_id: Uuid,
+    pub target_type: String,
+    pub target_id: Uuid,
+    // None once the post or comment is gone for good
+    pub target_author_id: Option<Uuid>,
+    pub reason: String,
+    pub details: Option<String>,
+    pub status: String,
+    pub resolution: Option<String>,
+    pub resolution_note: Option<String>,
+    pub resolved_by: Option<Uuid>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReportListParams {
+    pub status: Option<String>, // "open" (default), "reviewing", "resolved", "dismissed" or "all"
+    pub target_type: Option<String>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+// Moderation Models
+#[derive(Debug, Deserialize)]
+pub struct AdminPostParams {
+    pub status: Option<String>, // "published", "draft", "scheduled", "deleted" or "all" (default)
+    pub author_id: Option<Uuid>,
+    pub q: Option<String>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminPost {
+    pub id: Uuid,
+    pub title: String,
+    pub slug: String,
+    pub author_id: Uuid,
+    pub author_username: String,
+    pub is_published: bool,
+    pub published_at: Option<DateTime<Utc>>,
+    pub publish_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub deleted_by: Option<Uuid>,
+    pub like_count: i64,
+    pub comment_count: i64,
+    pub open_reports: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminCommentParams {
+    pub status: Option<String>, // "visible", "hidden", "deleted" or "all" (default)
+    pub post_id: Option<Uuid>,
+    pub author_id: Option<Uuid>,
+    pub q: Option<String>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+// Unlike CommentResponse, shows the content of deleted and hidden comments
+#[derive(Debug, Serialize)]
+pub struct AdminComment {
+    pub id: Uuid,
+    pub post_id: Uuid,
+    pub parent_id: Option<Uuid>,
+    pub author_id: Uuid,
+    pub author_username: String,
+    pub content: String,
+    pub like_count: i64,
+    pub hidden_at: Option<DateTime<Utc>>,
+    pub hidden_by: Option<Uuid>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub open_reports: i64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminUserParams {
+    pub status: Option<String>, // "active", "suspended", "banned", "deactivated" or "all" (default)
+    pub role: Option<Role>,
+    pub q: Option<String>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+    pub is_verified: Option<bool>,
+    pub deactivated_at: Option<DateTime<Utc>>,
+    pub banned_at: Option<DateTime<Utc>>,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub post_count: i64,
+    pub comment_count: i64,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SuspendUserRequest {
+    #[validate(range(min = 1, max = 365))]
+    pub days: u32,
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SuspensionResponse {
+    pub user_id: Uuid,
+    pub suspended_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ModerationLogParams {
+    pub action: Option<String>,
+    pub actor_id: Option<Uuid>,
+    pub target_id: Option<Uuid>,
+    pub page: Option<u32>,
+    pub limit: Option<u32>,
+}
+
 // JWT Claims
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user_id
     pub username: String,
     pub exp: usize,
+    #[serde(default)]
+    pub iat: usize,
 }
 
 // API Response wrapper
@@ -200,11 +947,12 @@ impl<T> ApiResponse<T> {
         }
     }
 
-    pub fn error(message: String) -> ApiResponse<()> {
+    // Success without a payload, e.g. after a delete
+    pub fn ok_message(message: &str) -> ApiResponse<()> {
         ApiResponse {
-            success: false,
+            success: true,
             data: None,
-            message: Some(message),
+            message: Some(message.to_string()),
         }
     }
 }
@@ -225,6 +973,179 @@ impl Default for PaginationParams {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct DraftFilterParams {
+    pub status: Option<String>, // "draft" or "scheduled"
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ActivityParams {
+    pub interval: Option<String>, // "hour", "day" or "week"
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityBucket {
+    pub bucket: DateTime<Utc>,
+    pub like_count: i64,
+    pub comment_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostStatsBucket {
+    pub bucket: DateTime<Utc>,
+    pub view_count: i64,
+    pub unique_viewer_count: i64,
+    pub like_count: i64,
+    pub comment_count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PostStatsResponse {
+    pub view_count: i64,
+    pub unique_viewer_count: i64,
+    pub like_count: i64,
+    pub comment_count: i64,
+    pub buckets: Vec<PostStatsBucket>,
+}
+
+// Filters for the published post list; all of them combine
+#[derive(Debug, Deserialize)]
+pub struct PostFilterParams {
+    pub lang: Option<String>,
+    // Username or user id
+    pub author: Option<String>,
+    pub tag: Option<String>,
+    // Searched for in titles and content
+    pub q: Option<String>,
+    pub published_after: Option<DateTime<Utc>>,
+    pub published_before: Option<DateTime<Utc>>,
+}
+
+// Comma-separated fields to keep in list responses, e.g.
+// `id,title,author.username`
+#[derive(Debug, Deserialize)]
+pub struct FieldsParams {
+    pub fields: Option<String>,
+}
+
+// Sorting
+#[derive(Debug, Deserialize)]
+pub struct SortParams {
+    pub sort: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PostSort {
+    Newest,
+    MostLiked,
+    MostCommented,
+    Trending,
+}
+
+impl PostSort {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("newest") {
+            "newest" => Ok(PostSort::Newest),
+            "most_liked" => Ok(PostSort::MostLiked),
+            "most_commented" => Ok(PostSort::MostCommented),
+            "trending" => Ok(PostSort::Trending),
+            other => Err(format!(
+                "Invalid sort '{}', expected one of: newest, most_liked, most_commented, trending",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PostSort::Newest => "newest",
+            PostSort::MostLiked => "most_liked",
+            PostSort::MostCommented => "most_commented",
+            PostSort::Trending => "trending",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CommentSort {
+    Oldest,
+    Newest,
+    Top,
+}
+
+impl CommentSort {
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("oldest") {
+            "oldest" => Ok(CommentSort::Oldest),
+            "newest" => Ok(CommentSort::Newest),
+            "top" => Ok(CommentSort::Top),
+            other => Err(format!(
+                "Invalid sort '{}', expected one of: oldest, newest, top",
+                other
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CommentSort::Oldest => "oldest",
+            CommentSort::Newest => "newest",
+            CommentSort::Top => "top",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RelatedPostsParams {
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TrendingParams {
+    pub period: Option<String>, // "24h" (default) or "7d"
+}
+
+/// How far back trending looks. Activity counts for less the older it is,
+/// halving every `half_life_hours`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrendingPeriod {
+    Day,
+    Week,
+}
+
+impl TrendingPeriod {
+    pub const ALL: [TrendingPeriod; 2] = [TrendingPeriod::Day, TrendingPeriod::Week];
+
+    pub fn parse(value: Option<&str>) -> Result<Self, String> {
+        match value.unwrap_or("24h") {
+            "24h" => Ok(TrendingPeriod::Day),
+            "7d" => Ok(TrendingPeriod::Week),
+            other => Err(format!("Invalid period '{}', expected one of: 24h, 7d", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrendingPeriod::Day => "24h",
+            TrendingPeriod::Week => "7d",
+        }
+    }
+
+    pub fn hours(&self) -> i64 {
+        match self {
+            TrendingPeriod::Day => 24,
+            TrendingPeriod::Week => 7 * 24,
+        }
+    }
+
+    pub fn half_life_hours(&self) -> f64 {
+        match self {
+            TrendingPeriod::Day => 6.0,
+            TrendingPeriod::Week => 36.0,
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct PaginatedResponse<T> {
     pub data: Vec<T>,
@@ -232,4 +1153,131 @@ pub struct PaginatedResponse<T> {
     pub page: u32,
     pub limit: u32,
     pub total_pages: u32,
-}
\ No newline at end of file
+}
+// Realtime notifications, pushed to the recipient's open WebSocket connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Mention,
+    Comment,
+    Reply,
+    Follow,
+    // One of the user's drafts is about to be cleaned up
+    DraftExpiring,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 5] = [
+        NotificationKind::Mention,
+        NotificationKind::Comment,
+        NotificationKind::Reply,
+        NotificationKind::Follow,
+        NotificationKind::DraftExpiring,
+    ];
+}
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub kind: NotificationKind,
+    // The user whose action triggered it
+    pub actor_id: Uuid,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_id: Option<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Notification preferences
+/// How a notification reaches its recipient.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    InApp,
+    Email,
+    // Stored for clients, but there's no push transport yet
+    Push,
+}
+
+impl NotificationChannel {
+    pub const ALL: [NotificationChannel; 3] = [
+        NotificationChannel::InApp,
+        NotificationChannel::Email,
+        NotificationChannel::Push,
+    ];
+}
+
+/// How often a user is emailed a digest of followed authors' new posts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    #[default]
+    Off,
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestFrequency::Off => "off",
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    pub fn from_db(value: &str) -> Self {
+        match value {
+            "daily" => DigestFrequency::Daily,
+            "weekly" => DigestFrequency::Weekly,
+            _ => DigestFrequency::Off,
+        }
+    }
+}
+
+// Only the choices a user has made; anything missing uses the default
+pub type ChannelPreferences = BTreeMap<NotificationChannel, BTreeMap<NotificationKind, bool>>;
+
+// Every channel and kind, with defaults filled in
+#[derive(Debug, Serialize)]
+pub struct NotificationSettingsResponse {
+    pub channels: ChannelPreferences,
+    pub digest: DigestFrequency,
+}
+
+// Only the channels, kinds and digest given are changed
+#[derive(Debug, Deserialize)]
+pub struct UpdateNotificationSettingsRequest {
+    #[serde(default)]
+    pub channels: ChannelPreferences,
+    pub digest: Option<DigestFrequency>,
+}
+
+// Media
+#[derive(Debug, MultipartForm)]
+pub struct MediaUploadForm {
+    pub file: MultipartBytes,
+}
+
+#[derive(Debug, Clone)]
+pub struct Media {
+    pub id: Uuid,
+    pub owner_id: Uuid,
+    pub storage_key: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MediaResponse {
+    pub id: Uuid,
+    // Absolute URL, usable as a `cover_image` or `avatar_url`
+    pub url: String,
+    pub content_type: String,
+    pub size_bytes: i64,
+    // Resized copies by preset name (thumbnail, feed, full). Generated after
+    // the upload, so empty until they're ready.
+    pub variants: BTreeMap<String, String>,
+    pub created_at: DateTime<Utc>,
+}
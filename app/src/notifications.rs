@@ -0,0 +1,339 @@
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::events::{DomainEvent, Subscriber};
+use crate::mailer::{Email, Mailer};
+use crate::models::{ChannelPreferences, DigestFrequency, NotificationChannel, NotificationKind};
+
+// Characters of a comment quoted in its email
+const SNIPPET_LENGTH: usize = 200;
+
+/// A user's notification preferences, as stored in `notification_settings`.
+#[derive(Debug, Default)]
+pub struct NotificationSettings {
+    pub channels: ChannelPreferences,
+    pub digest_frequency: DigestFrequency,
+}
+
+impl NotificationSettings {
+    /// The user's settings, or the defaults if they never changed any.
+    pub async fn load(pool: &PgPool, user_id: Uuid) -> Result<Self, sqlx::Error> {
+        let settings = sqlx::query!(
+            r#"
+            SELECT channels as "channels: Json<ChannelPreferences>", digest_frequency
+            FROM notification_settings
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(match settings {
+            Some(settings) => Self {
+                channels: settings.channels.0,
+                digest_frequency: DigestFrequency::from_db(&settings.digest_frequency),
+            },
+            None => Self::default(),
+        })
+    }
+
+    /// Whether the user wants `kind` notifications sent over `channel`.
+    pub fn allows(&self, channel: NotificationChannel, kind: NotificationKind) -> bool {
+        allows(&self.channels, channel, kind)
+    }
+
+    /// Every channel and kind, with defaults for those the user hasn't set.
+    pub fn resolved_channels(&self) -> ChannelPreferences {
+        NotificationChannel::ALL
+            .into_iter()
+            .map(|channel| {
+                let kinds = NotificationKind::ALL
+                    .into_iter()
+                    .map(|kind| (kind, self.allows(channel, kind)))
+                    .collect();
+                (channel, kinds)
+            })
+            .collect()
+    }
+}
+
+fn allows(channels: &ChannelPreferences, channel: NotificationChannel, kind: NotificationKind) -> bool {
+    channels
+        .get(&channel)
+        .and_then(|kinds| kinds.get(&kind))
+        .copied()
+        .unwrap_or_else(|| enabled_by_default(channel, kind))
+}
+
+// Everything is on in the app and for push. Replies, new followers and
+// drafts about to be cleaned up are emailed unless turned off; mentions and
+// comments on the user's posts only once turned on.
+pub fn enabled_by_default(channel: NotificationChannel, kind: NotificationKind) -> bool {
+    match channel {
+        NotificationChannel::InApp | NotificationChannel::Push => true,
+        NotificationChannel::Email => matches!(
+            kind,
+            NotificationKind::Reply | NotificationKind::Follow | NotificationKind::DraftExpiring
+        ),
+    }
+}
+
+/// The recipients who want their notification sent over `channel`, in the
+/// same order.
+pub async fn opted_in(
+    pool: &PgPool,
+    channel: NotificationChannel,
+    recipients: Vec<(Uuid, NotificationKind)>,
+) -> Result<Vec<(Uuid, NotificationKind)>, sqlx::Error> {
+    if recipients.is_empty() {
+        return Ok(recipients);
+    }
+
+    let user_ids: Vec<Uuid> = recipients.iter().map(|(user_id, _)| *user_id).collect();
+    let settings: HashMap<Uuid, ChannelPreferences> = sqlx::query!(
+        r#"
+        SELECT user_id, channels as "channels: Json<ChannelPreferences>"
+        FROM notification_settings
+        WHERE user_id = ANY($1)
+        "#,
+        &user_ids
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| (row.user_id, row.channels.0))
+    .collect();
+
+    let defaults = ChannelPreferences::new();
+    Ok(recipients
+        .into_iter()
+        .filter(|(user_id, kind)| allows(settings.get(user_id).unwrap_or(&defaults), channel, *kind))
+        .collect())
+}
+
+/// Who a new comment concerns. Each user is listed once, under the most
+/// specific reason that applies, and the comment's author not at all.
+pub fn comment_recipients(
+    author_id: Uuid,
+    post_author_id: Uuid,
+    parent_author_id: Option<Uuid>,
+    mentioned_user_ids: &[Uuid],
+) -> Vec<(Uuid, NotificationKind)> {
+    let mut seen = HashSet::from([author_id]);
+    parent_author_id
+        .map(|user_id| (user_id, NotificationKind::Reply))
+        .into_iter()
+        .chain(mentioned_user_ids.iter().map(|user_id| (*user_id, NotificationKind::Mention)))
+        .chain([(post_author_id, NotificationKind::Comment)])
+        .filter(|(user_id, _)| seen.insert(*user_id))
+        .collect()
+}
+
+/// Footer linking to where the recipient can change what they're sent.
+pub fn settings_footer(app_base_url: &str) -> String {
+    format!("\n\nManage your email notifications at {}/settings/notifications", app_base_url)
+}
+
+/// Outbox subscriber emailing users about activity that concerns them, as
+/// their settings allow. Only verified addresses of active accounts are
+/// emailed.
+pub struct EmailSubscriber {
+    pool: PgPool,
+    mailer: web::Data<dyn Mailer>,
+    app_base_url: String,
+}
+
+impl EmailSubscriber {
+    pub fn new(pool: PgPool, mailer: web::Data<dyn Mailer>, app_base_url: String) -> Self {
+        Self {
+            pool,
+            mailer,
+            app_base_url,
+        }
+    }
+
+    // The address to email the user at about `kind`, if they want that
+    async fn recipient(&self, user_id: Uuid, kind: NotificationKind) -> Result<Option<String>, String> {
+        let user = sqlx::query!(
+            r#"
+            SELECT email FROM users
+            WHERE id = $1 AND is_verified = true AND deactivated_at IS NULL AND banned_at IS NULL
+            "#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some(user) = user else {
+            return Ok(None);
+        };
+        let settings = NotificationSettings::load(&self.pool, user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        Ok(settings.allows(NotificationChannel::Email, kind).then_some(user.email))
+    }
+
+    async fn comment_created(&self, comment_id: Uuid, recipients: Vec<(Uuid, NotificationKind)>) -> Result<(), String> {
+        // Nothing to send if it was deleted since
+        let comment = sqlx::query!(
+            r#"
+            SELECT c.content, p.title, p.slug, u.username
+            FROM comments c
+            INNER JOIN posts p ON c.post_id = p.id
+            INNER JOIN users u ON c.author_id = u.id
+            WHERE c.id = $1 AND c.deleted_at IS NULL AND p.deleted_at IS NULL
+            "#,
+            comment_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some(comment) = comment else {
+            return Ok(());
+        };
+        let snippet: String = comment.content.chars().take(SNIPPET_LENGTH).collect();
+
+        for (user_id, kind) in recipients {
+            let Some(to) = self.recipient(user_id, kind).await? else {
+                continue;
+            };
+            let (subject, action) = match kind {
+                NotificationKind::Reply => ("replied to your comment", "replied to your comment on"),
+                NotificationKind::Mention => ("mentioned you in a comment", "mentioned you in a comment on"),
+                _ => ("commented on your post", "commented on"),
+            };
+            let email = Email {
+                to,
+                subject: format!("{} {}", comment.username, subject),
+                body: format!(
+                    "{} {} \"{}\":\n\n{}\n\n{}/posts/{}{}",
+                    comment.username,
+                    action,
+                    comment.title,
+                    snippet,
+                    self.app_base_url,
+                    comment.slug,
+                    settings_footer(&self.app_base_url)
+                ),
+            };
+            self.mailer.send(&email).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn user_followed(&self, follower_id: Uuid, following_id: Uuid) -> Result<(), String> {
+        // Nothing to send if it was undone since
+        let follower = sqlx::query_scalar!(
+            r#"
+            SELECT u.username
+            FROM follows f
+            INNER JOIN users u ON f.follower_id = u.id
+            WHERE f.follower_id = $1 AND f.following_id = $2
+            "#,
+            follower_id,
+            following_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some(username) = follower else {
+            return Ok(());
+        };
+        let Some(to) = self.recipient(following_id, NotificationKind::Follow).await? else {
+            return Ok(());
+        };
+
+        let email = Email {
+            to,
+            subject: format!("{} started following you", username),
+            body: format!(
+                "{} started following you.\n\n{}/users/{}{}",
+                username,
+                self.app_base_url,
+                username,
+                settings_footer(&self.app_base_url)
+            ),
+        };
+        self.mailer.send(&email).await
+    }
+
+    async fn draft_expiring(&self, post_id: Uuid, author_id: Uuid, deletes_at: DateTime<Utc>) -> Result<(), String> {
+        // Nothing to send if it was edited, published or deleted since
+        let draft = sqlx::query!(
+            r#"
+            SELECT title, slug, updated_at as "updated_at!"
+            FROM posts
+            WHERE id = $1 AND is_published = false AND deleted_at IS NULL AND cleanup_warned_at >= updated_at
+            "#,
+            post_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let Some(draft) = draft else {
+            return Ok(());
+        };
+        let Some(to) = self.recipient(author_id, NotificationKind::DraftExpiring).await? else {
+            return Ok(());
+        };
+
+        let email = Email {
+            to,
+            subject: format!("Your draft \"{}\" will be deleted soon", draft.title),
+            body: format!(
+                "Your draft \"{}\" hasn't been edited since {}. It will be deleted on or after {} unless you edit or publish it.\n\n{}/posts/{}{}",
+                draft.title,
+                draft.updated_at.format("%B %-d, %Y"),
+                deletes_at.format("%B %-d, %Y"),
+                self.app_base_url,
+                draft.slug,
+                settings_footer(&self.app_base_url)
+            ),
+        };
+        self.mailer.send(&email).await
+    }
+}
+
+#[async_trait]
+impl Subscriber for EmailSubscriber {
+    fn name(&self) -> &'static str {
+        "email"
+    }
+
+    async fn handle(&self, _event_id: Uuid, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::CommentCreated {
+                comment_id,
+                author_id,
+                post_author_id,
+                parent_author_id,
+                mentioned_user_ids,
+                ..
+            } => {
+                let recipients = comment_recipients(*author_id, *post_author_id, *parent_author_id, mentioned_user_ids);
+                self.comment_created(*comment_id, recipients).await
+            }
+            DomainEvent::UserFollowed { follower_id, following_id } => {
+                self.user_followed(*follower_id, *following_id).await
+            }
+            DomainEvent::DraftExpiring {
+                post_id,
+                author_id,
+                deletes_at,
+            } => self.draft_expiring(*post_id, *author_id, *deletes_at).await,
+            DomainEvent::PostPublished { .. } => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::Utc;
+use futures_util::StreamExt;
+use redis::aio::ConnectionManager;
+use redis::AsyncCommands;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::events::{DomainEvent, Subscriber};
+use crate::handlers::posts::announce_published;
+use crate::models::{Notification, NotificationChannel, NotificationKind, Post};
+use crate::notifications::{comment_recipients, opted_in};
+
+// Redis channel the pods exchange messages on
+const REDIS_CHANNEL: &str = "realtime";
+// Messages held for each connection before a slow one starts missing them
+const LOCAL_BUFFER: usize = 1024;
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Who a message is for: one user's connections, every connection watching
+/// a post, or the feed streams of an author's followers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "id", rename_all = "snake_case")]
+pub enum Topic {
+    User(Uuid),
+    Post(Uuid),
+    Author(Uuid),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RealtimeMessage {
+    pub topic: Topic,
+    /// Sent to clients as is
+    pub event: Value,
+}
+
+/// Fans events out to connected clients. Registered as `web::Data<Broker>`.
+///
+/// Without `REALTIME_REDIS_URL` events only reach clients connected to the
+/// pod that published them; with it they go through Redis pub/sub and reach
+/// every pod.
+pub struct Broker {
+    local: broadcast::Sender<Arc<RealtimeMessage>>,
+    redis: Option<ConnectionManager>,
+}
+
+impl Broker {
+    pub async fn from_env() -> Result<Self, String> {
+        let (local, _) = broadcast::channel(LOCAL_BUFFER);
+
+        let redis = match env::var("REALTIME_REDIS_URL").ok().filter(|url| !url.trim().is_empty()) {
+            Some(url) => {
+                let client = redis::Client::open(url.trim()).map_err(|e| format!("invalid REALTIME_REDIS_URL: {}", e))?;
+                let connection = ConnectionManager::new(client.clone())
+                    .await
+                    .map_err(|e| format!("failed to connect to Redis: {}", e))?;
+                spawn_redis_listener(client, local.clone());
+                Some(connection)
+            }
+            None => None,
+        };
+
+        Ok(Self { local, redis })
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.redis.is_some()
+    }
+
+    /// Every message published from now on; callers filter by topic.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<RealtimeMessage>> {
+        self.local.subscribe()
+    }
+
+    /// Sends `{"type": event_type, "data": data}` to the topic's subscribers.
+    /// Delivery is best-effort: clients that aren't connected miss the event,
+    /// and failures are only logged.
+    pub async fn publish<T: Serialize>(&self, topic: Topic, event_type: &str, data: &T) {
+        let message = RealtimeMessage {
+            topic,
+            event: json!({ "type": event_type, "data": data }),
+        };
+
+        match &self.redis {
+            Some(connection) => {
+                let payload = match serde_json::to_string(&message) {
+                    Ok(payload) => payload,
+                    Err(e) => return log::error!("Failed to encode realtime event {}: {}", event_type, e),
+                };
+                let mut connection = connection.clone();
+                if let Err(e) = connection.publish::<_, _, ()>(REDIS_CHANNEL, payload).await {
+                    log::warn!("Failed to publish realtime event {}: {}", event_type, e);
+                }
+            }
+            // Only fails when nobody is connected
+            None => {
+                let _ = self.local.send(Arc::new(message));
+            }
+        }
+    }
+
+    /// Notifies each recipient of something `actor_id` did, skipping the
+    /// actor and anyone already listed.
+    pub async fn notify_all(
+        &self,
+        actor_id: Uuid,
+        post_id: Option<Uuid>,
+        comment_id: Option<Uuid>,
+        recipients: impl IntoIterator<Item = (Uuid, NotificationKind)>,
+    ) {
+        let mut notified = HashSet::from([actor_id]);
+        for (recipient_id, kind) in recipients {
+            if notified.insert(recipient_id) {
+                let notification = Notification {
+                    kind,
+                    actor_id,
+                    post_id,
+                    comment_id,
+                    created_at: Utc::now(),
+                };
+                self.publish(Topic::User(recipient_id), "notification", &notification).await;
+            }
+        }
+    }
+}
+
+/// Outbox subscriber turning domain events into notifications and feed
+/// stream announcements.
+pub struct RealtimeSubscriber {
+    pool: PgPool,
+    broker: web::Data<Broker>,
+}
+
+impl RealtimeSubscriber {
+    pub fn new(pool: PgPool, broker: web::Data<Broker>) -> Self {
+        Self { pool, broker }
+    }
+}
+
+#[async_trait]
+impl Subscriber for RealtimeSubscriber {
+    fn name(&self) -> &'static str {
+        "realtime"
+    }
+
+    async fn handle(&self, _event_id: Uuid, event: &DomainEvent) -> Result<(), String> {
+        match event {
+            DomainEvent::PostPublished { post_id, .. } => {
+                // Nothing to announce if it was unpublished or deleted since
+                let post = sqlx::query_as!(
+                    Post,
+                    r#"
+                    SELECT id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
+                    FROM posts
+                    WHERE id = $1 AND is_published = true AND deleted_at IS NULL
+                    "#,
+                    post_id
+                )
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| e.to_string())?;
+
+                if let Some(post) = post {
+                    announce_published(&self.pool, &self.broker, vec![post])
+                        .await
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            DomainEvent::CommentCreated {
+                comment_id,
+                post_id,
+                author_id,
+                post_author_id,
+                parent_author_id,
+                mentioned_user_ids,
+            } => {
+                let recipients = comment_recipients(*author_id, *post_author_id, *parent_author_id, mentioned_user_ids);
+                let recipients = opted_in(&self.pool, NotificationChannel::InApp, recipients)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.broker.notify_all(*author_id, Some(*post_id), Some(*comment_id), recipients).await;
+            }
+            DomainEvent::UserFollowed { follower_id, following_id } => {
+                let recipients = opted_in(&self.pool, NotificationChannel::InApp, vec![(*following_id, NotificationKind::Follow)])
+                    .await
+                    .map_err(|e| e.to_string())?;
+                self.broker.notify_all(*follower_id, None, None, recipients).await;
+            }
+            DomainEvent::DraftExpiring { post_id, author_id, .. } => {
+                let recipients = opted_in(&self.pool, NotificationChannel::InApp, vec![(*author_id, NotificationKind::DraftExpiring)])
+                    .await
+                    .map_err(|e| e.to_string())?;
+                // notify_all leaves out the actor, who here is the recipient
+                if !recipients.is_empty() {
+                    let notification = Notification {
+                        kind: NotificationKind::DraftExpiring,
+                        actor_id: *author_id,
+                        post_id: Some(*post_id),
+                        comment_id: None,
+                        created_at: Utc::now(),
+                    };
+                    self.broker.publish(Topic::User(*author_id), "notification", &notification).await;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Relays messages from Redis to this pod's connections, resubscribing
+// whenever the subscription drops
+fn spawn_redis_listener(client: redis::Client, local: broadcast::Sender<Arc<RealtimeMessage>>) {
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = relay(&client, &local).await {
+                log::warn!("Realtime Redis subscription failed: {}", e);
+            }
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    });
+}
+
+async fn relay(client: &redis::Client, local: &broadcast::Sender<Arc<RealtimeMessage>>) -> redis::RedisResult<()> {
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(REDIS_CHANNEL).await?;
+
+    let mut messages = pubsub.on_message();
+    while let Some(message) = messages.next().await {
+        let payload: String = message.get_payload()?;
+        match serde_json::from_str::<RealtimeMessage>(&payload) {
+            Ok(message) => {
+                let _ = local.send(Arc::new(message));
+            }
+            Err(e) => log::warn!("Ignoring malformed realtime message: {}", e),
+        }
+    }
+
+    Ok(())
+}
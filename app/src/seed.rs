@@ -0,0 +1,204 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use slug::slugify;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::utils::markdown;
+use crate::utils::password::hash_password;
+
+// Every seeded account uses this password
+const SEED_PASSWORD: &str = "password123";
+
+const USERS: &[(&str, &str, &str)] = &[
+    ("alice", "Alice Martin", "Writes about Rust and systems programming."),
+    ("bob", "Bob Nguyen", "Kubernetes operator and occasional blogger."),
+    ("carol", "Carol Smith", "Frontend developer, tea enthusiast."),
+    ("dave", "Dave Okafor", "Reads everything, writes sometimes."),
+];
+
+// (author index, title, tags, published)
+const POSTS: &[(usize, &str, &[&str], bool)] = &[
+    (0, "Getting Started with Actix Web", &["rust", "web"], true),
+    (0, "Error Handling Patterns in Rust", &["rust"], true),
+    (1, "Deploying Rust Services on k3s", &["kubernetes", "rust", "devops"], true),
+    (1, "Notes on CloudNativePG Backups", &["kubernetes", "devops"], true),
+    (2, "Building a Blog Frontend", &["web"], true),
+    (2, "Half-finished Thoughts on CSS", &["web"], false),
+];
+
+// (follower index, following index)
+const FOLLOWS: &[(usize, usize)] = &[(0, 1), (1, 0), (2, 0), (3, 0), (3, 1), (3, 2)];
+
+// (user index, post index)
+const LIKES: &[(usize, usize)] = &[(1, 0), (2, 0), (3, 0), (0, 2), (3, 2), (3, 4)];
+
+// (author index, post index, parent comment index, content)
+const COMMENTS: &[(usize, usize, Option<usize>, &str)] = &[
+    (1, 0, None, "Great intro, this helped me get my first service running."),
+    (0, 0, Some(0), "Glad to hear it! Let me know what you build."),
+    (3, 2, None, "How do you handle database migrations during rollouts?"),
+    (1, 2, Some(2), "The app runs them on startup, so one replica at a time."),
+    (0, 4, None, "Looking forward to seeing the finished frontend."),
+];
+
+/// Fills an empty database with a fixed set of users, posts, tags, follows,
+/// likes and comments for local development. Ids and timestamps are derived
+/// from the fixture indexes so every run produces the same data. Does
+/// nothing if any user already exists.
+pub async fn run(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let user_count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users")
+        .fetch_one(pool)
+        .await?;
+
+    if user_count.0 > 0 {
+        log::info!("Database already contains users, skipping seed");
+        return Ok(());
+    }
+
+    let password_hash = hash_password(SEED_PASSWORD).expect("Failed to hash seed password");
+    let mut tx = pool.begin().await?;
+
+    for (i, (username, full_name, bio)) in USERS.iter().enumerate() {
+        sqlx::query!(
+            r#"
+            INSERT INTO users (id, username, email, password_hash, full_name, bio, is_verified, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, true, $7, $7)
+            "#,
+            user_id(i),
+            username,
+            format!("{}@example.com", username),
+            password_hash,
+            full_name,
+            bio,
+            timestamp(i as i64)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let mut tag_names: Vec<&str> = POSTS.iter().flat_map(|(_, _, tags, _)| tags.iter().copied()).collect();
+    tag_names.sort_unstable();
+    tag_names.dedup();
+
+    for (i, name) in tag_names.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO tags (id, name, slug, created_at) VALUES ($1, $2, $3, $4)",
+            fixture_id(0x7a9, i),
+            name,
+            slugify(name),
+            timestamp(0)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (i, (author, title, tags, published)) in POSTS.iter().enumerate() {
+        let created_at = timestamp(10 + i as i64);
+        let content = format!("# {}\n\nThis is seeded content for \"{}\".", title, title);
+        let word_count = markdown::word_count(&content);
+        sqlx::query!(
+            r#"
+            INSERT INTO posts (id, title, slug, content, excerpt, author_id, is_published, published_at, word_count, reading_time_minutes, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $10, $11, $9, $9)
+            "#,
+            post_id(i),
+            title,
+            slugify(title),
+            content,
+            format!("A short look at {}.", title.to_lowercase()),
+            user_id(*author),
+            published,
+            published.then_some(created_at),
+            created_at,
+            word_count as i32,
+            markdown::reading_time_minutes(word_count) as i32
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for tag in tags.iter() {
+            let tag_index = tag_names.iter().position(|name| name == tag).unwrap();
+            sqlx::query!(
+                "INSERT INTO post_tags (post_id, tag_id) VALUES ($1, $2)",
+                post_id(i),
+                fixture_id(0x7a9, tag_index)
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+    }
+
+    for (i, (follower, following)) in FOLLOWS.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO follows (id, follower_id, following_id, created_at) VALUES ($1, $2, $3, $4)",
+            fixture_id(0xf0, i),
+            user_id(*follower),
+            user_id(*following),
+            timestamp(20 + i as i64)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (i, (user, post)) in LIKES.iter().enumerate() {
+        sqlx::query!(
+            "INSERT INTO likes (id, user_id, post_id, created_at) VALUES ($1, $2, $3, $4)",
+            fixture_id(0x11e, i),
+            user_id(*user),
+            post_id(*post),
+            timestamp(30 + i as i64)
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    for (i, (author, post, parent, content)) in COMMENTS.iter().enumerate() {
+        let created_at = timestamp(40 + i as i64);
+        sqlx::query!(
+            r#"
+            INSERT INTO comments (id, content, post_id, author_id, parent_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            "#,
+            fixture_id(0xc0, i),
+            content,
+            post_id(*post),
+            user_id(*author),
+            parent.map(|parent| fixture_id(0xc0, parent)),
+            created_at
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    log::info!(
+        "Seeded {} users, {} posts, {} tags, {} follows, {} likes and {} comments (password: {})",
+        USERS.len(),
+        POSTS.len(),
+        tag_names.len(),
+        FOLLOWS.len(),
+        LIKES.len(),
+        COMMENTS.len(),
+        SEED_PASSWORD
+    );
+
+    Ok(())
+}
+
+fn fixture_id(kind: u128, index: usize) -> Uuid {
+    Uuid::from_u128((kind << 64) | (index as u128 + 1))
+}
+
+fn user_id(index: usize) -> Uuid {
+    fixture_id(0x05e, index)
+}
+
+fn post_id(index: usize) -> Uuid {
+    fixture_id(0x905, index)
+}
+
+// Fixture timestamps are spaced an hour apart from a fixed starting point
+fn timestamp(offset_hours: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2025, 1, 1, 9, 0, 0).unwrap() + Duration::hours(offset_hours)
+}
@@ -0,0 +1,159 @@
+use chrono::Utc;
+use slug::slugify;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::Post;
+
+// Longer titles are cut at a word boundary so suffixed slugs still fit
+// the column
+const MAX_SLUG_BASE_LENGTH: usize = 100;
+
+// Past this many posts sharing a base slug, a short random suffix is used
+// instead of counting further
+const MAX_NUMBERED_SUFFIX: u32 = 50;
+
+/// Slugifies `text`, cut at a hyphen to at most `MAX_SLUG_BASE_LENGTH`
+/// characters, or `fallback` when nothing of it survives slugifying.
+pub fn base_slug(text: &str, fallback: &str) -> String {
+    let slug = slugify(text);
+    let slug = if slug.len() > MAX_SLUG_BASE_LENGTH {
+        // slugify output is ASCII, so any index is a char boundary
+        let cut = &slug[..MAX_SLUG_BASE_LENGTH];
+        cut.rsplit_once('-').map_or(cut, |(head, _)| head).to_string()
+    } else {
+        slug
+    };
+
+    if slug.is_empty() {
+        fallback.to_string()
+    } else {
+        slug
+    }
+}
+
+/// `base` with a short random suffix, for when numbered suffixes run out or
+/// another request took the slug first.
+pub fn with_short_hash(base: &str) -> String {
+    format!("{}-{}", base, &Uuid::new_v4().simple().to_string()[..6])
+}
+
+/// Whether a post insert or update failed because another post took the
+/// slug between picking it and writing it.
+pub fn is_post_slug_conflict(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|error| error.constraint())
+        == Some("idx_posts_slug")
+}
+
+// Appends -2, -3, ... to `base` until it is not in `taken`, switching to a
+// short random suffix after MAX_NUMBERED_SUFFIX
+fn first_free_slug(base: &str, taken: &[String]) -> String {
+    let mut slug = base.to_string();
+    let mut suffix = 2;
+    while taken.contains(&slug) {
+        if suffix > MAX_NUMBERED_SUFFIX {
+            return with_short_hash(base);
+        }
+        slug = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    slug
+}
+
+/// Slugifies `title` into a slug that clashes with neither another post's
+/// slug nor a slug that redirects to another post.
+pub async fn unique_post_slug(
+    pool: &PgPool,
+    title: &str,
+    post_id: Option<Uuid>,
+) -> Result<String, sqlx::Error> {
+    let base = base_slug(title, "post");
+
+    let taken: Vec<String> = sqlx::query!(
+        r#"
+        SELECT slug as "slug!" FROM posts
+        WHERE id IS DISTINCT FROM $1 AND (slug = $2 OR slug LIKE $3)
+        UNION
+        SELECT old_slug FROM post_slug_redirects
+        WHERE post_id IS DISTINCT FROM $1 AND (old_slug = $2 OR old_slug LIKE $3)
+        "#,
+        post_id,
+        base,
+        format!("{}-%", base)
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| row.slug)
+    .collect();
+
+    Ok(first_free_slug(&base, &taken))
+}
+
+/// Same strategy as `unique_post_slug`, among tag slugs.
+pub async fn unique_tag_slug(pool: &PgPool, name: &str) -> Result<String, sqlx::Error> {
+    let base = base_slug(name, "tag");
+
+    let taken: Vec<String> = sqlx::query_scalar!(
+        "SELECT slug FROM tags WHERE slug = $1 OR slug LIKE $2",
+        base,
+        format!("{}-%", base)
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(first_free_slug(&base, &taken))
+}
+
+/// Moves a post to `new_slug`, keeping `old_slug` as a redirect.
+pub async fn replace_post_slug(
+    pool: &PgPool,
+    post_id: Uuid,
+    old_slug: &str,
+    new_slug: &str,
+) -> Result<Post, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let post = sqlx::query_as!(
+        Post,
+        r#"
+        UPDATE posts SET slug = $2, updated_at = $3
+        WHERE id = $1
+        RETURNING id, title, slug, content, excerpt, cover_image, author_id, is_published, published_at, publish_at, language, like_count, comment_count, word_count, reading_time_minutes, created_at, updated_at
+        "#,
+        post_id,
+        new_slug,
+        Utc::now()
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    if old_slug != new_slug {
+        sqlx::query!(
+            r#"
+            INSERT INTO post_slug_redirects (id, post_id, old_slug, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (old_slug) DO UPDATE SET post_id = EXCLUDED.post_id, created_at = EXCLUDED.created_at
+            "#,
+            Uuid::new_v4(),
+            post_id,
+            old_slug,
+            Utc::now()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // The post may be taking back one of its own earlier slugs
+        sqlx::query!(
+            "DELETE FROM post_slug_redirects WHERE old_slug = $1",
+            new_slug
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(post)
+}
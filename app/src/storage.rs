@@ -0,0 +1,115 @@
+use actix_web::web::Bytes;
+use async_trait::async_trait;
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as ObjectPath;
+use object_store::{Attribute, Attributes, ObjectStore, PutOptions, PutPayload};
+use std::env;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Where uploaded media is kept. Registered as `web::Data<dyn Storage>` so
+/// the backend can be swapped without touching the handlers.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    async fn put(&self, key: &str, content_type: &str, data: Bytes) -> Result<(), String>;
+    /// Returns `None` when nothing is stored under `key`.
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, String>;
+    /// Deleting a missing object is not an error.
+    async fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+/// Builds the backend selected by `MEDIA_STORAGE`: `local` (the default)
+/// writes under `MEDIA_LOCAL_DIR`, `s3` uses the bucket in `MEDIA_S3_BUCKET`.
+pub fn from_env() -> Result<Arc<dyn Storage>, String> {
+    match env::var("MEDIA_STORAGE").unwrap_or_default().trim().to_lowercase().as_str() {
+        "" | "local" => {
+            let root = env::var("MEDIA_LOCAL_DIR").unwrap_or_else(|_| "./uploads".to_string());
+            Ok(Arc::new(LocalStorage { root: PathBuf::from(root) }))
+        }
+        "s3" => Ok(Arc::new(S3Storage::from_env()?)),
+        other => Err(format!("invalid MEDIA_STORAGE '{}', expected one of: local, s3", other)),
+    }
+}
+
+/// Keeps files on the local disk; for development and single-node setups.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+#[async_trait]
+impl Storage for LocalStorage {
+    async fn put(&self, key: &str, _content_type: &str, data: Bytes) -> Result<(), String> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(|e| e.to_string())?;
+        }
+        tokio::fs::write(path, data).await.map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, String> {
+        match tokio::fs::read(self.root.join(key)).await {
+            Ok(data) => Ok(Some(Bytes::from(data))),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Keeps files in an S3 bucket, or any S3-compatible store such as MinIO.
+pub struct S3Storage {
+    store: AmazonS3,
+}
+
+impl S3Storage {
+    // Credentials, region and endpoint come from the standard AWS_* variables
+    // (AWS_ENDPOINT and AWS_ALLOW_HTTP=true for a local MinIO)
+    fn from_env() -> Result<Self, String> {
+        let bucket = env::var("MEDIA_S3_BUCKET").map_err(|_| "MEDIA_S3_BUCKET is required for S3 storage".to_string())?;
+        let store = AmazonS3Builder::from_env()
+            .with_bucket_name(bucket)
+            .build()
+            .map_err(|e| format!("invalid S3 storage configuration: {}", e))?;
+        Ok(Self { store })
+    }
+}
+
+#[async_trait]
+impl Storage for S3Storage {
+    async fn put(&self, key: &str, content_type: &str, data: Bytes) -> Result<(), String> {
+        let mut attributes = Attributes::new();
+        attributes.insert(Attribute::ContentType, content_type.to_string().into());
+        let options = PutOptions {
+            attributes,
+            ..Default::default()
+        };
+
+        self.store
+            .put_opts(&ObjectPath::from(key), PutPayload::from(data), options)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Bytes>, String> {
+        match self.store.get(&ObjectPath::from(key)).await {
+            Ok(result) => result.bytes().await.map(Some).map_err(|e| e.to_string()),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), String> {
+        match self.store.delete(&ObjectPath::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
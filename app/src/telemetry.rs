@@ -0,0 +1,96 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpMessage, HttpRequest};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::env;
+use std::io::{self, IsTerminal};
+use tracing::Span;
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpan, RootSpanBuilder};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+const DEFAULT_SERVICE_NAME: &str = "blog-backend";
+
+/// Installs the global subscriber: log lines go to stderr, filtered by
+/// `RUST_LOG`, and spans are exported over OTLP/HTTP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` or `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is
+/// set. Existing `log` macros are forwarded to the same subscriber.
+///
+/// The returned provider must be shut down on exit to flush pending spans.
+pub fn init() -> Result<Option<SdkTracerProvider>, String> {
+    let provider = if otlp_configured() { Some(tracer_provider()?) } else { None };
+    let otel_layer = provider
+        .as_ref()
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME)));
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("error")))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_ansi(io::stderr().is_terminal()),
+        )
+        .with(otel_layer)
+        .try_init()
+        .map_err(|e| e.to_string())?;
+
+    Ok(provider)
+}
+
+pub fn shutdown(provider: Option<SdkTracerProvider>) {
+    if let Some(provider) = provider {
+        if let Err(e) = provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+fn otlp_configured() -> bool {
+    ["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"]
+        .iter()
+        .any(|var| env::var(var).is_ok_and(|value| !value.trim().is_empty()))
+}
+
+fn tracer_provider() -> Result<SdkTracerProvider, String> {
+    // The exporter reads the endpoint, headers and timeout from the standard
+    // OTEL_EXPORTER_OTLP_* variables
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .build()
+        .map_err(|e| format!("invalid OTLP exporter configuration: {}", e))?;
+    let service_name = env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string());
+
+    // Continue traces started by callers that send a `traceparent` header
+    opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build())
+}
+
+/// Root span for each request: the standard HTTP fields plus the id of the
+/// authenticated user, filled in once it is known.
+pub struct RequestSpan;
+
+impl RootSpanBuilder for RequestSpan {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        tracing_actix_web::root_span!(request, user.id = tracing::field::Empty)
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
+
+pub fn record_user_id(req: &HttpRequest, user_id: Uuid) {
+    if let Some(span) = req.extensions().get::<RootSpan>() {
+        span.record("user.id", tracing::field::display(user_id));
+    }
+}
@@ -0,0 +1,114 @@
+//! Fixtures for handler tests. Every `#[sqlx::test]` runs against its own
+//! freshly migrated database, so rows are inserted directly.
+
+use actix_web::http::{header, Method};
+use actix_web::web;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::{Config, JwtConfig, OAuthConfig};
+use crate::middleware::cors::CorsConfig;
+use crate::models::Claims;
+use crate::utils::jwt::JwtKeys;
+
+/// Settings for handler tests, independent of the environment the tests run
+/// in. Tests needing other values change their own copy.
+pub fn settings() -> Config {
+    Config {
+        database_url: String::new(),
+        database_max_connections: 5,
+        host: "127.0.0.1".to_string(),
+        port: 8080,
+        shutdown_timeout_secs: 30,
+        app_base_url: "http://localhost:3000".to_string(),
+        password_reset_ttl: Duration::minutes(60),
+        email_verification_ttl: Duration::hours(48),
+        require_verified_email: false,
+        media_max_upload_bytes: 5 * 1024 * 1024,
+        comment_max_depth: 5,
+        comment_edit_window: None,
+        default_follow_user_ids: Vec::new(),
+        privacy_mode: false,
+        jwt: JwtConfig {
+            secret: "test-secret".to_string(),
+            keys: JwtKeys::hmac("test-secret"),
+            access_token_ttl: Duration::minutes(60),
+            refresh_token_ttl: Duration::days(30),
+        },
+        oauth: OAuthConfig {
+            public_api_url: "http://localhost:8080".to_string(),
+            github: None,
+            google: None,
+        },
+        cors: CorsConfig {
+            allowed_origins: vec!["http://localhost:3000".to_string()],
+            allow_any_origin: false,
+            allowed_methods: vec![Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::PATCH],
+            allowed_headers: vec![header::AUTHORIZATION, header::CONTENT_TYPE],
+            max_age: 3600,
+        },
+    }
+}
+
+pub fn config() -> web::Data<Config> {
+    web::Data::new(settings())
+}
+
+/// `Authorization` header value that authenticates as `user_id`.
+pub fn bearer(config: &Config, user_id: Uuid) -> String {
+    let now = Utc::now();
+    let claims = Claims {
+        sub: user_id.to_string(),
+        username: String::new(),
+        exp: (now + Duration::hours(1)).timestamp() as usize,
+        iat: now.timestamp() as usize,
+    };
+    format!("Bearer {}", config.jwt.keys.sign(&claims).unwrap())
+}
+
+pub async fn insert_user(pool: &PgPool, username: &str, password_hash: &str) -> Uuid {
+    sqlx::query_scalar!(
+        "INSERT INTO users (id, username, email, password_hash) VALUES ($1, $2, $3, $4) RETURNING id",
+        Uuid::new_v4(),
+        username,
+        format!("{}@example.com", username),
+        password_hash
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
+
+pub async fn insert_published_post(pool: &PgPool, author_id: Uuid, title: &str, published_at: DateTime<Utc>) -> Uuid {
+    let post_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO posts (id, title, slug, content, author_id, is_published, published_at, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, true, $6, $6, $6)
+        "#,
+        post_id,
+        title,
+        format!("{}-{}", slug::slugify(title), post_id.simple()),
+        format!("Content of {}", title),
+        author_id,
+        published_at
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+    post_id
+}
+
+pub async fn insert_comment(pool: &PgPool, post_id: Uuid, author_id: Uuid, content: &str) -> Uuid {
+    sqlx::query_scalar!(
+        "INSERT INTO comments (id, content, post_id, author_id) VALUES ($1, $2, $3, $4) RETURNING id",
+        Uuid::new_v4(),
+        content,
+        post_id,
+        author_id
+    )
+    .fetch_one(pool)
+    .await
+    .unwrap()
+}
@@ -0,0 +1,81 @@
+use actix_web::http::header::{self, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::time::SystemTime;
+
+/// `ETag` and `Last-Modified` values for a response, used to answer
+/// conditional requests with `304 Not Modified`.
+pub struct CacheValidators {
+    pub etag: EntityTag,
+    pub last_modified: Option<DateTime<Utc>>,
+    // The body depends on who is asking
+    pub private: bool,
+}
+
+impl CacheValidators {
+    /// Builds a weak ETag by hashing the serialized `body`, so any change to
+    /// what the client would receive changes the tag. Only pass
+    /// `last_modified` when nothing in the body can change without it.
+    pub fn for_body<T: Serialize>(body: &T, last_modified: Option<DateTime<Utc>>) -> Result<Self, serde_json::Error> {
+        let digest = hex::encode(&Sha256::digest(serde_json::to_vec(body)?)[..16]);
+
+        Ok(Self {
+            etag: EntityTag::new_weak(digest),
+            last_modified,
+            private: false,
+        })
+    }
+
+    /// Marks the response as depending on the caller's credentials, so
+    /// shared caches don't store it and keep copies per `Authorization`.
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    /// Whether the client's cached copy is still current. `If-None-Match`
+    /// takes precedence; `If-Modified-Since` is only checked without it.
+    pub fn is_fresh(&self, req: &HttpRequest) -> bool {
+        if req.headers().contains_key(header::IF_NONE_MATCH) {
+            return match IfNoneMatch::parse(req) {
+                Ok(IfNoneMatch::Any) => true,
+                Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+                Err(_) => false,
+            };
+        }
+
+        match (self.last_modified, IfModifiedSince::parse(req)) {
+            (Some(last_modified), Ok(IfModifiedSince(since))) => {
+                // HTTP dates only have second precision
+                let since = DateTime::<Utc>::from(SystemTime::from(since));
+                last_modified.timestamp() <= since.timestamp()
+            }
+            _ => false,
+        }
+    }
+
+    /// Adds the `ETag` and `Last-Modified` headers to a response, and the
+    /// `Cache-Control` and `Vary` headers of a private one.
+    pub fn apply<'a>(&self, builder: &'a mut HttpResponseBuilder) -> &'a mut HttpResponseBuilder {
+        builder.insert_header(header::ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            builder.insert_header(header::LastModified(HttpDate::from(SystemTime::from(last_modified))));
+        }
+        if self.private {
+            builder.insert_header(header::CacheControl(vec![header::CacheDirective::Private]));
+            builder.insert_header((header::VARY, "Authorization"));
+        }
+        builder
+    }
+
+    /// A `304 Not Modified` response when the client's copy is current.
+    pub fn not_modified(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        if self.is_fresh(req) {
+            Some(self.apply(&mut HttpResponse::NotModified()).finish())
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+// More than this is almost certainly not a hand-written field list
+const MAX_FIELDS: usize = 50;
+
+/// The fields a client asked for with `?fields=id,title,author.username`.
+///
+/// Dotted paths pick fields of nested objects, and of every object in a
+/// nested array. Names that match nothing are ignored, so clients can ask
+/// for fields that only some items have. Names may be given in snake_case or
+/// camelCase, so clients of an `API_FIELD_CASE=camel` deployment can use the
+/// names they see.
+#[derive(Debug, Clone)]
+pub struct FieldSelection {
+    paths: Vec<Vec<String>>,
+}
+
+impl FieldSelection {
+    /// Parses a comma-separated field list; `None` or a blank list selects
+    /// everything.
+    pub fn parse(fields: Option<&str>) -> Result<Option<Self>, String> {
+        let Some(fields) = fields.map(str::trim).filter(|fields| !fields.is_empty()) else {
+            return Ok(None);
+        };
+
+        let mut paths = Vec::new();
+        for field in fields.split(',').map(str::trim) {
+            // Selection runs before the response's keys are camelCased
+            let path: Vec<String> = field.split('.').map(camel_to_snake).collect();
+            if path.iter().any(|segment| segment.is_empty()) {
+                return Err(format!("Invalid field '{}' in fields", field));
+            }
+            paths.push(path);
+        }
+
+        if paths.len() > MAX_FIELDS {
+            return Err(format!("fields can list at most {} fields", MAX_FIELDS));
+        }
+
+        Ok(Some(Self { paths }))
+    }
+
+    /// Serializes `item` keeping only the selected fields.
+    pub fn select<T: Serialize>(&self, item: &T) -> Result<Value, serde_json::Error> {
+        let value = serde_json::to_value(item)?;
+        let paths: Vec<&[String]> = self.paths.iter().map(Vec::as_slice).collect();
+        Ok(select_paths(value, &paths))
+    }
+}
+
+/// Serializes each item, narrowed to `selection` when there is one.
+pub fn shape<T: Serialize>(
+    items: Vec<T>,
+    selection: Option<&FieldSelection>,
+) -> Result<Vec<Value>, serde_json::Error> {
+    items
+        .iter()
+        .map(|item| match selection {
+            Some(selection) => selection.select(item),
+            None => serde_json::to_value(item),
+        })
+        .collect()
+}
+
+fn camel_to_snake(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            if !snake.is_empty() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn select_paths(value: Value, paths: &[&[String]]) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut selected = Map::new();
+            for (key, field) in object {
+                // A bare name keeps the whole field, however deep
+                let rest: Vec<&[String]> = paths
+                    .iter()
+                    .filter(|path| path[0] == key)
+                    .map(|path| &path[1..])
+                    .collect();
+                if rest.is_empty() {
+                    continue;
+                }
+                if rest.iter().any(|path| path.is_empty()) {
+                    selected.insert(key, field);
+                } else {
+                    selected.insert(key, select_paths(field, &rest));
+                }
+            }
+            Value::Object(selected)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|item| select_paths(item, paths)).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn select(fields: &str, item: Value) -> Value {
+        FieldSelection::parse(Some(fields)).unwrap().unwrap().select(&item).unwrap()
+    }
+
+    #[test]
+    fn camel_case_names_select_snake_case_fields() {
+        let post = json!({ "id": 1, "author_id": 2, "like_count": 3, "author": { "avatar_url": "a.png", "username": "u" } });
+
+        assert_eq!(select("authorId,author.avatarUrl", post.clone()), json!({ "author_id": 2, "author": { "avatar_url": "a.png" } }));
+        assert_eq!(select("author_id,author.avatar_url", post.clone()), json!({ "author_id": 2, "author": { "avatar_url": "a.png" } }));
+        assert_eq!(select("id,likeCount", post), json!({ "id": 1, "like_count": 3 }));
+    }
+
+    #[test]
+    fn paths_reach_into_arrays() {
+        let post = json!({ "tags": ["a"], "authors": [{ "id": 1, "username": "a" }, { "id": 2, "username": "b" }] });
+        assert_eq!(select("authors.username", post), json!({ "authors": [{ "username": "a" }, { "username": "b" }] }));
+    }
+
+    #[test]
+    fn empty_segments_are_rejected() {
+        assert!(FieldSelection::parse(Some("id,,title")).is_err());
+        assert!(FieldSelection::parse(Some("author.")).is_err());
+        assert!(FieldSelection::parse(Some("  ")).unwrap().is_none());
+    }
+}
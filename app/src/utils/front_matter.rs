@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+
+/// Fields written to the YAML front matter of an exported post.
+pub struct FrontMatter<'a> {
+    pub title: &'a str,
+    pub tags: &'a [String],
+    pub excerpt: Option<&'a str>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// Renders a post as markdown preceded by a YAML front matter block.
+///
+/// Strings are written as JSON string literals, which YAML accepts as
+/// double-quoted scalars, so titles with quotes or colons round-trip safely.
+pub fn to_markdown(front_matter: &FrontMatter, content: &str) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap_or_default();
+
+    let mut markdown = String::from("---\n");
+    markdown.push_str(&format!("title: {}\n", quote(front_matter.title)));
+    markdown.push_str(&format!(
+        "tags: [{}]\n",
+        front_matter.tags.iter().map(|tag| quote(tag)).collect::<Vec<_>>().join(", ")
+    ));
+    if let Some(excerpt) = front_matter.excerpt {
+        markdown.push_str(&format!("excerpt: {}\n", quote(excerpt)));
+    }
+    if let Some(published_at) = front_matter.published_at {
+        markdown.push_str(&format!("published_at: {}\n", published_at.to_rfc3339()));
+    }
+    markdown.push_str("---\n\n");
+    markdown.push_str(content);
+    if !content.ends_with('\n') {
+        markdown.push('\n');
+    }
+
+    markdown
+}
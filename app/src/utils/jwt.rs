@@ -1,22 +1,226 @@
-use jsonwebtoken::{decode, DecodingKey, Validation};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::traits::PublicKeyParts;
+use rsa::RsaPublicKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
 use uuid::Uuid;
 
 use crate::models::Claims;
 
-pub const JWT_SECRET: &str = "your-secret-key-change-this-in-production";
+// DER header of an Ed25519 SubjectPublicKeyInfo; the raw 32 byte key follows
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
 
-pub fn validate_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(JWT_SECRET.as_ref()),
-        &Validation::default(),
-    )?;
+/// A public verification key in JSON Web Key format (RFC 7517).
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwk {
+    kty: &'static str,
+    #[serde(rename = "use")]
+    key_use: &'static str,
+    alg: &'static str,
+    kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    e: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    crv: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Clone)]
+struct VerificationKey {
+    kid: Option<String>,
+    key: DecodingKey,
+}
+
+/// Keys access tokens are signed and verified with. HS256 uses the shared
+/// `JWT_SECRET`; RS256 and EdDSA sign with a private key and publish the
+/// public keys, including retired ones that may still have live tokens.
+#[derive(Clone)]
+pub struct JwtKeys {
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    signing_kid: Option<String>,
+    verification_keys: Vec<VerificationKey>,
+    jwks: Vec<Jwk>,
+}
 
-    Ok(token_data.claims)
+impl fmt::Debug for JwtKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JwtKeys")
+            .field("algorithm", &self.algorithm)
+            .field("signing_kid", &self.signing_kid)
+            .finish_non_exhaustive()
+    }
 }
 
-pub fn extract_user_id_from_token(token: &str) -> Result<Uuid, Box<dyn std::error::Error>> {
-    let claims = validate_token(token)?;
+impl JwtKeys {
+    pub fn hmac(secret: &str) -> Self {
+        Self {
+            algorithm: Algorithm::HS256,
+            encoding_key: EncodingKey::from_secret(secret.as_ref()),
+            signing_kid: None,
+            verification_keys: vec![VerificationKey {
+                kid: None,
+                key: DecodingKey::from_secret(secret.as_ref()),
+            }],
+            jwks: Vec::new(),
+        }
+    }
+
+    /// Signs with `private_pem`, whose public half is `public_pem`. Tokens
+    /// signed with any of `previous_public_pems` keep verifying so keys can
+    /// be rotated without logging everyone out.
+    pub fn asymmetric(
+        algorithm: Algorithm,
+        private_pem: &str,
+        public_pem: &str,
+        previous_public_pems: &[String],
+    ) -> Result<Self, String> {
+        let encoding_key = match algorithm {
+            Algorithm::RS256 => EncodingKey::from_rsa_pem(private_pem.as_bytes()),
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(private_pem.as_bytes()),
+            _ => return Err(format!("unsupported asymmetric algorithm {:?}", algorithm)),
+        }
+        .map_err(|e| format!("invalid private key: {}", e))?;
+
+        let mut verification_keys = Vec::new();
+        let mut jwks = Vec::new();
+        for pem in std::iter::once(public_pem).chain(previous_public_pems.iter().map(String::as_str)) {
+            let (jwk, key) = public_key(algorithm, pem)?;
+            verification_keys.push(VerificationKey {
+                kid: Some(jwk.kid.clone()),
+                key,
+            });
+            jwks.push(jwk);
+        }
+
+        let keys = Self {
+            algorithm,
+            encoding_key,
+            signing_kid: Some(jwks[0].kid.clone()),
+            verification_keys,
+            jwks,
+        };
+
+        // Catches a private key that doesn't belong to the configured public key
+        let probe = Claims {
+            sub: Uuid::nil().to_string(),
+            username: String::new(),
+            exp: usize::MAX,
+            iat: 0,
+        };
+        keys.sign(&probe)
+            .and_then(|token| keys.validate(&token))
+            .map_err(|_| "JWT private key does not match the public key".to_string())?;
+
+        Ok(keys)
+    }
+
+    pub fn sign(&self, claims: &Claims) -> Result<String, jsonwebtoken::errors::Error> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.signing_kid.clone();
+        encode(&header, claims, &self.encoding_key)
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
+        let header = decode_header(token)?;
+        let key = self
+            .verification_keys
+            .iter()
+            .find(|key| header.kid.is_none() || key.kid == header.kid)
+            .ok_or_else(|| jsonwebtoken::errors::Error::from(ErrorKind::InvalidSignature))?;
+
+        let token_data = decode::<Claims>(token, &key.key, &Validation::new(self.algorithm))?;
+        Ok(token_data.claims)
+    }
+
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.jwks.clone(),
+        }
+    }
+}
+
+// Builds the JWK and verification key for a PEM public key. The key id is
+// the RFC 7638 thumbprint, so it is stable without any extra configuration.
+fn public_key(algorithm: Algorithm, pem: &str) -> Result<(Jwk, DecodingKey), String> {
+    match algorithm {
+        Algorithm::RS256 => {
+            let key = RsaPublicKey::from_public_key_pem(pem)
+                .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+                .map_err(|e| format!("invalid RSA public key: {}", e))?;
+            let n = URL_SAFE_NO_PAD.encode(key.n().to_bytes_be());
+            let e = URL_SAFE_NO_PAD.encode(key.e().to_bytes_be());
+            let kid = thumbprint(&format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, e, n));
+            let decoding_key = DecodingKey::from_rsa_components(&n, &e).map_err(|e| e.to_string())?;
+
+            Ok((
+                Jwk {
+                    kty: "RSA",
+                    key_use: "sig",
+                    alg: "RS256",
+                    kid,
+                    n: Some(n),
+                    e: Some(e),
+                    crv: None,
+                    x: None,
+                },
+                decoding_key,
+            ))
+        }
+        Algorithm::EdDSA => {
+            let der = pem::parse(pem)
+                .map_err(|e| format!("invalid Ed25519 public key: {}", e))?
+                .into_contents();
+            let raw = der
+                .strip_prefix(&ED25519_SPKI_PREFIX[..])
+                .filter(|raw| raw.len() == 32)
+                .ok_or_else(|| "invalid Ed25519 public key: not an Ed25519 SubjectPublicKeyInfo".to_string())?;
+            let x = URL_SAFE_NO_PAD.encode(raw);
+            let kid = thumbprint(&format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, x));
+            let decoding_key = DecodingKey::from_ed_components(&x).map_err(|e| e.to_string())?;
+
+            Ok((
+                Jwk {
+                    kty: "OKP",
+                    key_use: "sig",
+                    alg: "EdDSA",
+                    kid,
+                    n: None,
+                    e: None,
+                    crv: Some("Ed25519"),
+                    x: Some(x),
+                },
+                decoding_key,
+            ))
+        }
+        _ => Err(format!("unsupported asymmetric algorithm {:?}", algorithm)),
+    }
+}
+
+fn thumbprint(canonical_jwk: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(canonical_jwk.as_bytes()))
+}
+
+pub fn validate_token(token: &str, keys: &JwtKeys) -> Result<Claims, jsonwebtoken::errors::Error> {
+    keys.validate(token)
+}
+
+pub fn extract_user_id_from_token(token: &str, keys: &JwtKeys) -> Result<Uuid, Box<dyn std::error::Error>> {
+    let claims = validate_token(token, keys)?;
     let user_id = Uuid::parse_str(&claims.sub)?;
     Ok(user_id)
-}
\ No newline at end of file
+}
@@ -0,0 +1,18 @@
+use isolang::Language;
+
+/// Detects the language of `text` and returns its ISO 639-1 code.
+///
+/// Returns `None` when the detector isn't confident, which is common for
+/// very short texts, or when the language has no two-letter code.
+pub fn detect_language(text: &str) -> Option<String> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+    Language::from_639_3(info.lang().code())
+        .and_then(|language| language.to_639_1())
+        .map(str::to_string)
+}
+
+/// Normalizes an ISO 639-1 code to lowercase, returning `None` for unknown codes.
+pub fn normalize_language(code: &str) -> Option<String> {
+    let code = code.trim().to_lowercase();
+    Language::from_639_1(&code).map(|_| code)
+}
@@ -0,0 +1,73 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+/// Renders post markdown to HTML that is safe to insert into a page.
+///
+/// Raw HTML in the markdown is passed through the sanitizer along with the
+/// rendered output, so scripts, event handlers and `javascript:` links are
+/// stripped while ordinary formatting survives.
+pub fn render_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_TASKLISTS;
+
+    let mut unsafe_html = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, options));
+
+    ammonia::clean(&unsafe_html)
+}
+
+// Average adult reading speed for on-screen prose
+const WORDS_PER_MINUTE: usize = 200;
+
+/// The text of post markdown without its syntax, raw HTML or images, with
+/// blocks separated by newlines.
+pub fn plain_text(markdown: &str) -> String {
+    let mut text = String::with_capacity(markdown.len());
+    // Alt text of images comes through as text events too
+    let mut image_depth = 0;
+    for event in Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(Tag::Image { .. }) => image_depth += 1,
+            Event::End(TagEnd::Image) => image_depth -= 1,
+            Event::Text(chunk) | Event::Code(chunk) if image_depth == 0 => text.push_str(&chunk),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::CodeBlock | TagEnd::TableCell) => {
+                text.push('\n')
+            }
+            _ => {}
+        }
+    }
+    text.trim().to_string()
+}
+
+/// Words a reader sees in post markdown.
+pub fn word_count(markdown: &str) -> usize {
+    plain_text(markdown).split_whitespace().count()
+}
+
+/// Minutes it takes to read `word_count` words, rounded up; at least one.
+pub fn reading_time_minutes(word_count: usize) -> usize {
+    word_count.div_ceil(WORDS_PER_MINUTE).max(1)
+}
+
+// Length of generated excerpts, in characters
+const EXCERPT_LENGTH: usize = 200;
+
+/// An excerpt of post markdown: its plain text on one line, cut at a word
+/// boundary to about `EXCERPT_LENGTH` characters. `None` when the content
+/// has no text at all.
+pub fn excerpt(markdown: &str) -> Option<String> {
+    let text = plain_text(markdown).split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= EXCERPT_LENGTH {
+        return Some(text);
+    }
+
+    let cut: String = text.chars().take(EXCERPT_LENGTH).collect();
+    // A single word longer than the excerpt is cut mid-word
+    let cut = cut.rsplit_once(' ').map_or(cut.as_str(), |(head, _)| head);
+    Some(format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation())))
+}
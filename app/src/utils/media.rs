@@ -0,0 +1,28 @@
+use serde::Serializer;
+use std::env;
+
+/// Resolves a stored media path against `MEDIA_BASE_URL`.
+///
+/// Absolute URLs (with a scheme, or protocol-relative) are returned as is, as
+/// are all values when no base URL is configured.
+pub fn absolute_media_url(value: &str) -> String {
+    let base_url = env::var("MEDIA_BASE_URL").unwrap_or_default();
+    let base_url = base_url.trim().trim_end_matches('/');
+
+    if base_url.is_empty() || value.starts_with("//") || url::Url::parse(value).is_ok() {
+        return value.to_string();
+    }
+
+    format!("{}/{}", base_url, value.trim_start_matches('/'))
+}
+
+/// `serialize_with` helper for optional media URL fields in responses.
+pub fn serialize_media_url<S>(value: &Option<String>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(value) => serializer.serialize_some(&absolute_media_url(value)),
+        None => serializer.serialize_none(),
+    }
+}
@@ -0,0 +1,40 @@
+/// Extracts the distinct usernames mentioned as `@username` in `content`.
+///
+/// A mention must start at the beginning of the text or after a character
+/// that can't be part of a username, so email addresses aren't picked up.
+pub fn extract_mentions(content: &str) -> Vec<String> {
+    let mut usernames: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = content.char_indices().peekable();
+
+    while let Some((idx, c)) = chars.next() {
+        let at_boundary = !matches!(prev, Some(p) if is_username_char(p));
+        prev = Some(c);
+
+        if c != '@' || !at_boundary {
+            continue;
+        }
+
+        let start = idx + c.len_utf8();
+        let mut end = start;
+        while let Some(&(i, next)) = chars.peek() {
+            if !is_username_char(next) {
+                break;
+            }
+            end = i + next.len_utf8();
+            prev = Some(next);
+            chars.next();
+        }
+
+        let username = &content[start..end];
+        if (3..=50).contains(&username.len()) && !usernames.iter().any(|u| u == username) {
+            usernames.push(username.to_string());
+        }
+    }
+
+    usernames
+}
+
+fn is_username_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
@@ -1 +1,14 @@
-pub mod jwt;
\ No newline at end of file
+pub mod conditional;
+pub mod email_verification;
+pub mod fields;
+pub mod front_matter;
+pub mod jwt;
+pub mod language;
+pub mod markdown;
+pub mod media;
+pub mod mentions;
+pub mod pagination;
+pub mod password;
+pub mod tags;
+pub mod tokens;
+pub mod validation;
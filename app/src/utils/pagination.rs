@@ -0,0 +1,49 @@
+/// A clamped page of a listing, ready to bind as `LIMIT`/`OFFSET`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub page: u32,
+    pub limit: u32,
+    pub offset: i64,
+}
+
+impl Page {
+    /// Clamps the requested page and limit to the default listing bounds:
+    /// 20 items per page, at most 100.
+    pub fn new(page: Option<u32>, limit: Option<u32>) -> Self {
+        Self::with_limits(page, limit, 20, 100)
+    }
+
+    /// Clamps `page` to at least 1 and `limit` to `1..=max_limit`.
+    ///
+    /// The offset is computed in `i64`, so no page number can overflow it.
+    pub fn with_limits(page: Option<u32>, limit: Option<u32>, default_limit: u32, max_limit: u32) -> Self {
+        let page = page.unwrap_or(1).max(1);
+        let limit = limit.unwrap_or(default_limit).clamp(1, max_limit);
+        let offset = (page as i64 - 1) * limit as i64;
+        Self { page, limit, offset }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_and_zero_values_fall_back_to_the_first_page() {
+        assert_eq!(Page::new(None, None), Page { page: 1, limit: 20, offset: 0 });
+        assert_eq!(Page::new(Some(0), Some(0)), Page { page: 1, limit: 1, offset: 0 });
+    }
+
+    #[test]
+    fn limit_is_capped() {
+        assert_eq!(Page::new(Some(3), Some(1000)), Page { page: 3, limit: 100, offset: 200 });
+        assert_eq!(Page::with_limits(Some(2), None, 50, 200).offset, 50);
+        assert_eq!(Page::with_limits(None, Some(500), 50, 200).limit, 200);
+    }
+
+    #[test]
+    fn huge_pages_do_not_overflow_the_offset() {
+        assert_eq!(Page::new(Some(42949674), Some(100)).offset, 4294967300);
+        assert_eq!(Page::new(Some(u32::MAX), Some(100)).offset, (u32::MAX as i64 - 1) * 100);
+    }
+}
@@ -0,0 +1,221 @@
+use argon2::password_hash::{self, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use std::env;
+use std::fmt;
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, PartialEq)]
+pub enum PasswordMatch {
+    Invalid,
+    Valid,
+    // Correct, but the stored hash uses a legacy algorithm or predates the
+    // pepper and should be replaced
+    ValidNeedsRehash,
+}
+
+#[derive(Debug)]
+pub struct PasswordError(String);
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "password hashing failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for PasswordError {}
+
+/// A password hashing algorithm. Hashes are stored in PHC / modular crypt
+/// format, so each one starts with the identifier of the algorithm that
+/// produced it (`$argon2id$`, `$2b$`, ...).
+pub trait PasswordHasher {
+    /// Whether `password_hash` was produced by this algorithm.
+    fn owns(&self, password_hash: &str) -> bool;
+    fn hash(&self, password: &str) -> Result<String, PasswordError>;
+    fn verify(&self, password: &str, password_hash: &str) -> Result<bool, PasswordError>;
+}
+
+pub struct Argon2idHasher;
+
+impl PasswordHasher for Argon2idHasher {
+    fn owns(&self, password_hash: &str) -> bool {
+        password_hash.starts_with("$argon2id$")
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| PasswordError(e.to_string()))
+    }
+
+    fn verify(&self, password: &str, password_hash: &str) -> Result<bool, PasswordError> {
+        let parsed = PasswordHash::new(password_hash).map_err(|e| PasswordError(e.to_string()))?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(PasswordError(e.to_string())),
+        }
+    }
+}
+
+// Only kept to verify hashes created before the switch to Argon2id
+pub struct BcryptHasher;
+
+impl PasswordHasher for BcryptHasher {
+    fn owns(&self, password_hash: &str) -> bool {
+        ["$2a$", "$2b$", "$2x$", "$2y$"]
+            .iter()
+            .any(|prefix| password_hash.starts_with(prefix))
+    }
+
+    fn hash(&self, password: &str) -> Result<String, PasswordError> {
+        bcrypt::hash(password, bcrypt::DEFAULT_COST).map_err(|e| PasswordError(e.to_string()))
+    }
+
+    fn verify(&self, password: &str, password_hash: &str) -> Result<bool, PasswordError> {
+        bcrypt::verify(password, password_hash).map_err(|e| PasswordError(e.to_string()))
+    }
+}
+
+// New hashes always use the first hasher; the rest can still verify
+const CURRENT_HASHER: Argon2idHasher = Argon2idHasher;
+const LEGACY_HASHERS: [&dyn PasswordHasher; 1] = [&BcryptHasher];
+
+fn pepper() -> Option<String> {
+    env::var("PASSWORD_PEPPER").ok().filter(|pepper| !pepper.is_empty())
+}
+
+// HMAC-SHA256 of the password keyed with the pepper, hex encoded so it stays
+// well under bcrypt's 72 byte input limit
+fn apply_pepper(password: &str, pepper: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(pepper.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(password.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Hashes a password with Argon2id, peppering it first when `PASSWORD_PEPPER` is set.
+pub fn hash_password(password: &str) -> Result<String, PasswordError> {
+    hash_with_pepper(password, pepper().as_deref())
+}
+
+fn hash_with_pepper(password: &str, pepper: Option<&str>) -> Result<String, PasswordError> {
+    match pepper {
+        Some(pepper) => CURRENT_HASHER.hash(&apply_pepper(password, pepper)),
+        None => CURRENT_HASHER.hash(password),
+    }
+}
+
+/// Checks a password against a stored hash from any supported algorithm.
+/// Legacy hashes, and with a pepper configured those created before it was
+/// introduced, still verify but are flagged for rehashing.
+pub fn verify_password(password: &str, password_hash: &str) -> Result<PasswordMatch, PasswordError> {
+    verify_with_pepper(password, password_hash, pepper().as_deref())
+}
+
+fn verify_with_pepper(
+    password: &str,
+    password_hash: &str,
+    pepper: Option<&str>,
+) -> Result<PasswordMatch, PasswordError> {
+    let current = CURRENT_HASHER.owns(password_hash);
+    let hasher: &dyn PasswordHasher = if current {
+        &CURRENT_HASHER
+    } else {
+        match LEGACY_HASHERS.iter().find(|hasher| hasher.owns(password_hash)) {
+            Some(hasher) => *hasher,
+            // Unknown format, e.g. an account without a password
+            None => return Ok(PasswordMatch::Invalid),
+        }
+    };
+
+    let password_match = match pepper {
+        Some(pepper) => {
+            if hasher.verify(&apply_pepper(password, pepper), password_hash)? {
+                PasswordMatch::Valid
+            } else if hasher.verify(password, password_hash)? {
+                PasswordMatch::ValidNeedsRehash
+            } else {
+                PasswordMatch::Invalid
+            }
+        }
+        None if hasher.verify(password, password_hash)? => PasswordMatch::Valid,
+        None => PasswordMatch::Invalid,
+    };
+
+    match password_match {
+        PasswordMatch::Valid if !current => Ok(PasswordMatch::ValidNeedsRehash),
+        password_match => Ok(password_match),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEPPER: &str = "test-pepper";
+
+    #[test]
+    fn peppered_hash_verifies_with_the_pepper() {
+        let hash = hash_with_pepper("correct horse", Some(PEPPER)).unwrap();
+
+        assert_eq!(verify_with_pepper("correct horse", &hash, Some(PEPPER)).unwrap(), PasswordMatch::Valid);
+        assert_eq!(verify_with_pepper("wrong horse", &hash, Some(PEPPER)).unwrap(), PasswordMatch::Invalid);
+    }
+
+    #[test]
+    fn peppered_hash_does_not_verify_without_the_same_pepper() {
+        let hash = hash_with_pepper("correct horse", Some(PEPPER)).unwrap();
+
+        assert_eq!(verify_with_pepper("correct horse", &hash, Some("other-pepper")).unwrap(), PasswordMatch::Invalid);
+        assert_eq!(verify_with_pepper("correct horse", &hash, None).unwrap(), PasswordMatch::Invalid);
+    }
+
+    #[test]
+    fn unpeppered_hash_needs_rehash_once_a_pepper_is_set() {
+        let hash = hash_with_pepper("correct horse", None).unwrap();
+
+        assert_eq!(verify_with_pepper("correct horse", &hash, None).unwrap(), PasswordMatch::Valid);
+        assert_eq!(
+            verify_with_pepper("correct horse", &hash, Some(PEPPER)).unwrap(),
+            PasswordMatch::ValidNeedsRehash
+        );
+        assert_eq!(verify_with_pepper("wrong horse", &hash, Some(PEPPER)).unwrap(), PasswordMatch::Invalid);
+    }
+
+    #[test]
+    fn rehashed_password_verifies_with_the_pepper() {
+        let legacy = hash_with_pepper("correct horse", None).unwrap();
+        assert_eq!(
+            verify_with_pepper("correct horse", &legacy, Some(PEPPER)).unwrap(),
+            PasswordMatch::ValidNeedsRehash
+        );
+
+        // What login stores after a ValidNeedsRehash match
+        let rehashed = hash_with_pepper("correct horse", Some(PEPPER)).unwrap();
+        assert_eq!(verify_with_pepper("correct horse", &rehashed, Some(PEPPER)).unwrap(), PasswordMatch::Valid);
+    }
+
+    #[test]
+    fn bcrypt_hash_needs_rehash() {
+        let hash = bcrypt::hash("correct horse", 4).unwrap();
+
+        assert_eq!(verify_with_pepper("correct horse", &hash, None).unwrap(), PasswordMatch::ValidNeedsRehash);
+        assert_eq!(
+            verify_with_pepper("correct horse", &hash, Some(PEPPER)).unwrap(),
+            PasswordMatch::ValidNeedsRehash
+        );
+        assert_eq!(verify_with_pepper("wrong horse", &hash, None).unwrap(), PasswordMatch::Invalid);
+    }
+
+    #[test]
+    fn unknown_hash_format_is_invalid() {
+        assert_eq!(verify_with_pepper("correct horse", "", None).unwrap(), PasswordMatch::Invalid);
+        assert_eq!(verify_with_pepper("correct horse", "plaintext", Some(PEPPER)).unwrap(), PasswordMatch::Invalid);
+    }
+}
@@ -0,0 +1,29 @@
+/// Most tags a post can carry.
+pub const MAX_TAGS_PER_POST: usize = 10;
+
+/// Normalizes a tag name the way tags are stored: lowercase, with runs of
+/// whitespace turned into single hyphens, so `Web  Development` becomes
+/// `web-development`.
+pub fn normalize_tag_name(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+        .to_lowercase()
+}
+
+/// Characters a normalized tag name may contain besides letters and digits,
+/// for names such as `c++`, `c#` or `node.js`.
+pub fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '+' | '#' | '.')
+}
+
+/// Normalizes a list of tag names, dropping the ones that end up the same.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+    for name in tags.iter().map(|tag| normalize_tag_name(tag)) {
+        if !normalized.contains(&name) {
+            normalized.push(name);
+        }
+    }
+    normalized
+}
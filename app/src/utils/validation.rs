@@ -0,0 +1,265 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::env;
+use url::Url;
+use validator::{ValidationError, ValidationErrors, ValidationErrorsKind};
+
+use crate::utils::language::normalize_language;
+use crate::utils::tags::{is_tag_char, normalize_tag_name, normalize_tags, MAX_TAGS_PER_POST};
+use crate::webhooks::{is_public_ip, private_targets_allowed, EVENT_TYPES};
+
+/// Checks that an image URL points at one of the hosts in `IMAGE_HOST_ALLOWLIST`.
+///
+/// The allowlist is a comma-separated list of hosts; subdomains of a listed
+/// host are accepted too, as are the hosts uploads are served from. An empty
+/// or unset allowlist accepts any host.
+pub fn validate_image_host(value: &str) -> Result<(), ValidationError> {
+    let allowlist = env::var("IMAGE_HOST_ALLOWLIST").unwrap_or_default();
+    let mut allowed_hosts: Vec<String> = allowlist
+        .split(',')
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .collect();
+
+    if allowed_hosts.is_empty() {
+        return Ok(());
+    }
+
+    // Uploads served from MEDIA_BASE_URL or by this API are always allowed
+    for var in ["MEDIA_BASE_URL", "PUBLIC_API_URL"] {
+        if let Some(host) = env::var(var).ok().and_then(|url| Url::parse(&url).ok()?.host_str().map(str::to_string)) {
+            allowed_hosts.push(host);
+        }
+    }
+
+    let host = Url::parse(value)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_lowercase()));
+
+    match host {
+        Some(host)
+            if allowed_hosts.iter().any(|allowed| {
+                let allowed = allowed.to_lowercase();
+                host == allowed || host.ends_with(&format!(".{}", allowed))
+            }) =>
+        {
+            Ok(())
+        }
+        _ => Err(ValidationError::new("image_host_not_allowed")),
+    }
+}
+
+const MAX_TAG_LENGTH: usize = 50;
+
+/// Rejects blank tag names, names longer than the `tags.name` column allows
+/// and names with characters tags can't have, once normalized.
+pub fn validate_tag_name(name: &str) -> Result<(), ValidationError> {
+    let normalized = normalize_tag_name(name);
+    if normalized.is_empty() {
+        let mut error = ValidationError::new("tag_blank");
+        error.message = Some("Tag must not be empty or whitespace".into());
+        return Err(error);
+    }
+    if normalized.chars().count() > MAX_TAG_LENGTH {
+        let mut error = ValidationError::new("tag_too_long");
+        error.message = Some(format!("Tag must be at most {} characters", MAX_TAG_LENGTH).into());
+        error.add_param("max".into(), &MAX_TAG_LENGTH);
+        return Err(error);
+    }
+    if !normalized.chars().all(is_tag_char) || !normalized.chars().any(char::is_alphanumeric) {
+        let mut error = ValidationError::new("tag_invalid_characters");
+        error.message = Some("Tag may only contain letters, digits, hyphens, '+', '#' and '.', and needs a letter or digit".into());
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Tag slugs are lowercase letters, digits and single hyphens, as
+/// `slug::slugify` produces them.
+pub fn validate_tag_slug(slug: &str) -> Result<(), ValidationError> {
+    let valid = !slug.is_empty()
+        && slug.len() <= 60
+        && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        && !slug.starts_with('-')
+        && !slug.ends_with('-')
+        && !slug.contains("--");
+    if valid {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_slug");
+    error.message = Some("Slug must be up to 60 lowercase letters, digits and single hyphens".into());
+    Err(error)
+}
+
+/// Accepts `#rrggbb` colors, in any case.
+pub fn validate_hex_color(color: &str) -> Result<(), ValidationError> {
+    let valid = color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit());
+    if valid {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("invalid_color");
+    error.message = Some("Color must be a hex color such as #1a2b3c".into());
+    Err(error)
+}
+
+/// Validates every entry of a tags array, reporting the index of the first
+/// invalid tag in the error's `index` param, and caps how many distinct tags
+/// a post can have.
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    for (index, tag) in tags.iter().enumerate() {
+        if let Err(mut error) = validate_tag_name(tag) {
+            error.add_param("index".into(), &index);
+            error.add_param("tag".into(), tag);
+            return Err(error);
+        }
+    }
+    if normalize_tags(tags).len() > MAX_TAGS_PER_POST {
+        return Err(too_many_tags());
+    }
+    Ok(())
+}
+
+/// The error for a post that would end up with more than `MAX_TAGS_PER_POST` tags.
+pub fn too_many_tags() -> ValidationError {
+    let mut error = ValidationError::new("too_many_tags");
+    error.message = Some(format!("A post can have at most {} tags", MAX_TAGS_PER_POST).into());
+    error.add_param("max".into(), &MAX_TAGS_PER_POST);
+    error
+}
+
+/// Accepts ISO 639-1 language codes such as `en` or `fr`, in any case.
+pub fn validate_language(code: &str) -> Result<(), ValidationError> {
+    match normalize_language(code) {
+        Some(_) => Ok(()),
+        None => {
+            let mut error = ValidationError::new("unknown_language");
+            error.message = Some("Language must be an ISO 639-1 code".into());
+            Err(error)
+        }
+    }
+}
+
+/// Checks that a webhook URL is an http(s) URL without credentials and,
+/// unless `WEBHOOK_ALLOW_PRIVATE_URLS` is on, not aimed at localhost or a
+/// private address. Hostnames are checked again when delivering.
+pub fn validate_webhook_url(value: &str) -> Result<(), ValidationError> {
+    let Ok(url) = Url::parse(value) else {
+        return Err(ValidationError::new("url"));
+    };
+    if !matches!(url.scheme(), "http" | "https") || !url.username().is_empty() || url.password().is_some() {
+        return Err(ValidationError::new("webhook_url_invalid"));
+    }
+    if private_targets_allowed() {
+        return Ok(());
+    }
+
+    let private = match url.host() {
+        Some(url::Host::Domain(host)) => {
+            let host = host.to_lowercase();
+            host == "localhost" || host.ends_with(".localhost")
+        }
+        Some(url::Host::Ipv4(ip)) => !is_public_ip(ip.into()),
+        Some(url::Host::Ipv6(ip)) => !is_public_ip(ip.into()),
+        None => true,
+    };
+    if private {
+        return Err(ValidationError::new("webhook_url_private"));
+    }
+    Ok(())
+}
+
+/// Accepts the event types webhooks can subscribe to.
+pub fn validate_webhook_event_types(event_types: &[String]) -> Result<(), ValidationError> {
+    for (index, event_type) in event_types.iter().enumerate() {
+        if !EVENT_TYPES.contains(&event_type.as_str()) {
+            let mut error = ValidationError::new("unknown_event_type");
+            error.message = Some(format!("Event types must be one of: {}", EVENT_TYPES.join(", ")).into());
+            error.add_param("index".into(), &index);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+/// One failed rule for a request field.
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub params: Map<String, Value>,
+}
+
+/// Flattens `ValidationErrors` into a map from field name to the rules it
+/// failed, e.g. `{"title": [{"code": "length", "message": ...}]}`. Nested
+/// fields are keyed by their path, such as `links[0].url`.
+pub fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<FieldError>> {
+    let mut fields = BTreeMap::new();
+    collect_field_errors(errors, None, &mut fields);
+    fields
+}
+
+fn collect_field_errors(
+    errors: &ValidationErrors,
+    prefix: Option<&str>,
+    fields: &mut BTreeMap<String, Vec<FieldError>>,
+) {
+    for (field, kind) in errors.errors() {
+        let path = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, field),
+            None => field.to_string(),
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields
+                    .entry(path)
+                    .or_default()
+                    .extend(errors.iter().map(field_error));
+            }
+            ValidationErrorsKind::Struct(errors) => collect_field_errors(errors, Some(&path), fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_field_errors(errors, Some(&format!("{}[{}]", path, index)), fields);
+                }
+            }
+        }
+    }
+}
+
+fn field_error(error: &ValidationError) -> FieldError {
+    // The rejected value is left out so passwords aren't echoed back
+    let params: Map<String, Value> = error
+        .params
+        .iter()
+        .filter(|(name, _)| *name != "value")
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    let message = match &error.message {
+        Some(message) => message.to_string(),
+        None => default_message(&error.code, &params),
+    };
+
+    FieldError {
+        code: error.code.to_string(),
+        message,
+        params,
+    }
+}
+
+// validator only attaches a message when the rule declares one
+fn default_message(code: &str, params: &Map<String, Value>) -> String {
+    match (code, params.get("min"), params.get("max")) {
+        ("length", Some(min), Some(max)) => format!("Must be between {} and {} characters", min, max),
+        ("length", Some(min), None) => format!("Must be at least {} characters", min),
+        ("length", None, Some(max)) => format!("Must be at most {} characters", max),
+        ("email", _, _) => "Must be a valid email address".to_string(),
+        ("url", _, _) => "Must be a valid URL".to_string(),
+        ("image_host_not_allowed", _, _) => "Images must be hosted on an allowed host".to_string(),
+        ("webhook_url_invalid", _, _) => "Must be an http or https URL without credentials".to_string(),
+        ("webhook_url_private", _, _) => "Must not point at localhost or a private network".to_string(),
+        _ => format!("Failed the {} check", code),
+    }
+}
@@ -0,0 +1,123 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::env;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::utils::tokens::hash_token;
+
+// Expired dedup entries are only swept once this many are tracked
+const PRUNE_THRESHOLD: usize = 50_000;
+
+struct PendingView {
+    post_id: Uuid,
+    viewer_key: String,
+    viewed_at: DateTime<Utc>,
+}
+
+/// Counts post views. Repeat views by the same viewer within
+/// `POST_VIEW_DEDUP_MINUTES` (default 30) are ignored, and counted views are
+/// buffered in memory and written in batches by `jobs::post_views`.
+/// Registered as `web::Data<ViewRecorder>`.
+///
+/// Deduplication is per pod, so a viewer whose requests land on several pods
+/// can be counted once by each.
+pub struct ViewRecorder {
+    dedup_window: Duration,
+    recent: Mutex<HashMap<(Uuid, String), Instant>>,
+    pending: Mutex<Vec<PendingView>>,
+}
+
+impl ViewRecorder {
+    pub fn from_env() -> Self {
+        let dedup_minutes = env::var("POST_VIEW_DEDUP_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        Self {
+            dedup_window: Duration::from_secs(dedup_minutes * 60),
+            recent: Mutex::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records a view by `user_id` if signed in, otherwise by client address.
+    /// Authors viewing their own posts, and views nobody can be identified
+    /// for, are not counted.
+    pub fn record(&self, post_id: Uuid, author_id: Uuid, user_id: Option<Uuid>, client: Option<IpAddr>) {
+        if user_id == Some(author_id) {
+            return;
+        }
+
+        // Client addresses are hashed so the table doesn't hold them in the clear
+        let viewer_key = match (user_id, client) {
+            (Some(user_id), _) => format!("user:{}", user_id),
+            (None, Some(ip)) => format!("ip:{}", hash_token(&ip.to_string())),
+            (None, None) => return,
+        };
+
+        let now = Instant::now();
+        {
+            let mut recent = self.recent.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if recent.len() >= PRUNE_THRESHOLD {
+                recent.retain(|_, seen| now.duration_since(*seen) < self.dedup_window);
+            }
+
+            let key = (post_id, viewer_key.clone());
+            if recent
+                .get(&key)
+                .is_some_and(|seen| now.duration_since(*seen) < self.dedup_window)
+            {
+                return;
+            }
+            recent.insert(key, now);
+        }
+
+        self.pending
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(PendingView {
+                post_id,
+                viewer_key,
+                viewed_at: Utc::now(),
+            });
+    }
+
+    /// Writes the buffered views, returning how many were stored. Views of
+    /// posts deleted in the meantime are dropped.
+    pub async fn flush(&self, pool: &PgPool) -> Result<u64, sqlx::Error> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|poisoned| poisoned.into_inner()));
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let mut post_ids = Vec::with_capacity(pending.len());
+        let mut viewer_keys = Vec::with_capacity(pending.len());
+        let mut viewed_at = Vec::with_capacity(pending.len());
+        for view in pending {
+            post_ids.push(view.post_id);
+            viewer_keys.push(view.viewer_key);
+            viewed_at.push(view.viewed_at);
+        }
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO post_views (post_id, viewer_key, viewed_at)
+            SELECT v.post_id, v.viewer_key, v.viewed_at
+            FROM UNNEST($1::uuid[], $2::text[], $3::timestamptz[]) AS v(post_id, viewer_key, viewed_at)
+            WHERE EXISTS (SELECT 1 FROM posts p WHERE p.id = v.post_id)
+            "#,
+            &post_ids,
+            &viewer_keys,
+            &viewed_at
+        )
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
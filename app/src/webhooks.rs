@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::env;
+use std::net::IpAddr;
+use url::Url;
+use uuid::Uuid;
+
+use crate::events::{DomainEvent, Subscriber};
+use crate::middleware::auth::user_role;
+use crate::models::Role;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Event types a webhook can subscribe to.
+pub const EVENT_TYPES: [&str; 3] = ["post.published", "comment.created", "user.followed"];
+// Deliveries sent concurrently by one pass
+pub const BATCH_SIZE: i64 = 20;
+// Failed deliveries are retried with exponential backoff, up to this many times
+const MAX_ATTEMPTS: i32 = 10;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 7200;
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+// Response bodies recorded in last_error are cut to this many characters
+const MAX_ERROR_LENGTH: usize = 500;
+
+/// Whether webhooks may target localhost and private networks, for local
+/// development. Set with `WEBHOOK_ALLOW_PRIVATE_URLS`.
+pub fn private_targets_allowed() -> bool {
+    env::var("WEBHOOK_ALLOW_PRIVATE_URLS")
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "on" | "yes"))
+        .unwrap_or(false)
+}
+
+/// False for loopback, private, link-local and other addresses that don't
+/// reach the internet.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (ip.octets()[0] == 100 && ip.octets()[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local fc00::/7 and link-local fe80::/10
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Client deliveries are sent with: redirects aren't followed, so a webhook
+/// can't be bounced to an address it wasn't allowed to use.
+pub fn http_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent(concat!("blog-backend-webhooks/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to build the webhook HTTP client")
+}
+
+/// Hex HMAC-SHA256 of `{timestamp}.{body}`, sent as
+/// `X-Webhook-Signature: sha256=<signature>`.
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Outbox subscriber queueing a delivery for each webhook an event matches.
+/// A webhook matches events about its owner (their posts being published or
+/// commented on, and their new followers), or every event if it's site-wide.
+pub struct WebhookSubscriber {
+    pool: PgPool,
+}
+
+impl WebhookSubscriber {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl Subscriber for WebhookSubscriber {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn handle(&self, event_id: Uuid, event: &DomainEvent) -> Result<(), String> {
+        let owner_id = match event {
+            DomainEvent::PostPublished { author_id, .. } => *author_id,
+            DomainEvent::CommentCreated { post_author_id, .. } => *post_author_id,
+            DomainEvent::UserFollowed { following_id, .. } => *following_id,
+            DomainEvent::DraftExpiring { .. } => return Ok(()),
+        };
+
+        let webhooks = sqlx::query!(
+            r#"
+            SELECT w.id, w.user_id, w.site_wide
+            FROM webhooks w
+            INNER JOIN users u ON w.user_id = u.id
+            WHERE $1 = ANY(w.event_types) AND (w.user_id = $2 OR w.site_wide)
+              AND u.deactivated_at IS NULL AND u.banned_at IS NULL
+            "#,
+            event.event_type(),
+            owner_id
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        // Site-wide webhooks stop matching other users' events once their
+        // owner is no longer an admin
+        let mut webhook_ids = Vec::new();
+        for webhook in webhooks {
+            if webhook.user_id != owner_id
+                && user_role(&self.pool, webhook.user_id).await.map_err(|e| e.to_string())? != Role::Admin
+            {
+                continue;
+            }
+            webhook_ids.push(webhook.id);
+        }
+        if webhook_ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut payload = serde_json::to_value(event).map_err(|e| e.to_string())?;
+        payload["id"] = json!(event_id);
+
+        // Repeats of the event are ignored
+        sqlx::query!(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event_id, event_type, payload)
+            SELECT webhook_id, $2, $3, $4 FROM UNNEST($1::uuid[]) AS webhook_id
+            ON CONFLICT (webhook_id, event_id) DO NOTHING
+            "#,
+            &webhook_ids,
+            event_id,
+            event.event_type(),
+            Json(&payload) as _
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(())
+    }
+}
+
+/// Sends up to `BATCH_SIZE` due deliveries concurrently, returning how many
+/// were claimed. Run by `jobs::webhook_deliveries`; deliveries are locked
+/// while being sent, so every pod can run it.
+pub async fn deliver_batch(pool: &PgPool, client: &reqwest::Client) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let due = sqlx::query!(
+        r#"
+        SELECT d.id, d.event_type, d.payload, d.attempts, w.url, w.secret
+        FROM webhook_deliveries d
+        INNER JOIN webhooks w ON d.webhook_id = w.id
+        WHERE d.status = 'pending' AND d.next_attempt_at <= NOW()
+        ORDER BY d.next_attempt_at
+        LIMIT $1
+        FOR UPDATE OF d SKIP LOCKED
+        "#,
+        BATCH_SIZE
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let outcomes = join_all(due.iter().map(|delivery| {
+        send(client, delivery.id, &delivery.url, &delivery.secret, &delivery.event_type, &delivery.payload)
+    }))
+    .await;
+
+    for (delivery, outcome) in due.iter().zip(outcomes) {
+        let attempts = delivery.attempts + 1;
+        let Some(error) = outcome.error else {
+            sqlx::query!(
+                r#"
+                UPDATE webhook_deliveries
+                SET status = 'delivered', attempts = $2, response_status = $3, last_error = NULL, delivered_at = NOW()
+                WHERE id = $1
+                "#,
+                delivery.id,
+                attempts,
+                outcome.response_status
+            )
+            .execute(&mut *tx)
+            .await?;
+            continue;
+        };
+
+        let failed = attempts >= MAX_ATTEMPTS;
+        if failed {
+            log::warn!("Giving up on webhook delivery {} after {} attempts: {}", delivery.id, attempts, error);
+        }
+
+        let backoff = Duration::seconds((BASE_BACKOFF_SECS << (attempts - 1).min(16)).min(MAX_BACKOFF_SECS));
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = CASE WHEN $5 THEN 'failed' ELSE 'pending' END,
+                attempts = $2, response_status = $3, last_error = $4, next_attempt_at = $6
+            WHERE id = $1
+            "#,
+            delivery.id,
+            attempts,
+            outcome.response_status,
+            error,
+            failed,
+            Utc::now() + backoff
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(due.len())
+}
+
+struct Outcome {
+    response_status: Option<i32>,
+    // None when the endpoint answered with a 2xx
+    error: Option<String>,
+}
+
+impl Outcome {
+    fn failed(response_status: Option<i32>, error: String) -> Self {
+        Self {
+            response_status,
+            error: Some(error),
+        }
+    }
+}
+
+async fn send(
+    client: &reqwest::Client,
+    delivery_id: Uuid,
+    url: &str,
+    secret: &str,
+    event_type: &str,
+    payload: &Value,
+) -> Outcome {
+    if !private_targets_allowed() {
+        if let Err(e) = check_target(url).await {
+            return Outcome::failed(None, e);
+        }
+    }
+
+    let body = payload.to_string();
+    let timestamp = Utc::now().timestamp();
+    let response = client
+        .post(url)
+        .header(CONTENT_TYPE, "application/json")
+        // The same for every attempt, so receivers can ignore repeats
+        .header("X-Webhook-Id", delivery_id.to_string())
+        .header("X-Webhook-Event", event_type)
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header("X-Webhook-Signature", format!("sha256={}", sign(secret, timestamp, &body)))
+        .body(body)
+        .send()
+        .await;
+
+    match response {
+        Ok(response) if response.status().is_success() => Outcome {
+            response_status: Some(response.status().as_u16() as i32),
+            error: None,
+        },
+        Ok(response) => {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            let body: String = body.chars().take(MAX_ERROR_LENGTH).collect();
+            Outcome::failed(Some(status.as_u16() as i32), format!("HTTP {}: {}", status, body))
+        }
+        Err(e) => Outcome::failed(None, e.to_string()),
+    }
+}
+
+// Hostnames are resolved before each attempt so one that has been pointed at
+// a private address since it was registered is refused
+async fn check_target(url: &str) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+    // IPv6 literals are bracketed in URLs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+
+    let addresses = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("failed to resolve {}: {}", host, e))?;
+    for address in addresses {
+        if !is_public_ip(address.ip()) {
+            return Err(format!("{} resolves to a private address", host));
+        }
+    }
+    Ok(())
+}